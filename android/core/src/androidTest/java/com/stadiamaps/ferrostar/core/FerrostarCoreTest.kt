@@ -26,6 +26,7 @@ import uniffi.ferrostar.NavigationControllerConfig
 import uniffi.ferrostar.Route
 import uniffi.ferrostar.RouteAdapter
 import uniffi.ferrostar.RouteDeviation
+import uniffi.ferrostar.RouteDeviationContext
 import uniffi.ferrostar.RouteDeviationDetector
 import uniffi.ferrostar.RouteDeviationTracking
 import uniffi.ferrostar.RouteRequest
@@ -435,8 +436,7 @@ class FerrostarCoreTest {
                         object : RouteDeviationDetector {
                           override fun checkRouteDeviation(
                               location: UserLocation,
-                              route: Route,
-                              currentRouteStep: RouteStep
+                              context: RouteDeviationContext
                           ): RouteDeviation {
                             return RouteDeviation.OffRoute(42.0)
                           }
@@ -114,6 +114,7 @@ fun TripState.preferredUserLocation() =
         when (this.deviation) {
           is RouteDeviation.NoDeviation -> this.userLocation
           is RouteDeviation.OffRoute -> this.snappedUserLocation
+          is RouteDeviation.WrongWay -> this.userLocation
         }
       }
       is TripState.Idle -> this.userLocation
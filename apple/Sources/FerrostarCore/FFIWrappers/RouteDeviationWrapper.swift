@@ -3,14 +3,14 @@ import FerrostarCoreFFI
 import Foundation
 
 private final class DetectorImpl: RouteDeviationDetector {
-    let detectorFunc: @Sendable (UserLocation, Route, RouteStep) -> RouteDeviation
+    let detectorFunc: @Sendable (UserLocation, RouteDeviationContext) -> RouteDeviation
 
-    init(detectorFunc: @escaping @Sendable (UserLocation, Route, RouteStep) -> RouteDeviation) {
+    init(detectorFunc: @escaping @Sendable (UserLocation, RouteDeviationContext) -> RouteDeviation) {
         self.detectorFunc = detectorFunc
     }
 
-    func checkRouteDeviation(location: UserLocation, route: Route, currentRouteStep: RouteStep) -> RouteDeviation {
-        detectorFunc(location, route, currentRouteStep)
+    func checkRouteDeviation(location: UserLocation, context: RouteDeviationContext) -> RouteDeviation {
+        detectorFunc(location, context)
     }
 }
 
@@ -20,7 +20,27 @@ public enum SwiftRouteDeviationTracking {
 
     case staticThreshold(minimumHorizontalAccuracy: UInt16, maxAcceptableDeviation: Double)
 
-    case custom(detector: @Sendable (UserLocation, Route, RouteStep) -> RouteDeviation)
+    case staticThresholdWithWrongWay(
+        minimumHorizontalAccuracy: UInt16,
+        maxAcceptableDeviation: Double,
+        minimumCourseDifference: UInt16,
+        wrongWayFixes: UInt16
+    )
+
+    case staticThresholdWithHysteresis(
+        minimumHorizontalAccuracy: UInt16,
+        maxAcceptableDeviation: Double,
+        clearDeviation: Double,
+        minimumOffRouteFixes: UInt16,
+        minimumOffRouteDistance: Double
+    )
+
+    case defaultFor(modeOfTravel: ModeOfTravel)
+
+    case custom(detector: @Sendable (UserLocation, RouteDeviationContext) -> RouteDeviation)
+
+    /// A custom detector which returns its next iteration (including any history it keeps) from each check.
+    case statefulCustom(detector: StatefulRouteDeviationDetector)
 
     var ffiValue: FerrostarCoreFFI.RouteDeviationTracking {
         switch self {
@@ -34,8 +54,38 @@ public enum SwiftRouteDeviationTracking {
                 minimumHorizontalAccuracy: minimumHorizontalAccuracy,
                 maxAcceptableDeviation: maxAcceptableDeviation
             )
+        case let .staticThresholdWithWrongWay(
+            minimumHorizontalAccuracy: minimumHorizontalAccuracy,
+            maxAcceptableDeviation: maxAcceptableDeviation,
+            minimumCourseDifference: minimumCourseDifference,
+            wrongWayFixes: wrongWayFixes
+        ):
+            .staticThresholdWithWrongWay(
+                minimumHorizontalAccuracy: minimumHorizontalAccuracy,
+                maxAcceptableDeviation: maxAcceptableDeviation,
+                minimumCourseDifference: minimumCourseDifference,
+                wrongWayFixes: wrongWayFixes
+            )
+        case let .staticThresholdWithHysteresis(
+            minimumHorizontalAccuracy: minimumHorizontalAccuracy,
+            maxAcceptableDeviation: maxAcceptableDeviation,
+            clearDeviation: clearDeviation,
+            minimumOffRouteFixes: minimumOffRouteFixes,
+            minimumOffRouteDistance: minimumOffRouteDistance
+        ):
+            .staticThresholdWithHysteresis(
+                minimumHorizontalAccuracy: minimumHorizontalAccuracy,
+                maxAcceptableDeviation: maxAcceptableDeviation,
+                clearDeviation: clearDeviation,
+                minimumOffRouteFixes: minimumOffRouteFixes,
+                minimumOffRouteDistance: minimumOffRouteDistance
+            )
+        case let .defaultFor(modeOfTravel: modeOfTravel):
+            .defaultFor(modeOfTravel: modeOfTravel)
         case let .custom(detector: detectorFunc):
             .custom(detector: DetectorImpl(detectorFunc: detectorFunc))
+        case let .statefulCustom(detector: detector):
+            .statefulCustom(detector: detector)
         }
     }
 }
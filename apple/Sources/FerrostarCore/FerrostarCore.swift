@@ -360,8 +360,8 @@ public protocol FerrostarCoreDelegate: AnyObject {
                 annotationJson: _
             ):
                 switch deviation {
-                case .noDeviation:
-                    // No action
+                case .noDeviation, .wrongWay:
+                    // No action; travelling the wrong way along the route doesn't warrant a new route
                     break
                 case let .offRoute(deviationFromRouteLine: deviationFromRouteLine):
                     guard !self.routeRequestInFlight, // We can't have a request in flight already
@@ -115,7 +115,7 @@ public struct NavigationState: Hashable {
             switch deviation {
             case .noDeviation:
                 snappedUserLocation
-            case .offRoute:
+            case .offRoute, .wrongWay:
                 userLocation
             }
         }
@@ -10,7 +10,7 @@ use crate::{
 };
 use geo::{
     Bearing, Closest, Coord, Distance, Euclidean, Geodesic, Haversine, HaversineClosestPoint,
-    Length, LineLocatePoint, LineString, Point,
+    LineLocatePoint, LineString, Point,
 };
 
 #[cfg(test)]
@@ -57,7 +57,7 @@ pub fn index_of_closest_segment_origin(location: UserLocation, line: &LineString
 /// Get the bearing to the next point on the `LineString`.
 ///
 /// Returns [`None`] if the index points at or past the last point in the `LineString`.
-fn get_bearing_to_next_point(
+pub(crate) fn get_bearing_to_next_point(
     index_along_line: usize,
     line: &LineString,
 ) -> Option<CourseOverGround> {
@@ -113,6 +113,65 @@ pub fn snap_user_location_to_line(location: UserLocation, line: &LineString) ->
     )
 }
 
+/// The number of segments on either side of the previously known segment
+/// which are searched when snapping, before falling back to searching the whole line.
+const SNAPPING_SEARCH_WINDOW: usize = 16;
+
+/// Snaps a user location to the closest point on a line,
+/// searching near a previously known segment first.
+///
+/// Scanning every segment of a long line (ex: a motorway step which runs for hundreds of kilometers)
+/// on every location update is expensive,
+/// and the user is usually close to where they were at the last update.
+/// So when `previous_index` (the index of the previously closest segment origin) is known,
+/// only the segments near it are searched at first.
+/// If the closest of these is at the edge of the search window,
+/// the user may well be closer to a segment outside of it,
+/// so the whole line is searched instead.
+///
+/// Returns the index of the closest segment origin to the snapped location
+/// (see [`index_of_closest_segment_origin`]) along with the snapped location.
+pub(crate) fn snap_user_location_to_line_near(
+    location: UserLocation,
+    line: &LineString,
+    previous_index: Option<u64>,
+) -> (Option<u64>, UserLocation) {
+    let segment_count = line.0.len().saturating_sub(1);
+    let window = previous_index
+        .and_then(|index| usize::try_from(index).ok())
+        .filter(|index| *index < segment_count)
+        .map(|index| {
+            (
+                index.saturating_sub(SNAPPING_SEARCH_WINDOW),
+                (index + SNAPPING_SEARCH_WINDOW + 1).min(segment_count),
+            )
+        })
+        // Don't bother with a window covering the whole line.
+        .filter(|(start, end)| end - start < segment_count);
+
+    if let Some((start, end)) = window {
+        let window_line = LineString::from(line.0[start..=end].to_vec());
+        let closest_index = index_of_closest_segment_origin(location, &window_line)
+            .and_then(|index| usize::try_from(index).ok());
+        if let Some(closest_index) = closest_index {
+            let is_at_window_edge = (closest_index == 0 && start > 0)
+                || (start + closest_index + 1 == end && end < segment_count);
+            if !is_at_window_edge {
+                let snapped_location = snap_user_location_to_line(location, &window_line);
+                let index = index_of_closest_segment_origin(snapped_location, &window_line)
+                    .map(|index| index + start as u64);
+                return (index, snapped_location);
+            }
+        }
+    }
+
+    let snapped_location = snap_user_location_to_line(location, line);
+    (
+        index_of_closest_segment_origin(snapped_location, line),
+        snapped_location,
+    )
+}
+
 /// Internal function that truncates a float to 6 digits.
 ///
 /// Note that this approach is not a substitute for fixed precision decimals,
@@ -244,42 +303,59 @@ pub(crate) fn advance_step(remaining_steps: &[RouteStep]) -> StepAdvanceStatus {
     }
 }
 
+/// Computes the cumulative distance (in meters) along a linestring to each of its coordinates.
+///
+/// The first value is always zero (unless the linestring is empty),
+/// and the last is the length of the linestring.
+pub(crate) fn cumulative_distances(linestring: &LineString) -> Vec<f64> {
+    let mut total = 0.0;
+    let mut previous_point: Option<Point> = None;
+    linestring
+        .points()
+        .map(|point| {
+            if let Some(previous_point) = previous_point {
+                total += Haversine.distance(previous_point, point);
+            }
+            previous_point = Some(point);
+            total
+        })
+        .collect()
+}
+
 /// Computes the distance that a point lies along a linestring,
 /// assuming that units are latitude and longitude for the geometries.
 ///
+/// `cumulative_distances` must be the result of [`cumulative_distances`] for the linestring.
+///
 /// The result is given in meters.
 /// The result may be [`None`] in case of invalid input such as infinite floats.
-fn distance_along(point: &Point, linestring: &LineString) -> Option<f64> {
-    let total_length = Haversine.length(linestring);
+pub(crate) fn distance_along(
+    point: &Point,
+    linestring: &LineString,
+    cumulative_distances: &[f64],
+) -> Option<f64> {
+    let total_length = cumulative_distances.last().copied().unwrap_or(0.0);
     if total_length == 0.0 {
         return Some(0.0);
     }
 
-    let (_, _, traversed) = linestring.lines().try_fold(
-        (0f64, f64::INFINITY, 0f64),
-        |(cum_length, closest_dist_to_point, traversed), segment| {
-            // Convert to a LineString so we get haversine ops
-            let segment_linestring = LineString::from(segment);
-
+    let (_, traversed) = linestring.lines().zip(cumulative_distances).try_fold(
+        (f64::INFINITY, 0f64),
+        |(closest_dist_to_point, traversed), (segment, cum_length)| {
             // Compute distance to the line (sadly Euclidean only; no haversine_distance in GeoRust
             // but this is probably OK for now)
             let segment_distance_to_point = Euclidean.distance(&segment, point);
-            // Compute total segment length in meters
-            let segment_length = Haversine.length(&segment_linestring);
 
             if segment_distance_to_point < closest_dist_to_point {
                 let segment_fraction = segment.line_locate_point(point)?;
+                // Total segment length in meters
+                let segment_length = Haversine.distance(segment.start_point(), segment.end_point());
                 Some((
-                    cum_length + segment_length,
                     segment_distance_to_point,
                     cum_length + segment_fraction * segment_length,
                 ))
             } else {
-                Some((
-                    cum_length + segment_length,
-                    closest_dist_to_point,
-                    traversed,
-                ))
+                Some((closest_dist_to_point, traversed))
             }
         },
     )?;
@@ -295,9 +371,14 @@ fn travel_distance_to_end_of_step(
     snapped_location: &Point,
     current_step_linestring: &LineString,
 ) -> Option<f64> {
-    let step_length = Haversine.length(current_step_linestring);
-    distance_along(snapped_location, current_step_linestring)
-        .map(|traversed| step_length - traversed)
+    let cumulative_distances = cumulative_distances(current_step_linestring);
+    let step_length = cumulative_distances.last().copied().unwrap_or(0.0);
+    distance_along(
+        snapped_location,
+        current_step_linestring,
+        &cumulative_distances,
+    )
+    .map(|traversed| step_length - traversed)
 }
 
 /// Calculates the distance (in meters) between two user locations.
@@ -317,6 +398,20 @@ pub fn calculate_trip_progress(
     snapped_location: &Point,
     current_step_linestring: &LineString,
     remaining_steps: &[RouteStep],
+) -> TripProgress {
+    trip_progress_from_distance_to_next_maneuver(
+        travel_distance_to_end_of_step(snapped_location, current_step_linestring),
+        remaining_steps,
+    )
+}
+
+/// Computes the user's progress along the current trip,
+/// given the travel distance to the end of the current step (if it could be determined).
+///
+/// NOTE to callers: `remaining_steps` includes the current step!
+pub(crate) fn trip_progress_from_distance_to_next_maneuver(
+    distance_to_next_maneuver: Option<f64>,
+    remaining_steps: &[RouteStep],
 ) -> TripProgress {
     let Some(current_step) = remaining_steps.first() else {
         return TripProgress {
@@ -327,9 +422,7 @@ pub fn calculate_trip_progress(
     };
 
     // Calculate the distance and duration till the end of the current route step.
-    let distance_to_next_maneuver =
-        travel_distance_to_end_of_step(snapped_location, current_step_linestring)
-            .unwrap_or(current_step.distance);
+    let distance_to_next_maneuver = distance_to_next_maneuver.unwrap_or(current_step.distance);
 
     // This could be improved with live traffic data along the route.
     // TODO: Figure out the best way to enable this use case
@@ -665,6 +758,90 @@ mod bearing_snapping_tests {
     }
 }
 
+#[cfg(test)]
+mod windowed_snapping_tests {
+
+    use super::*;
+
+    /// A line heading east along the equator, with a point every ~11m.
+    fn long_line() -> LineString {
+        LineString::new(
+            (0..1_000)
+                .map(|index| coord!(x: f64::from(index) * 0.0001, y: 0.0))
+                .collect(),
+        )
+    }
+
+    fn snap_fully(location: UserLocation, line: &LineString) -> (Option<u64>, UserLocation) {
+        let snapped_location = snap_user_location_to_line(location, line);
+        (
+            index_of_closest_segment_origin(snapped_location, line),
+            snapped_location,
+        )
+    }
+
+    #[test]
+    fn test_windowed_snapping_matches_full_search_near_previous_index() {
+        let line = long_line();
+        let location = make_user_location(coord!(x: 0.05005, y: 0.0001), 5.0);
+
+        let (index, snapped_location) = snap_user_location_to_line_near(location, &line, Some(495));
+        assert_eq!(index, Some(500));
+        assert_eq!((index, snapped_location), snap_fully(location, &line));
+    }
+
+    #[test]
+    fn test_windowed_snapping_falls_back_to_full_search() {
+        let line = long_line();
+        let location = make_user_location(coord!(x: 0.09005, y: 0.0001), 5.0);
+
+        for previous_index in [None, Some(0), Some(500), Some(10_000)] {
+            let (index, snapped_location) =
+                snap_user_location_to_line_near(location, &line, previous_index);
+            assert_eq!(index, Some(900));
+            assert_eq!((index, snapped_location), snap_fully(location, &line));
+        }
+    }
+
+    #[test]
+    fn test_windowed_snapping_at_ends_of_line() {
+        let line = long_line();
+
+        let start = make_user_location(coord!(x: -0.001, y: 0.0), 5.0);
+        assert_eq!(
+            snap_user_location_to_line_near(start, &line, Some(3)),
+            snap_fully(start, &line)
+        );
+
+        let end = make_user_location(coord!(x: 0.2, y: 0.0), 5.0);
+        assert_eq!(
+            snap_user_location_to_line_near(end, &line, Some(995)),
+            snap_fully(end, &line)
+        );
+    }
+
+    #[test]
+    fn test_windowed_snapping_prefers_segments_near_previous_index() {
+        // An out and back line, where the return leg is very slightly closer to the location.
+        let mut coords: Vec<Coord> = (0..100)
+            .map(|index| coord!(x: f64::from(index) * 0.0001, y: 0.0))
+            .collect();
+        coords.extend(
+            (0..100)
+                .rev()
+                .map(|index| coord!(x: f64::from(index) * 0.0001, y: 0.000_001)),
+        );
+        let line = LineString::new(coords);
+        let location = make_user_location(coord!(x: 0.00505, y: 0.0001), 5.0);
+
+        let (index, _) = snap_user_location_to_line_near(location, &line, Some(48));
+        assert_eq!(index, Some(50));
+
+        let (index, _) = snap_user_location_to_line_near(location, &line, None);
+        assert_eq!(index, Some(148));
+    }
+}
+
 // TODO: Other unit tests
 // - Under and over distance accuracy thresholds
 // - Equator and extreme latitude
@@ -12,21 +12,23 @@
 //! When architecting a Ferrostar core integration for a new platform,
 //! we suggest enforcing a similar separation of concerns.
 
-use crate::algorithms::deviation_from_line;
+use crate::algorithms::{
+    deviation_from_line, get_bearing_to_next_point, index_of_closest_segment_origin,
+};
 use crate::models::{Route, RouteStep, UserLocation};
+use crate::navigation_controller::models::{TripProgress, TripState};
+use crate::navigation_controller::route_geometry::RouteGeometry;
 #[cfg(feature = "alloc")]
-use alloc::sync::Arc;
-use geo::Point;
+use alloc::{sync::Arc, vec::Vec};
+use geo::{Distance, Haversine, LineString, Point};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "wasm-bindgen")]
 use tsify::Tsify;
 
 #[cfg(test)]
 use {
-    crate::{
-        models::GeographicCoordinate,
-        navigation_controller::test_helpers::{gen_dummy_route_step, gen_route_from_steps},
-    },
+    crate::models::GeographicCoordinate,
+    crate::navigation_controller::test_helpers::{gen_dummy_route_step, gen_route_from_steps},
     proptest::prelude::*,
 };
 
@@ -56,60 +58,529 @@ pub enum RouteDeviationTracking {
         /// is greater than this threshold, it will be flagged as an off route condition.
         max_acceptable_deviation: f64,
     },
-    // TODO: Standard variants that account for mode of travel. For example, `DefaultFor(modeOfTravel: ModeOfTravel)` with sensible defaults for walking, driving, cycling, etc.
+    /// Detects deviation from the route like [`RouteDeviationTracking::StaticThreshold`],
+    /// and additionally detects when the user is travelling against the direction of the route
+    /// while still close to the route line (ex: after making a U-turn).
+    ///
+    /// Wrong-way travel is detected by comparing the course over ground
+    /// with the bearing of the closest route segment.
+    /// Locations without a course, or which are moving too slowly for the course to be reliable,
+    /// are ignored.
+    #[cfg_attr(feature = "wasm-bindgen", serde(rename_all = "camelCase"))]
+    StaticThresholdWithWrongWay {
+        /// The minimum required horizontal accuracy of the user location, in meters.
+        /// Values larger than this will not trigger route deviation warnings.
+        minimum_horizontal_accuracy: u16,
+        /// The maximum acceptable deviation from the route line, in meters.
+        ///
+        /// If the distance between the reported location and the expected route line
+        /// is greater than this threshold, it will be flagged as an off route condition.
+        max_acceptable_deviation: f64,
+        /// The minimum difference between the course over ground and the bearing of the route,
+        /// in degrees, for a location to count as travelling the wrong way.
+        minimum_course_difference: u16,
+        /// The number of consecutive locations which must be travelling the wrong way
+        /// before it is reported.
+        wrong_way_fixes: u16,
+    },
+    /// Detects deviation from the route like [`RouteDeviationTracking::StaticThreshold`],
+    /// but debounces the result so that a single bad location doesn't flip the deviation status.
+    ///
+    /// The user must stay beyond the threshold for a number of consecutive locations
+    /// (or a distance) before the deviation is reported.
+    /// Once reported, the deviation is only cleared when the user is back within a smaller threshold.
+    #[cfg_attr(feature = "wasm-bindgen", serde(rename_all = "camelCase"))]
+    StaticThresholdWithHysteresis {
+        /// The minimum required horizontal accuracy of the user location, in meters.
+        /// Values larger than this will not change the deviation status.
+        minimum_horizontal_accuracy: u16,
+        /// The maximum acceptable deviation from the route line, in meters.
+        max_acceptable_deviation: f64,
+        /// Once off route, the deviation from the route line (in meters)
+        /// must be at most this value before the user is considered back on the route.
+        ///
+        /// This should be smaller than `max_acceptable_deviation`;
+        /// larger values are treated as `max_acceptable_deviation`.
+        clear_deviation: f64,
+        /// The number of consecutive locations beyond `max_acceptable_deviation`
+        /// before the deviation is reported.
+        minimum_off_route_fixes: u16,
+        /// The distance travelled beyond `max_acceptable_deviation` (in meters)
+        /// before the deviation is reported, even if there were fewer than `minimum_off_route_fixes` locations.
+        ///
+        /// This is the length of the path through the locations beyond the threshold,
+        /// not the straight-line distance from the first one.
+        minimum_off_route_distance: f64,
+    },
+    /// Detects deviation from the route using thresholds tuned for the given mode of travel.
+    ///
+    /// In addition to a static distance threshold and accuracy gate,
+    /// the user must be off the route for a minimum time or distance
+    /// before it is reported, so that brief location glitches are ignored.
+    #[cfg_attr(feature = "wasm-bindgen", serde(rename_all = "camelCase"))]
+    DefaultFor { mode_of_travel: ModeOfTravel },
     /// An arbitrary user-defined implementation.
     /// You decide with your own [`RouteDeviationDetector`] implementation!
     #[serde(skip)]
     Custom {
         detector: Arc<dyn RouteDeviationDetector>,
     },
+    /// An arbitrary user-defined implementation which keeps state between location updates.
+    ///
+    /// The detector given here is used for the first check;
+    /// after that, each check uses the next iteration returned by the previous one.
+    #[serde(skip)]
+    StatefulCustom {
+        detector: Arc<dyn StatefulRouteDeviationDetector>,
+    },
 }
 
 impl RouteDeviationTracking {
+    /// Checks whether the user has deviated from the route.
+    ///
+    /// Returns the deviation along with the detection state to use for the next location update.
     #[must_use]
+    #[allow(clippy::too_many_lines)]
     pub(crate) fn check_route_deviation(
         &self,
         location: UserLocation,
-        route: &Route,
-        current_route_step: &RouteStep,
-    ) -> RouteDeviation {
+        context: &Arc<RouteDeviationContext>,
+        state: &RouteDeviationState,
+    ) -> (RouteDeviation, RouteDeviationState) {
+        if context.current_route_step_ref().is_none() {
+            return (RouteDeviation::NoDeviation, state.clone());
+        }
+        let route_line = || route_line_near_current_step(location, context);
+
         match self {
-            RouteDeviationTracking::None => RouteDeviation::NoDeviation,
+            RouteDeviationTracking::None => (RouteDeviation::NoDeviation, state.clone()),
             RouteDeviationTracking::StaticThreshold {
                 minimum_horizontal_accuracy,
                 max_acceptable_deviation,
+            } => (
+                static_threshold_deviation(
+                    location,
+                    route_line(),
+                    *minimum_horizontal_accuracy,
+                    *max_acceptable_deviation,
+                ),
+                state.clone(),
+            ),
+            RouteDeviationTracking::StaticThresholdWithWrongWay {
+                minimum_horizontal_accuracy,
+                max_acceptable_deviation,
+                minimum_course_difference,
+                wrong_way_fixes,
             } => {
-                if location.horizontal_accuracy < f64::from(*minimum_horizontal_accuracy) {
-                    // Check if the deviation from the route line is within tolerance,
-                    // after sanity checking that the positioning signal is within accuracy tolerance.
-                    deviation_from_line(
-                        &Point::from(location),
-                        &current_route_step.get_linestring(),
-                    )
-                    .map_or(RouteDeviation::NoDeviation, |deviation| {
-                        if deviation > 0.0 && deviation > *max_acceptable_deviation {
-                            RouteDeviation::OffRoute {
-                                deviation_from_route_line: deviation,
-                            }
-                        } else {
-                            RouteDeviation::NoDeviation
-                        }
-                    })
-                } else {
-                    RouteDeviation::NoDeviation
+                let route_line = route_line();
+                let deviation = static_threshold_deviation(
+                    location,
+                    route_line,
+                    *minimum_horizontal_accuracy,
+                    *max_acceptable_deviation,
+                );
+                if location.horizontal_accuracy >= f64::from(*minimum_horizontal_accuracy) {
+                    // Inaccurate locations neither confirm nor refute wrong-way travel.
+                    return (deviation, state.clone());
                 }
+                wrong_way_deviation(
+                    location,
+                    route_line,
+                    state,
+                    deviation,
+                    *minimum_course_difference,
+                    *wrong_way_fixes,
+                )
             }
-            RouteDeviationTracking::Custom { detector } => {
-                detector.check_route_deviation(location, route.clone(), current_route_step.clone())
+            RouteDeviationTracking::StaticThresholdWithHysteresis {
+                minimum_horizontal_accuracy,
+                max_acceptable_deviation,
+                clear_deviation,
+                minimum_off_route_fixes,
+                minimum_off_route_distance,
+            } => {
+                let Some(deviation_from_route_line) =
+                    deviation_from_line(&Point::from(location), route_line())
+                else {
+                    return (RouteDeviation::NoDeviation, state.clone());
+                };
+                let is_accurate =
+                    location.horizontal_accuracy < f64::from(*minimum_horizontal_accuracy);
+
+                if state.off_route_reported {
+                    let clear_deviation = clear_deviation.min(*max_acceptable_deviation);
+                    if is_accurate && deviation_from_route_line <= clear_deviation {
+                        return (RouteDeviation::NoDeviation, RouteDeviationState::default());
+                    }
+                    return (
+                        RouteDeviation::OffRoute {
+                            deviation_from_route_line,
+                        },
+                        state.clone(),
+                    );
+                }
+                if !is_accurate {
+                    return (RouteDeviation::NoDeviation, state.clone());
+                }
+                if deviation_from_route_line <= *max_acceptable_deviation {
+                    return (RouteDeviation::NoDeviation, RouteDeviationState::default());
+                }
+
+                debounce_off_route(
+                    location,
+                    state,
+                    deviation_from_route_line,
+                    *minimum_off_route_fixes,
+                    *minimum_off_route_distance,
+                )
+            }
+            RouteDeviationTracking::DefaultFor { mode_of_travel } => DeviationPreset::for_mode(
+                *mode_of_travel,
+            )
+            .check_route_deviation(location, route_line(), state),
+            RouteDeviationTracking::Custom { detector } => (
+                detector.check_route_deviation(location, Arc::clone(context)),
+                state.clone(),
+            ),
+            RouteDeviationTracking::StatefulCustom { detector } => {
+                let result = state
+                    .custom_detector
+                    .as_ref()
+                    .unwrap_or(detector)
+                    .check_route_deviation(location, Arc::clone(context));
+                (result.deviation, RouteDeviationState::from(result))
+            }
+        }
+    }
+}
+
+/// Finds the part of the route line that deviation is measured against:
+/// whichever of the current step and the steps immediately before and after it
+/// is closest to the location.
+///
+/// Around maneuvers, the user may already be on the next step (or still on the previous one)
+/// before the current step changes, and should not be considered off route.
+///
+/// The caller must ensure that there is a current step.
+fn route_line_near_current_step(
+    location: UserLocation,
+    context: &RouteDeviationContext,
+) -> &LineString {
+    let point = Point::from(location);
+    let current_step_index = context.current_step_index;
+    let current_step = context
+        .route
+        .step(current_step_index)
+        .expect("Expected a current step");
+
+    let mut nearest_line = current_step.linestring();
+    let mut nearest_deviation = deviation_from_line(&point, nearest_line);
+    let adjacent_steps = [
+        current_step_index.checked_sub(1),
+        Some(current_step_index + 1),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|index| context.route.step(index));
+    for step in adjacent_steps {
+        // Skip steps which can't be any closer than the nearest one so far.
+        if let (Some(nearest_deviation), Some(minimum_distance)) =
+            (nearest_deviation, step.minimum_distance_to(&point))
+        {
+            if minimum_distance > nearest_deviation {
+                continue;
             }
         }
+        if let Some(deviation) = deviation_from_line(&point, step.linestring()) {
+            if nearest_deviation.is_none_or(|nearest_deviation| deviation < nearest_deviation) {
+                nearest_line = step.linestring();
+                nearest_deviation = Some(deviation);
+            }
+        }
+    }
+    nearest_line
+}
+
+/// The minimum speed (in meters per second) at which the course over ground
+/// is considered reliable enough for wrong-way detection.
+const MINIMUM_WRONG_WAY_SPEED: f64 = 1.0;
+
+fn static_threshold_deviation(
+    location: UserLocation,
+    route_line: &LineString,
+    minimum_horizontal_accuracy: u16,
+    max_acceptable_deviation: f64,
+) -> RouteDeviation {
+    if location.horizontal_accuracy < f64::from(minimum_horizontal_accuracy) {
+        // Check if the deviation from the route line is within tolerance,
+        // after sanity checking that the positioning signal is within accuracy tolerance.
+        deviation_from_line(&Point::from(location), route_line).map_or(
+            RouteDeviation::NoDeviation,
+            |deviation| {
+                if deviation > 0.0 && deviation > max_acceptable_deviation {
+                    RouteDeviation::OffRoute {
+                        deviation_from_route_line: deviation,
+                    }
+                } else {
+                    RouteDeviation::NoDeviation
+                }
+            },
+        )
+    } else {
+        RouteDeviation::NoDeviation
+    }
+}
+
+/// Counts a location beyond the deviation threshold towards reporting that the user is off route.
+fn debounce_off_route(
+    location: UserLocation,
+    state: &RouteDeviationState,
+    deviation_from_route_line: f64,
+    minimum_off_route_fixes: u16,
+    minimum_off_route_distance: f64,
+) -> (RouteDeviation, RouteDeviationState) {
+    let next_state = state.off_route(location);
+    let off_route_reported = next_state.off_route_fixes >= minimum_off_route_fixes
+        || next_state.distance_off_route >= minimum_off_route_distance;
+    let next_state = RouteDeviationState {
+        off_route_reported,
+        ..next_state
+    };
+
+    if off_route_reported {
+        (
+            RouteDeviation::OffRoute {
+                deviation_from_route_line,
+            },
+            next_state,
+        )
+    } else {
+        (RouteDeviation::NoDeviation, next_state)
+    }
+}
+
+/// Tracks wrong-way travel for locations which are accurate enough,
+/// given the result of the static threshold check.
+fn wrong_way_deviation(
+    location: UserLocation,
+    route_line: &LineString,
+    state: &RouteDeviationState,
+    deviation: RouteDeviation,
+    minimum_course_difference: u16,
+    wrong_way_fixes: u16,
+) -> (RouteDeviation, RouteDeviationState) {
+    if deviation != RouteDeviation::NoDeviation {
+        return (deviation, RouteDeviationState::default());
+    }
+
+    let Some(course_difference) = course_difference_from_route(location, route_line) else {
+        return (deviation, state.clone());
+    };
+    if course_difference < f64::from(minimum_course_difference) {
+        return (deviation, RouteDeviationState::default());
+    }
+
+    let window_size = usize::from(wrong_way_fixes.max(1));
+    let mut differences = state.wrong_way_course_differences.clone();
+    differences.push(course_difference);
+    if differences.len() > window_size {
+        differences.drain(..differences.len() - window_size);
+    }
+
+    let next_state = RouteDeviationState {
+        wrong_way_course_differences: differences,
+        ..RouteDeviationState::default()
+    };
+    if next_state.wrong_way_course_differences.len() < window_size {
+        return (deviation, next_state);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let course_difference =
+        next_state.wrong_way_course_differences.iter().sum::<f64>() / window_size as f64;
+    (RouteDeviation::WrongWay { course_difference }, next_state)
+}
+
+/// Computes the difference (in degrees, from 0 to 180) between the user's course over ground
+/// and the bearing of the closest segment of the route line.
+///
+/// Returns [`None`] if the location has no course, or is moving too slowly for it to be reliable.
+fn course_difference_from_route(location: UserLocation, route_line: &LineString) -> Option<f64> {
+    let course = location.course_over_ground?;
+    if location
+        .speed
+        .is_some_and(|speed| speed.value < MINIMUM_WRONG_WAY_SPEED)
+    {
+        return None;
+    }
+
+    let index = index_of_closest_segment_origin(location, route_line)?;
+    let route_bearing = get_bearing_to_next_point(usize::try_from(index).ok()?, route_line)?;
+
+    let difference = (i32::from(course.degrees) - i32::from(route_bearing.degrees)).abs() % 360;
+    Some(f64::from(difference.min(360 - difference)))
+}
+
+/// A mode of travel, used to select sensible route deviation tracking defaults.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(into_wasm_abi, from_wasm_abi))]
+pub enum ModeOfTravel {
+    Walking,
+    Cycling,
+    Driving,
+    /// Travel on a fixed route vehicle like a bus or train,
+    /// where location updates are often less accurate.
+    Transit,
+}
+
+/// Route deviation thresholds for a [`ModeOfTravel`].
+struct DeviationPreset {
+    /// The minimum required horizontal accuracy of the user location, in meters.
+    minimum_horizontal_accuracy: u16,
+    /// The maximum acceptable deviation from the route line, in meters.
+    max_acceptable_deviation: f64,
+    /// The minimum time (in seconds) the user must be off the route before it is reported.
+    minimum_time_off_route: f64,
+    /// The minimum distance (in meters) the user must travel off the route before it is reported.
+    minimum_distance_off_route: f64,
+}
+
+impl DeviationPreset {
+    fn for_mode(mode_of_travel: ModeOfTravel) -> Self {
+        match mode_of_travel {
+            // Pedestrians move slowly and routinely cut across plazas and parking lots,
+            // so we wait a bit longer before deciding they've gone a different way.
+            ModeOfTravel::Walking => Self {
+                minimum_horizontal_accuracy: 30,
+                max_acceptable_deviation: 25.0,
+                minimum_time_off_route: 10.0,
+                minimum_distance_off_route: 20.0,
+            },
+            ModeOfTravel::Cycling => Self {
+                minimum_horizontal_accuracy: 25,
+                max_acceptable_deviation: 30.0,
+                minimum_time_off_route: 6.0,
+                minimum_distance_off_route: 30.0,
+            },
+            ModeOfTravel::Driving => Self {
+                minimum_horizontal_accuracy: 25,
+                max_acceptable_deviation: 50.0,
+                minimum_time_off_route: 4.0,
+                minimum_distance_off_route: 50.0,
+            },
+            // Vehicles on fixed routes rarely leave them,
+            // and locations from inside them are often poor.
+            ModeOfTravel::Transit => Self {
+                minimum_horizontal_accuracy: 50,
+                max_acceptable_deviation: 100.0,
+                minimum_time_off_route: 30.0,
+                minimum_distance_off_route: 200.0,
+            },
+        }
+    }
+
+    fn check_route_deviation(
+        &self,
+        location: UserLocation,
+        route_line: &LineString,
+        state: &RouteDeviationState,
+    ) -> (RouteDeviation, RouteDeviationState) {
+        let deviation = static_threshold_deviation(
+            location,
+            route_line,
+            self.minimum_horizontal_accuracy,
+            self.max_acceptable_deviation,
+        );
+        if location.horizontal_accuracy >= f64::from(self.minimum_horizontal_accuracy) {
+            // Inaccurate locations neither confirm nor refute being off route.
+            return (deviation, state.clone());
+        }
+        if deviation == RouteDeviation::NoDeviation {
+            return (deviation, RouteDeviationState::default());
+        }
+
+        let next_state = state.off_route(location);
+        if self.has_been_off_route_long_enough(&next_state, &location) {
+            (deviation, next_state)
+        } else {
+            (RouteDeviation::NoDeviation, next_state)
+        }
+    }
+
+    /// Whether the user has been off the route for long enough (in time or distance) to report it.
+    fn has_been_off_route_long_enough(
+        &self,
+        state: &RouteDeviationState,
+        location: &UserLocation,
+    ) -> bool {
+        let time_off_route = state.off_route_since.map_or(0.0, |off_route_since| {
+            location
+                .timestamp
+                .duration_since(off_route_since.timestamp)
+                .map_or(0.0, |duration| duration.as_secs_f64())
+        });
+
+        time_off_route >= self.minimum_time_off_route
+            || state.distance_off_route >= self.minimum_distance_off_route
+    }
+}
+
+/// State carried between location updates for route deviation detection.
+///
+/// This is stored in the [`NavState`](crate::navigation_controller::models::NavState)
+/// so that detection can look at more than a single location
+/// while the navigation controller remains functionally pure.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", serde(rename_all = "camelCase"))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct RouteDeviationState {
+    /// The differences between the course over ground and the bearing of the route (in degrees)
+    /// for the most recent consecutive locations travelling the wrong way.
+    wrong_way_course_differences: Vec<f64>,
+    /// The first location in the current stretch of being off the route.
+    off_route_since: Option<UserLocation>,
+    /// The most recent location in the current stretch of being off the route.
+    last_off_route_location: Option<UserLocation>,
+    /// The distance travelled (in meters) between the locations
+    /// in the current stretch of being off the route.
+    distance_off_route: f64,
+    /// The number of consecutive locations in the current stretch of being off the route.
+    off_route_fixes: u16,
+    /// Whether the current stretch of being off the route has been reported.
+    off_route_reported: bool,
+    /// The current iteration of a [`StatefulRouteDeviationDetector`].
+    #[serde(skip)]
+    custom_detector: Option<Arc<dyn StatefulRouteDeviationDetector>>,
+}
+
+impl RouteDeviationState {
+    /// Adds a location to the current stretch of being off the route.
+    fn off_route(&self, location: UserLocation) -> Self {
+        let distance_off_route = self.last_off_route_location.map_or(0.0, |last| {
+            self.distance_off_route
+                + Haversine.distance(
+                    Point::from(last.coordinates),
+                    Point::from(location.coordinates),
+                )
+        });
+
+        Self {
+            off_route_since: Some(self.off_route_since.unwrap_or(location)),
+            last_off_route_location: Some(location),
+            distance_off_route,
+            off_route_fixes: self.off_route_fixes.saturating_add(1),
+            ..Self::default()
+        }
     }
 }
 
 /// Status information that describes whether the user is proceeding according to the route or not.
 ///
 /// Note that the name is intentionally a bit generic to allow for expansion of other states.
-/// For example, we could conceivably add a "wrong way" status in the future.
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
 #[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
@@ -123,13 +594,122 @@ pub enum RouteDeviation {
         /// The deviation from the route line, in meters.
         deviation_from_route_line: f64,
     },
+    /// The user is close to the route line, but travelling against the direction of the route.
+    #[cfg_attr(feature = "wasm-bindgen", serde(rename_all = "camelCase"))]
+    WrongWay {
+        /// The average difference between the course over ground and the bearing of the route,
+        /// in degrees.
+        course_difference: f64,
+    },
+}
+
+/// The user's position along the route, given to custom deviation detectors.
+///
+/// This is shared with detectors by reference rather than copying the route for every check.
+/// Foreign code only pays for copying the parts that it asks for.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct RouteDeviationContext {
+    route: Arc<RouteGeometry>,
+    current_step_index: usize,
+    snapped_location: UserLocation,
+    current_step_geometry_index: Option<u64>,
+    distance_along_route: f64,
+    previous_trip_state: Option<TripState>,
+}
+
+impl RouteDeviationContext {
+    pub(crate) fn new(
+        route: Arc<RouteGeometry>,
+        current_step_index: usize,
+        snapped_location: UserLocation,
+        current_step_geometry_index: Option<u64>,
+        progress: &TripProgress,
+        previous_trip_state: Option<TripState>,
+    ) -> Self {
+        let completed_steps_distance = route.step_start_distance(current_step_index);
+        let current_step_distance = route
+            .route()
+            .steps
+            .get(current_step_index)
+            .map_or(0.0, |step| {
+                (step.distance - progress.distance_to_next_maneuver).max(0.0)
+            });
+        let distance_along_route = completed_steps_distance + current_step_distance;
+
+        Self {
+            route,
+            current_step_index,
+            snapped_location,
+            current_step_geometry_index,
+            distance_along_route,
+            previous_trip_state,
+        }
+    }
+
+    /// The route being navigated, without copying it.
+    pub fn route_ref(&self) -> &Route {
+        self.route.route()
+    }
+
+    /// The current route step, without copying it.
+    pub fn current_route_step_ref(&self) -> Option<&RouteStep> {
+        self.route_ref().steps.get(self.current_step_index)
+    }
+
+    /// The trip state before this location update, without copying it.
+    pub fn previous_trip_state_ref(&self) -> Option<&TripState> {
+        self.previous_trip_state.as_ref()
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl RouteDeviationContext {
+    /// The route being navigated.
+    ///
+    /// NOTE: This copies the entire route; prefer the more specific accessors where possible.
+    pub fn route(&self) -> Route {
+        self.route_ref().clone()
+    }
+
+    /// The current route step.
+    pub fn current_route_step(&self) -> Option<RouteStep> {
+        self.current_route_step_ref().cloned()
+    }
+
+    /// The index of the current step within the route's steps.
+    pub fn current_step_index(&self) -> u64 {
+        self.current_step_index as u64
+    }
+
+    /// The user's location, snapped to the current route step.
+    pub fn snapped_location(&self) -> UserLocation {
+        self.snapped_location
+    }
+
+    /// The index of the closest segment origin to the snapped location within the current step's geometry.
+    pub fn current_step_geometry_index(&self) -> Option<u64> {
+        self.current_step_geometry_index
+    }
+
+    /// The distance (in meters) along the route to the snapped location.
+    pub fn distance_along_route(&self) -> f64 {
+        self.distance_along_route
+    }
+
+    /// The trip state before this location update.
+    ///
+    /// This is absent for the first check of a trip.
+    pub fn previous_trip_state(&self) -> Option<TripState> {
+        self.previous_trip_state.clone()
+    }
 }
 
 /// A custom deviation detector (for extending the behavior of [`RouteDeviationTracking`]).
 ///
 /// This allows for arbitrarily complex implementations when the provided ones are not enough.
-/// For example, detecting that the user is proceeding the wrong direction by keeping a ring buffer
-/// of recent locations, or perform local map matching.
+/// For example, performing local map matching.
+/// Detectors which need history (ex: a ring buffer of recent locations)
+/// should implement [`StatefulRouteDeviationDetector`] instead.
 #[cfg_attr(feature = "uniffi", uniffi::export(with_foreign))]
 pub trait RouteDeviationDetector: Send + Sync {
     /// Determines whether the user is following the route correctly or not.
@@ -141,11 +721,82 @@ pub trait RouteDeviationDetector: Send + Sync {
     fn check_route_deviation(
         &self,
         location: UserLocation,
-        route: Route,
-        current_route_step: RouteStep,
+        context: Arc<RouteDeviationContext>,
     ) -> RouteDeviation;
 }
 
+impl From<RouteDeviationResult> for RouteDeviationState {
+    fn from(result: RouteDeviationResult) -> Self {
+        Self {
+            custom_detector: Some(result.next_iteration),
+            ..Self::default()
+        }
+    }
+}
+
+/// The result of a [`StatefulRouteDeviationDetector`] check.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct RouteDeviationResult {
+    /// Whether the user is following the route correctly or not.
+    pub deviation: RouteDeviation,
+    /// The next iteration of the detector.
+    ///
+    /// This is stored in the [`NavState`](crate::navigation_controller::models::NavState)
+    /// and checked on the next location update.
+    /// Detectors which keep history should return a new instance including the updated history,
+    /// rather than mutating themselves;
+    /// detectors without state can return an identical instance.
+    pub next_iteration: Arc<dyn StatefulRouteDeviationDetector>,
+}
+
+/// A custom deviation detector which keeps state between location updates
+/// (for extending the behavior of [`RouteDeviationTracking`]).
+///
+/// Rather than mutating itself, each check returns the next iteration of the detector,
+/// which the navigation controller carries along in the navigation state.
+/// This keeps history-based detectors (ex: keeping a ring buffer of recent locations)
+/// pure and replayable.
+#[cfg_attr(feature = "uniffi", uniffi::export(with_foreign))]
+pub trait StatefulRouteDeviationDetector: Send + Sync {
+    /// Determines whether the user is following the route correctly or not.
+    ///
+    /// The context includes the trip state before this location update.
+    ///
+    /// NOTE: This function has a single responsibility.
+    /// Side-effects like whether to recalculate a route are left to higher levels,
+    /// and implementations should only be concerned with determining the facts.
+    #[must_use]
+    fn check_route_deviation(
+        &self,
+        location: UserLocation,
+        context: Arc<RouteDeviationContext>,
+    ) -> RouteDeviationResult;
+}
+
+/// Creates a context at the start of the given step, with no previous trip state.
+#[cfg(test)]
+fn test_context(route: &Route, current_step_index: usize) -> Arc<RouteDeviationContext> {
+    let start = route.steps[current_step_index].geometry[0];
+    Arc::new(RouteDeviationContext::new(
+        Arc::new(RouteGeometry::new(route.clone())),
+        current_step_index,
+        UserLocation {
+            coordinates: start,
+            horizontal_accuracy: 0.0,
+            course_over_ground: None,
+            timestamp: SystemTime::now(),
+            speed: None,
+        },
+        None,
+        &TripProgress {
+            distance_to_next_maneuver: route.steps[current_step_index].distance,
+            distance_remaining: 0.0,
+            duration_remaining: 0.0,
+        },
+        None,
+    ))
+}
+
 #[cfg(test)]
 proptest! {
     /// Tests [`RouteDeviationTracking::None`] behavior,
@@ -173,7 +824,7 @@ proptest! {
             speed: None
         };
         prop_assert_eq!(
-            tracking.check_route_deviation(user_location_on_route, &route, &current_route_step),
+            tracking.check_route_deviation(user_location_on_route, &test_context(&route, 0), &RouteDeviationState::default()).0,
             RouteDeviation::NoDeviation
         );
 
@@ -190,7 +841,7 @@ proptest! {
             speed: None
         };
         prop_assert_eq!(
-            tracking.check_route_deviation(user_location_random, &route, &current_route_step),
+            tracking.check_route_deviation(user_location_random, &test_context(&route, 0), &RouteDeviationState::default()).0,
             RouteDeviation::NoDeviation
         );
     }
@@ -209,8 +860,7 @@ proptest! {
             fn check_route_deviation(
                 &self,
                 _location: UserLocation,
-                _route: Route,
-                _current_route_step: RouteStep,
+                _context: Arc<RouteDeviationContext>,
             ) -> RouteDeviation {
                 return RouteDeviation::NoDeviation
             }
@@ -235,7 +885,7 @@ proptest! {
             speed: None
         };
         prop_assert_eq!(
-            tracking.check_route_deviation(user_location_on_route, &route, &current_route_step),
+            tracking.check_route_deviation(user_location_on_route, &test_context(&route, 0), &RouteDeviationState::default()).0,
             RouteDeviation::NoDeviation
         );
 
@@ -252,7 +902,7 @@ proptest! {
             speed: None
         };
         prop_assert_eq!(
-            tracking.check_route_deviation(user_location_random, &route, &current_route_step),
+            tracking.check_route_deviation(user_location_random, &test_context(&route, 0), &RouteDeviationState::default()).0,
             RouteDeviation::NoDeviation
         );
     }
@@ -270,8 +920,7 @@ proptest! {
             fn check_route_deviation(
                 &self,
                 _location: UserLocation,
-                _route: Route,
-                _current_route_step: RouteStep,
+                _context: Arc<RouteDeviationContext>,
             ) -> RouteDeviation {
                 return RouteDeviation::OffRoute {
                     deviation_from_route_line: 7.0
@@ -298,7 +947,7 @@ proptest! {
             speed: None
         };
         prop_assert_eq!(
-            tracking.check_route_deviation(user_location_on_route, &route, &current_route_step),
+            tracking.check_route_deviation(user_location_on_route, &test_context(&route, 0), &RouteDeviationState::default()).0,
             RouteDeviation::OffRoute {
                 deviation_from_route_line: 7.0
             }
@@ -317,7 +966,7 @@ proptest! {
             speed: None
         };
         prop_assert_eq!(
-            tracking.check_route_deviation(user_location_random, &route, &current_route_step),
+            tracking.check_route_deviation(user_location_random, &test_context(&route, 0), &RouteDeviationState::default()).0,
             RouteDeviation::OffRoute {
                 deviation_from_route_line: 7.0
             }
@@ -355,7 +1004,7 @@ proptest! {
             speed: None
         };
         prop_assert_eq!(
-            tracking.check_route_deviation(user_location_on_route, &route, &current_route_step),
+            tracking.check_route_deviation(user_location_on_route, &test_context(&route, 0), &RouteDeviationState::default()).0,
             RouteDeviation::NoDeviation
         );
 
@@ -374,7 +1023,7 @@ proptest! {
             speed: None
         };
         let deviation = deviation_from_line(&Point::from(coordinates), &current_route_step.get_linestring());
-        match tracking.check_route_deviation(user_location_random, &route, &current_route_step) {
+        match tracking.check_route_deviation(user_location_random, &test_context(&route, 0), &RouteDeviationState::default()).0 {
             RouteDeviation::NoDeviation => {
                 if let Some(calculated) = deviation {
                     prop_assert!(calculated <= max_acceptable_deviation);
@@ -386,6 +1035,9 @@ proptest! {
                     deviation.unwrap()
                 );
             }
+            RouteDeviation::WrongWay { .. } => {
+                prop_assert!(false, "Static threshold tracking never reports wrong-way travel");
+            }
         }
     }
 
@@ -418,8 +1070,539 @@ proptest! {
             speed: None
         };
         prop_assert_eq!(
-            tracking.check_route_deviation(user_location_random, &route, &current_route_step),
+            tracking.check_route_deviation(user_location_random, &test_context(&route, 0), &RouteDeviationState::default()).0,
             RouteDeviation::NoDeviation
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CourseOverGround, Speed};
+    use crate::navigation_controller::models::{
+        CourseFiltering, NavigationControllerConfig, WaypointAdvanceMode,
+    };
+    use crate::navigation_controller::step_advance::conditions::ManualStepCondition;
+    use crate::navigation_controller::{NavigationController, Navigator};
+    use core::time::Duration;
+
+    const DRIVING_TRACKING: RouteDeviationTracking = RouteDeviationTracking::DefaultFor {
+        mode_of_travel: ModeOfTravel::Driving,
+    };
+
+    const WRONG_WAY_TRACKING: RouteDeviationTracking =
+        RouteDeviationTracking::StaticThresholdWithWrongWay {
+            minimum_horizontal_accuracy: 25,
+            max_acceptable_deviation: 20.0,
+            minimum_course_difference: 120,
+            wrong_way_fixes: 3,
+        };
+
+    /// A location on (or near) a route heading east along the equator.
+    fn location(lng: f64, lat: f64, course: u16, speed: f64) -> UserLocation {
+        UserLocation {
+            coordinates: GeographicCoordinate { lat, lng },
+            horizontal_accuracy: 5.0,
+            course_over_ground: Some(CourseOverGround {
+                degrees: course,
+                accuracy: None,
+            }),
+            timestamp: SystemTime::now(),
+            speed: Some(Speed {
+                value: speed,
+                accuracy: None,
+            }),
+        }
+    }
+
+    /// A location without course or speed information, at the given number of seconds into a trip.
+    fn timed_location(lng: f64, lat: f64, horizontal_accuracy: f64, seconds: u64) -> UserLocation {
+        UserLocation {
+            coordinates: GeographicCoordinate { lat, lng },
+            horizontal_accuracy,
+            course_over_ground: None,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(seconds),
+            speed: None,
+        }
+    }
+
+    /// Feeds the locations through wrong-way tracking in order, returning each deviation.
+    fn check(locations: &[UserLocation]) -> Vec<RouteDeviation> {
+        check_with(&WRONG_WAY_TRACKING, locations)
+    }
+
+    /// Feeds the locations through the given tracking in order, returning each deviation.
+    fn check_with(
+        tracking: &RouteDeviationTracking,
+        locations: &[UserLocation],
+    ) -> Vec<RouteDeviation> {
+        let route = gen_route_from_steps(vec![gen_dummy_route_step(0.0, 0.0, 0.01, 0.0)]);
+        let mut state = RouteDeviationState::default();
+        locations
+            .iter()
+            .map(|location| {
+                let (deviation, next_state) =
+                    tracking.check_route_deviation(*location, &test_context(&route, 0), &state);
+                state = next_state;
+                deviation
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_wrong_way_after_consecutive_fixes() {
+        let deviations = check(&[
+            location(0.005, 0.0, 270, 10.0),
+            location(0.004, 0.0, 270, 10.0),
+            location(0.003, 0.00005, 260, 10.0),
+            location(0.002, 0.0, 280, 10.0),
+        ]);
+
+        assert_eq!(
+            deviations,
+            vec![
+                RouteDeviation::NoDeviation,
+                RouteDeviation::NoDeviation,
+                RouteDeviation::WrongWay {
+                    course_difference: 180.0 - 10.0 / 3.0
+                },
+                RouteDeviation::WrongWay {
+                    course_difference: 180.0 - 20.0 / 3.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn travelling_along_the_route_is_not_wrong_way() {
+        let deviations = check(&[
+            location(0.001, 0.0, 90, 10.0),
+            location(0.002, 0.0, 60, 10.0),
+            location(0.003, 0.0, 120, 10.0),
+            location(0.004, 0.0, 90, 10.0),
+        ]);
+
+        assert!(deviations
+            .iter()
+            .all(|deviation| *deviation == RouteDeviation::NoDeviation));
+    }
+
+    #[test]
+    fn travelling_along_the_route_resets_wrong_way_detection() {
+        let deviations = check(&[
+            location(0.005, 0.0, 270, 10.0),
+            location(0.004, 0.0, 270, 10.0),
+            location(0.004, 0.0, 90, 10.0),
+            location(0.005, 0.0, 270, 10.0),
+        ]);
+
+        assert!(deviations
+            .iter()
+            .all(|deviation| *deviation == RouteDeviation::NoDeviation));
+    }
+
+    #[test]
+    fn slow_locations_are_ignored() {
+        let deviations = check(&[
+            location(0.005, 0.0, 270, 10.0),
+            location(0.005, 0.0, 90, 0.5),
+            location(0.004, 0.0, 270, 10.0),
+            location(0.003, 0.0, 270, 10.0),
+        ]);
+
+        assert_eq!(
+            deviations.last(),
+            Some(&RouteDeviation::WrongWay {
+                course_difference: 180.0
+            })
+        );
+    }
+
+    #[test]
+    fn off_route_takes_precedence_over_wrong_way() {
+        let deviations = check(&[
+            location(0.005, 0.0, 270, 10.0),
+            location(0.004, 0.0, 270, 10.0),
+            location(0.003, 0.001, 270, 10.0),
+            location(0.002, 0.0, 270, 10.0),
+        ]);
+
+        assert!(matches!(deviations[2], RouteDeviation::OffRoute { .. }));
+        assert_eq!(deviations[3], RouteDeviation::NoDeviation);
+    }
+
+    #[test]
+    fn preset_ignores_brief_deviations() {
+        let deviations = check_with(
+            &DRIVING_TRACKING,
+            &[
+                timed_location(0.001, 0.0, 5.0, 0),
+                timed_location(0.0011, 0.001, 5.0, 1),
+                timed_location(0.0012, 0.0, 5.0, 2),
+                timed_location(0.0013, 0.001, 5.0, 3),
+            ],
+        );
+
+        assert!(deviations
+            .iter()
+            .all(|deviation| *deviation == RouteDeviation::NoDeviation));
+    }
+
+    #[test]
+    fn preset_reports_deviation_after_minimum_time() {
+        let deviations = check_with(
+            &DRIVING_TRACKING,
+            &[
+                timed_location(0.001, 0.001, 5.0, 0),
+                timed_location(0.001, 0.001, 5.0, 2),
+                timed_location(0.001, 0.001, 5.0, 4),
+            ],
+        );
+
+        assert_eq!(deviations[0], RouteDeviation::NoDeviation);
+        assert_eq!(deviations[1], RouteDeviation::NoDeviation);
+        assert!(matches!(deviations[2], RouteDeviation::OffRoute { .. }));
+    }
+
+    #[test]
+    fn preset_reports_deviation_after_minimum_distance() {
+        let deviations = check_with(
+            &DRIVING_TRACKING,
+            &[
+                timed_location(0.001, 0.001, 5.0, 0),
+                timed_location(0.001, 0.0015, 5.0, 1),
+            ],
+        );
+
+        assert_eq!(deviations[0], RouteDeviation::NoDeviation);
+        assert!(matches!(deviations[1], RouteDeviation::OffRoute { .. }));
+    }
+
+    #[test]
+    fn preset_ignores_inaccurate_locations() {
+        let deviations = check_with(
+            &DRIVING_TRACKING,
+            &[
+                timed_location(0.001, 0.001, 5.0, 0),
+                timed_location(0.001, 0.0, 100.0, 2),
+                timed_location(0.001, 0.001, 5.0, 4),
+            ],
+        );
+
+        // The inaccurate location on the route doesn't reset the time spent off the route.
+        assert!(matches!(deviations[2], RouteDeviation::OffRoute { .. }));
+    }
+
+    const HYSTERESIS_TRACKING: RouteDeviationTracking =
+        RouteDeviationTracking::StaticThresholdWithHysteresis {
+            minimum_horizontal_accuracy: 25,
+            max_acceptable_deviation: 50.0,
+            clear_deviation: 20.0,
+            minimum_off_route_fixes: 3,
+            minimum_off_route_distance: 100.0,
+        };
+
+    fn is_off_route(deviation: &RouteDeviation) -> bool {
+        matches!(deviation, RouteDeviation::OffRoute { .. })
+    }
+
+    #[test]
+    fn hysteresis_ignores_a_single_bad_location() {
+        let deviations = check_with(
+            &HYSTERESIS_TRACKING,
+            &[
+                timed_location(0.001, 0.0, 5.0, 0),
+                timed_location(0.002, 0.001, 5.0, 1),
+                timed_location(0.003, 0.0, 5.0, 2),
+                timed_location(0.004, 0.001, 5.0, 3),
+                timed_location(0.005, 0.0, 5.0, 4),
+            ],
+        );
+
+        assert!(!deviations.iter().any(is_off_route));
+    }
+
+    #[test]
+    fn hysteresis_reports_after_consecutive_locations() {
+        let deviations = check_with(
+            &HYSTERESIS_TRACKING,
+            &[
+                timed_location(0.001, 0.001, 5.0, 0),
+                timed_location(0.0011, 0.001, 5.0, 1),
+                timed_location(0.0012, 0.001, 5.0, 2),
+            ],
+        );
+
+        assert_eq!(
+            deviations.iter().map(is_off_route).collect::<Vec<_>>(),
+            vec![false, false, true]
+        );
+    }
+
+    #[test]
+    fn hysteresis_reports_after_distance() {
+        let deviations = check_with(
+            &HYSTERESIS_TRACKING,
+            &[
+                timed_location(0.001, 0.001, 5.0, 0),
+                timed_location(0.002, 0.001, 5.0, 1),
+            ],
+        );
+
+        assert_eq!(
+            deviations.iter().map(is_off_route).collect::<Vec<_>>(),
+            vec![false, true]
+        );
+    }
+
+    #[test]
+    fn hysteresis_clears_below_the_smaller_threshold() {
+        let deviations = check_with(
+            &HYSTERESIS_TRACKING,
+            &[
+                timed_location(0.001, 0.001, 5.0, 0),
+                timed_location(0.002, 0.001, 5.0, 1),
+                // About 33m from the route; within the threshold, but not close enough to clear.
+                timed_location(0.003, 0.0003, 5.0, 2),
+                // Inaccurate locations can't clear the deviation.
+                timed_location(0.004, 0.0, 100.0, 3),
+                timed_location(0.005, 0.0001, 5.0, 4),
+            ],
+        );
+
+        assert_eq!(
+            deviations.iter().map(is_off_route).collect::<Vec<_>>(),
+            vec![false, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn hysteresis_distance_follows_the_path_off_route() {
+        let tracking = RouteDeviationTracking::StaticThresholdWithHysteresis {
+            minimum_horizontal_accuracy: 25,
+            max_acceptable_deviation: 50.0,
+            clear_deviation: 20.0,
+            minimum_off_route_fixes: 10,
+            minimum_off_route_distance: 100.0,
+        };
+        // Going back and forth about 55m, so never more than 55m from the first location.
+        let deviations = check_with(
+            &tracking,
+            &[
+                timed_location(0.001, 0.001, 5.0, 0),
+                timed_location(0.0015, 0.001, 5.0, 1),
+                timed_location(0.001, 0.001, 5.0, 2),
+            ],
+        );
+
+        assert_eq!(
+            deviations.iter().map(is_off_route).collect::<Vec<_>>(),
+            vec![false, false, true]
+        );
+    }
+
+    #[test]
+    fn hysteresis_clear_deviation_is_limited_to_the_threshold() {
+        let tracking = RouteDeviationTracking::StaticThresholdWithHysteresis {
+            minimum_horizontal_accuracy: 25,
+            max_acceptable_deviation: 50.0,
+            clear_deviation: 80.0,
+            minimum_off_route_fixes: 1,
+            minimum_off_route_distance: 100.0,
+        };
+        let deviations = check_with(
+            &tracking,
+            &[
+                timed_location(0.001, 0.001, 5.0, 0),
+                // About 66m from the route; within the clear deviation, but still beyond the threshold.
+                timed_location(0.002, 0.0006, 5.0, 1),
+                timed_location(0.003, 0.0001, 5.0, 2),
+            ],
+        );
+
+        assert_eq!(
+            deviations.iter().map(is_off_route).collect::<Vec<_>>(),
+            vec![true, true, false]
+        );
+    }
+
+    #[test]
+    fn deviation_is_measured_against_adjacent_steps() {
+        // Heads east, turns left to head north, then turns right to head east again.
+        let route = gen_route_from_steps(vec![
+            gen_dummy_route_step(0.0, 0.0, 0.001, 0.0),
+            gen_dummy_route_step(0.001, 0.0, 0.001, 0.002),
+            gen_dummy_route_step(0.001, 0.002, 0.003, 0.002),
+        ]);
+        let tracking = RouteDeviationTracking::StaticThreshold {
+            minimum_horizontal_accuracy: 25,
+            max_acceptable_deviation: 50.0,
+        };
+        let check = |location: UserLocation, current_step_index: usize| {
+            tracking
+                .check_route_deviation(
+                    location,
+                    &test_context(&route, current_step_index),
+                    &RouteDeviationState::default(),
+                )
+                .0
+        };
+
+        // About 90m up the second step, before the first step has been advanced.
+        let on_next_step = timed_location(0.001, 0.0008, 5.0, 0);
+        assert_eq!(check(on_next_step, 0), RouteDeviation::NoDeviation);
+
+        // Still on the first step after the second step has become the current one.
+        let on_previous_step = timed_location(0.0002, 0.0, 5.0, 0);
+        assert_eq!(check(on_previous_step, 1), RouteDeviation::NoDeviation);
+
+        // Two steps ahead is beyond the window.
+        let two_steps_ahead = timed_location(0.003, 0.002, 5.0, 0);
+        assert!(matches!(
+            check(two_steps_ahead, 0),
+            RouteDeviation::OffRoute { .. }
+        ));
+    }
+
+    /// Reports the user off route from the third location onwards,
+    /// with the number of previous trip states it has seen as the deviation.
+    struct CountingDetector {
+        locations_seen: u16,
+        previous_trip_states_seen: u16,
+    }
+
+    impl StatefulRouteDeviationDetector for CountingDetector {
+        fn check_route_deviation(
+            &self,
+            _location: UserLocation,
+            context: Arc<RouteDeviationContext>,
+        ) -> RouteDeviationResult {
+            let next_iteration = CountingDetector {
+                locations_seen: self.locations_seen + 1,
+                previous_trip_states_seen: self.previous_trip_states_seen
+                    + u16::from(context.previous_trip_state_ref().is_some()),
+            };
+            let deviation = if next_iteration.locations_seen >= 3 {
+                RouteDeviation::OffRoute {
+                    deviation_from_route_line: f64::from(next_iteration.previous_trip_states_seen),
+                }
+            } else {
+                RouteDeviation::NoDeviation
+            };
+            RouteDeviationResult {
+                deviation,
+                next_iteration: Arc::new(next_iteration),
+            }
+        }
+    }
+
+    #[test]
+    fn stateful_custom_detector_iterations_are_carried_in_nav_state() {
+        let route = gen_route_from_steps(vec![gen_dummy_route_step(0.0, 0.0, 0.01, 0.0)]);
+        let controller = NavigationController::new(
+            route,
+            NavigationControllerConfig {
+                waypoint_advance: WaypointAdvanceMode::WaypointWithinRange(100.0),
+                route_deviation_tracking: RouteDeviationTracking::StatefulCustom {
+                    detector: Arc::new(CountingDetector {
+                        locations_seen: 0,
+                        previous_trip_states_seen: 0,
+                    }),
+                },
+                snapped_location_course_filtering: CourseFiltering::Raw,
+                step_advance_condition: Arc::new(ManualStepCondition {}),
+                arrival_step_advance_condition: Arc::new(ManualStepCondition {}),
+            },
+        );
+
+        let mut state = controller.get_initial_state(timed_location(0.001, 0.0, 5.0, 0));
+        let mut deviations = vec![];
+        for seconds in 1u16..=3 {
+            if let TripState::Navigating { deviation, .. } = state.trip_state() {
+                deviations.push(deviation);
+            }
+            state = controller.update_user_location(
+                timed_location(0.001 * f64::from(seconds), 0.0, 5.0, u64::from(seconds)),
+                state,
+            );
+        }
+        if let TripState::Navigating { deviation, .. } = state.trip_state() {
+            deviations.push(deviation);
+        }
+
+        assert_eq!(
+            deviations,
+            vec![
+                RouteDeviation::NoDeviation,
+                RouteDeviation::NoDeviation,
+                RouteDeviation::OffRoute {
+                    deviation_from_route_line: 2.0
+                },
+                RouteDeviation::OffRoute {
+                    deviation_from_route_line: 3.0
+                },
+            ]
+        );
+    }
+
+    /// Reports the distance along the route as the deviation.
+    struct PositionReportingDetector;
+
+    impl RouteDeviationDetector for PositionReportingDetector {
+        fn check_route_deviation(
+            &self,
+            _location: UserLocation,
+            context: Arc<RouteDeviationContext>,
+        ) -> RouteDeviation {
+            assert_eq!(context.current_step_index(), 1);
+            assert_eq!(
+                context
+                    .current_route_step_ref()
+                    .map(|step| step.geometry[0]),
+                Some(context.route_ref().steps[1].geometry[0])
+            );
+            RouteDeviation::OffRoute {
+                deviation_from_route_line: context.distance_along_route(),
+            }
+        }
+    }
+
+    #[test]
+    fn custom_detectors_receive_the_position_along_the_route() {
+        let route = gen_route_from_steps(vec![
+            gen_dummy_route_step(0.0, 0.0, 0.001, 0.0),
+            gen_dummy_route_step(0.001, 0.0, 0.002, 0.0),
+        ]);
+        let first_step_distance = route.steps[0].distance;
+        let location = timed_location(0.0015, 0.0, 5.0, 0);
+
+        let (deviation, _) = RouteDeviationTracking::Custom {
+            detector: Arc::new(PositionReportingDetector),
+        }
+        .check_route_deviation(
+            location,
+            &Arc::new(RouteDeviationContext::new(
+                Arc::new(RouteGeometry::new(route.clone())),
+                1,
+                location,
+                Some(0),
+                &TripProgress {
+                    distance_to_next_maneuver: route.steps[1].distance / 2.0,
+                    distance_remaining: route.steps[1].distance / 2.0,
+                    duration_remaining: 0.0,
+                },
+                None,
+            )),
+            &RouteDeviationState::default(),
+        );
+
+        let RouteDeviation::OffRoute {
+            deviation_from_route_line: distance_along_route,
+        } = deviation
+        else {
+            panic!("Expected the detector to report its position");
+        };
+        assert!((distance_along_route - first_step_distance * 1.5).abs() < 1e-6);
+    }
+}
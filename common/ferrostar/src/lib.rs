@@ -60,13 +60,18 @@ pub fn create_ferrostar_logger() {
 #[cfg(feature = "uniffi")]
 mod uniffi_deps {
     pub use crate::models::{Route, Waypoint};
+    pub use crate::navigation_controller::recording::{
+        sink::{NavigationRecordingSink, NdjsonRecordingSink},
+        NavigationRecordingError,
+    };
     pub use crate::routing_adapters::{
         error::{InstantiationError, ParsingError},
+        graphhopper::{GraphHopperHttpRequestGenerator, GraphHopperResponseParser},
         osrm::{
             models::{Route as OsrmRoute, Waypoint as OsrmWaypoint},
-            OsrmResponseParser,
+            OsrmHttpRequestGenerator, OsrmResponseParser,
         },
-        valhalla::ValhallaHttpRequestGenerator,
+        valhalla::{ValhallaHttpRequestGenerator, ValhallaResponseFormat},
         RouteRequestGenerator, RouteResponseParser,
     };
     pub use chrono::{DateTime, Utc};
@@ -132,6 +137,35 @@ fn create_valhalla_request_generator(
     )?))
 }
 
+/// Creates a [`RouteResponseParser`] capable of parsing Valhalla responses in the given format.
+///
+/// This is provided as a convenience for use from foreign code when creating your own [`routing_adapters::RouteAdapter`].
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+fn create_valhalla_response_parser(
+    format: ValhallaResponseFormat,
+) -> Result<Arc<dyn RouteResponseParser>, InstantiationError> {
+    format.response_parser()
+}
+
+/// Creates a [`RouteRequestGenerator`]
+/// which generates requests to an arbitrary OSRM server.
+///
+/// This is provided as a convenience for use from foreign code when creating your own [`routing_adapters::RouteAdapter`].
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+fn create_osrm_request_generator(
+    endpoint_url: String,
+    profile: String,
+    annotations: Vec<String>,
+) -> Arc<dyn RouteRequestGenerator> {
+    Arc::new(OsrmHttpRequestGenerator::new(
+        endpoint_url,
+        profile,
+        annotations,
+    ))
+}
+
 /// Creates a [`RouteResponseParser`] capable of parsing OSRM responses.
 ///
 /// This response parser is designed to be fairly flexible,
@@ -143,6 +177,46 @@ fn create_osrm_response_parser(polyline_precision: u32) -> Arc<dyn RouteResponse
     Arc::new(OsrmResponseParser::new(polyline_precision))
 }
 
+/// Creates a [`RouteRequestGenerator`]
+/// which generates requests to an arbitrary `GraphHopper` server.
+///
+/// This is provided as a convenience for use from foreign code when creating your own [`routing_adapters::RouteAdapter`].
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+fn create_graphhopper_request_generator(
+    endpoint_url: String,
+    profile: String,
+    options_json: Option<String>,
+) -> Result<Arc<dyn RouteRequestGenerator>, InstantiationError> {
+    Ok(Arc::new(
+        GraphHopperHttpRequestGenerator::with_options_json(
+            endpoint_url,
+            profile,
+            options_json.as_deref(),
+        )?,
+    ))
+}
+
+/// Creates a [`RouteResponseParser`] capable of parsing `GraphHopper` responses.
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+fn create_graphhopper_response_parser() -> Arc<dyn RouteResponseParser> {
+    Arc::new(GraphHopperResponseParser::new())
+}
+
+/// Creates a [`NavigationRecordingSink`] which writes newline-delimited JSON to the file at `path`.
+///
+/// Any existing file at the path is replaced.
+/// Pass the result to [`navigation_controller::create_recording_navigator`]
+/// to stream a recording of the session to disk.
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+fn create_ndjson_recording_sink(
+    path: &str,
+) -> Result<Arc<dyn NavigationRecordingSink>, NavigationRecordingError> {
+    Ok(Arc::new(NdjsonRecordingSink::new(path)?))
+}
+
 // MARK: OSRM Route Conversion
 
 /// Creates a [`Route`] from OSRM data.
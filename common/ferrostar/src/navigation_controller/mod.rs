@@ -2,6 +2,7 @@
 
 pub mod models;
 pub mod recording;
+pub(crate) mod route_geometry;
 pub mod step_advance;
 
 #[cfg(test)]
@@ -9,11 +10,12 @@ pub(crate) mod test_helpers;
 
 use crate::{
     algorithms::{
-        advance_step, apply_snapped_course, calculate_trip_progress,
-        index_of_closest_segment_origin, snap_user_location_to_line,
+        advance_step, apply_snapped_course, snap_user_location_to_line_near,
+        trip_progress_from_distance_to_next_maneuver,
     },
+    deviation_detection::{RouteDeviationContext, RouteDeviationState},
     models::{Route, RouteStep, UserLocation, Waypoint},
-    navigation_controller::models::TripSummary,
+    navigation_controller::models::{NavigationRecordingEvent, TripSummary},
 };
 use chrono::Utc;
 use geo::{
@@ -23,8 +25,13 @@ use geo::{
 use models::{
     NavState, NavigationControllerConfig, StepAdvanceStatus, TripState, WaypointAdvanceMode,
 };
+use recording::{
+    sink::NavigationRecordingSink, NavigationRecordingError, RecordingNavigationController,
+};
+use route_geometry::RouteGeometry;
 use std::clone::Clone;
 use std::sync::Arc;
+use step_advance::should_advance_step_with_geometry;
 
 #[cfg(feature = "wasm-bindgen")]
 use crate::navigation_controller::models::{JsNavState, SerializableNavigationControllerConfig};
@@ -41,6 +48,15 @@ pub trait Navigator: Send + Sync {
     fn get_initial_state(&self, location: UserLocation) -> NavState;
     fn advance_to_next_step(&self, state: NavState) -> NavState;
     fn update_user_location(&self, location: UserLocation, state: NavState) -> NavState;
+    /// Serializes a recording of the navigation session to JSON.
+    ///
+    /// The `events` are those accumulated in the most recent [`NavState`]
+    /// (see [`NavState::recording_events`]).
+    /// Navigators which do not record sessions return [`NavigationRecordingError::RecordingNotEnabled`].
+    fn get_recording(
+        &self,
+        events: Vec<NavigationRecordingEvent>,
+    ) -> Result<String, NavigationRecordingError>;
 }
 
 /// Creates a new navigation controller for the given route and configuration.
@@ -55,14 +71,28 @@ pub fn create_navigator(
 ) -> Arc<dyn Navigator> {
     if should_record {
         // Creates a navigation controller with a wrapper that records events.
-        // TODO: Currently just returns the regular controller
-        Arc::new(NavigationController { route, config })
+        Arc::new(RecordingNavigationController::new(route, config))
     } else {
         // Creates a normal navigation controller.
-        Arc::new(NavigationController { route, config })
+        Arc::new(NavigationController::new(route, config))
     }
 }
 
+/// Creates a new navigation controller which streams a recording of the session to `sink`.
+///
+/// Unlike [`create_navigator`], recorded events are not accumulated in memory,
+/// which makes this better suited to long sessions.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+pub fn create_recording_navigator(
+    route: Route,
+    config: NavigationControllerConfig,
+    sink: Arc<dyn NavigationRecordingSink>,
+) -> Result<Arc<dyn Navigator>, NavigationRecordingError> {
+    Ok(Arc::new(RecordingNavigationController::with_sink(
+        route, config, sink,
+    )?))
+}
+
 /// Manages the navigation lifecycle through a route,
 /// returning an updated state given inputs like user location.
 ///
@@ -71,7 +101,7 @@ pub fn create_navigator(
 /// - This is a pure type (no interior mutability), so a core function of your platform code is responsibly managing mutable state.
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 pub struct NavigationController {
-    route: Route,
+    route: Arc<RouteGeometry>,
     config: NavigationControllerConfig,
 }
 
@@ -80,14 +110,17 @@ impl NavigationController {
     #[cfg_attr(feature = "uniffi", uniffi::constructor)]
     /// Create a navigation controller for a route and configuration.
     pub fn new(route: Route, config: NavigationControllerConfig) -> Self {
-        Self { route, config }
+        Self {
+            route: Arc::new(RouteGeometry::new(route)),
+            config,
+        }
     }
 }
 
 impl Navigator for NavigationController {
     /// Returns initial trip state as if the user had just started the route with no progress.
     fn get_initial_state(&self, location: UserLocation) -> NavState {
-        let remaining_steps = self.route.steps.clone();
+        let remaining_steps = self.route.route().steps.clone();
 
         let initial_summary = TripSummary {
             distance_traveled: 0.0,
@@ -101,21 +134,26 @@ impl Navigator for NavigationController {
             return NavState::complete(location, initial_summary);
         };
 
-        // TODO: We could move this to the Route struct or NavigationController directly to only calculate it once.
-        let current_step_linestring = current_route_step.get_linestring();
+        let current_step_geometry = self.route.step_geometry(0, current_route_step);
         let (current_step_geometry_index, snapped_user_location) =
-            self.snap_user_to_line(location, &current_step_linestring);
+            self.snap_user_to_line(location, current_step_geometry.linestring(), None);
 
-        let progress = calculate_trip_progress(
-            &snapped_user_location.into(),
-            &current_step_linestring,
+        let progress = trip_progress_from_distance_to_next_maneuver(
+            current_step_geometry.distance_to_end(&snapped_user_location.into()),
             &remaining_steps,
         );
-        let deviation = self.config.route_deviation_tracking.check_route_deviation(
-            location,
-            &self.route,
-            current_route_step,
-        );
+        let context = Arc::new(RouteDeviationContext::new(
+            Arc::clone(&self.route),
+            0,
+            snapped_user_location,
+            current_step_geometry_index,
+            &progress,
+            None,
+        ));
+        let (deviation, route_deviation_state) = self
+            .config
+            .route_deviation_tracking
+            .check_route_deviation(location, &context, &RouteDeviationState::default());
         let visual_instruction = current_route_step
             .get_active_visual_instruction(progress.distance_to_next_maneuver)
             .cloned();
@@ -132,7 +170,14 @@ impl Navigator for NavigationController {
             snapped_user_location,
             remaining_steps,
             // Skip the first waypoint, as it is the current one
-            remaining_waypoints: self.route.waypoints.iter().skip(1).copied().collect(),
+            remaining_waypoints: self
+                .route
+                .route()
+                .waypoints
+                .iter()
+                .skip(1)
+                .copied()
+                .collect(),
             progress,
             summary: initial_summary,
             deviation,
@@ -141,7 +186,7 @@ impl Navigator for NavigationController {
             annotation_json,
         };
         let next_advance = Arc::clone(&self.config.step_advance_condition);
-        NavState::new(trip_state, next_advance)
+        NavState::new(trip_state, next_advance).with_route_deviation_state(route_deviation_state)
     }
 
     /// Advances navigation to the next step (or finishes the route).
@@ -153,41 +198,9 @@ impl Navigator for NavigationController {
     /// This method takes the intermediate state (e.g., from `update_user_location`) and advances if necessary,
     /// and does not handle anything like snapping.
     fn advance_to_next_step(&self, state: NavState) -> NavState {
-        match state.trip_state() {
-            TripState::Navigating {
-                user_location,
-                ref remaining_steps,
-                ref remaining_waypoints,
-                summary,
-                ..
-            } => {
-                let update = advance_step(remaining_steps);
-                match update {
-                    StepAdvanceStatus::Advanced { step: current_step } => {
-                        // Apply the updates
-                        let mut remaining_steps = remaining_steps.clone();
-                        remaining_steps.remove(0);
-
-                        // Create a new trip state with the updated current_step
-                        // and remaining_steps
-                        let trip_state = self.create_intermediate_trip_state(
-                            state.trip_state(),
-                            &user_location,
-                            &current_step,
-                            &remaining_steps,
-                            &remaining_waypoints,
-                        );
-
-                        NavState::new(trip_state, state.step_advance_condition())
-                    }
-                    StepAdvanceStatus::EndOfRoute => {
-                        NavState::complete(user_location, summary.clone())
-                    }
-                }
-            }
-            // Pass through
-            TripState::Idle { .. } | TripState::Complete { .. } => state.clone(),
-        }
+        // There is no new location, so keep the previous deviation
+        // rather than taking the current location into account a second time.
+        self.advance_to_next_step_from(&state, &state.route_deviation_state(), false)
     }
 
     /// Updates the user's current location and updates the navigation state accordingly.
@@ -220,32 +233,53 @@ impl Navigator for NavigationController {
 
                 // Get the step advance condition result.
                 let next_step = remaining_steps.get(1).cloned();
+                let current_step_index = self
+                    .route
+                    .route()
+                    .steps
+                    .len()
+                    .saturating_sub(remaining_steps.len());
+                let current_step_geometry =
+                    self.route.step_geometry(current_step_index, current_step);
                 let step_advance_result = if remaining_steps.len() <= 2 {
-                    self.config
-                        .arrival_step_advance_condition
-                        .should_advance_step(location, current_step.clone(), next_step)
+                    should_advance_step_with_geometry(
+                        self.config.arrival_step_advance_condition.as_ref(),
+                        location,
+                        current_step.clone(),
+                        current_step_geometry.linestring(),
+                        next_step,
+                    )
                 } else {
-                    state.step_advance_condition().should_advance_step(
+                    should_advance_step_with_geometry(
+                        state.step_advance_condition().as_ref(),
                         location,
                         current_step.clone(),
+                        current_step_geometry.linestring(),
                         next_step,
                     )
                 };
 
-                let intermediate_nav_state = NavState::new(
-                    self.create_intermediate_trip_state(
-                        state.trip_state(),
-                        &location,
-                        current_step,
-                        &remaining_steps,
-                        &remaining_waypoints,
-                    ),
-                    step_advance_result.next_iteration,
+                let (trip_state, route_deviation_state) = self.create_intermediate_trip_state(
+                    state.trip_state(),
+                    &location,
+                    current_step,
+                    &remaining_steps,
+                    &remaining_waypoints,
+                    &state.route_deviation_state(),
+                    true,
                 );
+                let intermediate_nav_state =
+                    NavState::new(trip_state, step_advance_result.next_iteration)
+                        .with_route_deviation_state(route_deviation_state);
 
                 if step_advance_result.should_advance {
-                    // Advance to the next step
-                    return self.advance_to_next_step(intermediate_nav_state);
+                    // Advance to the next step, checking the deviation from the new step
+                    // against the route deviation state from before this location update.
+                    return self.advance_to_next_step_from(
+                        &intermediate_nav_state,
+                        &state.route_deviation_state(),
+                        true,
+                    );
                 }
 
                 intermediate_nav_state
@@ -254,10 +288,72 @@ impl Navigator for NavigationController {
             TripState::Idle { .. } | TripState::Complete { .. } => state.clone(),
         }
     }
+
+    fn get_recording(
+        &self,
+        _events: Vec<NavigationRecordingEvent>,
+    ) -> Result<String, NavigationRecordingError> {
+        Err(NavigationRecordingError::RecordingNotEnabled)
+    }
 }
 
 /// Shared functionality for the navigation controller that is not exported by UniFFI.
 impl NavigationController {
+    /// Advances navigation to the next step (or finishes the route).
+    ///
+    /// If `check_deviation` is set, the deviation from the new step is checked
+    /// starting from the given route deviation state.
+    /// Otherwise, the previous deviation and the given route deviation state are kept as-is.
+    ///
+    /// Returns:
+    /// - `NavState`: The navigation state after advancing.
+    fn advance_to_next_step_from(
+        &self,
+        state: &NavState,
+        route_deviation_state: &RouteDeviationState,
+        check_deviation: bool,
+    ) -> NavState {
+        match state.trip_state() {
+            TripState::Navigating {
+                user_location,
+                ref remaining_steps,
+                ref remaining_waypoints,
+                summary,
+                ..
+            } => {
+                let update = advance_step(remaining_steps);
+                match update {
+                    StepAdvanceStatus::Advanced { step: current_step } => {
+                        // Apply the updates
+                        let mut remaining_steps = remaining_steps.clone();
+                        remaining_steps.remove(0);
+
+                        // Create a new trip state with the updated current_step
+                        // and remaining_steps
+                        let (trip_state, route_deviation_state) = self
+                            .create_intermediate_trip_state(
+                                state.trip_state(),
+                                &user_location,
+                                &current_step,
+                                &remaining_steps,
+                                &remaining_waypoints,
+                                route_deviation_state,
+                                check_deviation,
+                            );
+
+                        NavState::new(trip_state, state.step_advance_condition())
+                            .with_route_deviation_state(route_deviation_state)
+                    }
+                    StepAdvanceStatus::EndOfRoute => {
+                        NavState::complete(user_location, summary.clone())
+                    }
+                }
+            }
+            // Pass through
+            TripState::Idle { .. } | TripState::Complete { .. } => state.clone(),
+        }
+    }
+
     /// Create an intermediate trip state with updated values,
     /// but does _not_ advance to the next step or handle arrival.
     ///
@@ -267,9 +363,14 @@ impl NavigationController {
     /// - `current_step`: The current route step.
     /// - `remaining_steps`: The remaining route steps.
     /// - `remaining_waypoints`: The remaining waypoints.
+    /// - `route_deviation_state`: The route deviation state before this location update.
+    /// - `check_deviation`: Whether to check the route deviation for this location;
+    ///   if not, the previous deviation and route deviation state are kept.
     ///
     /// Returns:
     /// - `TripState`: The intermediate trip state.
+    /// - `RouteDeviationState`: The route deviation state after this location update.
+    #[allow(clippy::too_many_arguments)]
     fn create_intermediate_trip_state(
         &self,
         trip_state: TripState,
@@ -277,39 +378,69 @@ impl NavigationController {
         current_step: &RouteStep,
         remaining_steps: &Vec<RouteStep>,
         remaining_waypoints: &Vec<Waypoint>,
-    ) -> TripState {
-        match trip_state {
+        route_deviation_state: &RouteDeviationState,
+        check_deviation: bool,
+    ) -> (TripState, RouteDeviationState) {
+        match &trip_state {
             TripState::Navigating {
                 user_location: previous_user_location,
+                deviation: previous_deviation,
                 snapped_user_location: previous_snapped_user_location,
+                current_step_geometry_index: previous_geometry_index,
+                remaining_steps: previous_remaining_steps,
                 summary: previous_summary,
                 ..
             } => {
+                let previous_deviation = *previous_deviation;
                 // Find the nearest point on the route line
-                let current_step_linestring = current_step.get_linestring();
-                let (current_step_geometry_index, snapped_user_location) =
-                    self.snap_user_to_line(*location, &current_step_linestring);
-
-                let deviation = self.config.route_deviation_tracking.check_route_deviation(
+                let current_step_index = self
+                    .route
+                    .route()
+                    .steps
+                    .len()
+                    .saturating_sub(remaining_steps.len());
+                let current_step_geometry =
+                    self.route.step_geometry(current_step_index, current_step);
+                // The previous geometry index is only meaningful if we're still on the same step.
+                let previous_geometry_index = previous_geometry_index
+                    .filter(|_| previous_remaining_steps.len() == remaining_steps.len());
+                let (current_step_geometry_index, snapped_user_location) = self.snap_user_to_line(
                     *location,
-                    &self.route,
-                    current_step,
+                    current_step_geometry.linestring(),
+                    previous_geometry_index,
                 );
 
                 // Update trip summary with accumulated distance
                 let updated_summary = previous_summary.update(
-                    &previous_user_location,
+                    previous_user_location,
                     &location,
-                    &previous_snapped_user_location,
+                    previous_snapped_user_location,
                     &snapped_user_location,
                 );
 
-                let progress = calculate_trip_progress(
-                    &snapped_user_location.into(),
-                    &current_step_linestring,
+                let progress = trip_progress_from_distance_to_next_maneuver(
+                    current_step_geometry.distance_to_end(&snapped_user_location.into()),
                     &remaining_steps,
                 );
 
+                let context = Arc::new(RouteDeviationContext::new(
+                    Arc::clone(&self.route),
+                    current_step_index,
+                    snapped_user_location,
+                    current_step_geometry_index,
+                    &progress,
+                    Some(trip_state),
+                ));
+                let (deviation, route_deviation_state) = if check_deviation {
+                    self.config.route_deviation_tracking.check_route_deviation(
+                        *location,
+                        &context,
+                        route_deviation_state,
+                    )
+                } else {
+                    (previous_deviation, route_deviation_state.clone())
+                };
+
                 let visual_instruction = current_step
                     .get_active_visual_instruction(progress.distance_to_next_maneuver)
                     .cloned();
@@ -319,7 +450,7 @@ impl NavigationController {
                 let annotation_json = current_step_geometry_index
                     .and_then(|index| current_step.get_annotation_at_current_index(index));
 
-                TripState::Navigating {
+                let trip_state = TripState::Navigating {
                     current_step_geometry_index,
                     user_location: location.clone(),
                     snapped_user_location,
@@ -331,10 +462,13 @@ impl NavigationController {
                     visual_instruction,
                     spoken_instruction,
                     annotation_json,
-                }
+                };
+                (trip_state, route_deviation_state)
             }
             // Pass through
-            TripState::Idle { .. } | TripState::Complete { .. } => trip_state,
+            TripState::Idle { .. } | TripState::Complete { .. } => {
+                (trip_state, route_deviation_state.clone())
+            }
         }
     }
 
@@ -342,18 +476,19 @@ impl NavigationController {
     ///
     /// This bundles all work related to snapping the user's location to the route line and is not intended to be exported.
     ///
+    /// The search for the closest point starts near `previous_geometry_index` when it is known.
+    ///
     /// Returns the index of the closest segment origin to the snapped user location as well as the snapped user location.
     fn snap_user_to_line(
         &self,
         location: UserLocation,
         line: &LineString,
+        previous_geometry_index: Option<u64>,
     ) -> (Option<u64>, UserLocation) {
-        // Snap the user's latitude and longitude to the line.
-        let snapped_user_location = snap_user_location_to_line(location, line);
-
-        // Get the index of the closest segment origin to the snapped user location.
-        let current_step_geometry_index =
-            index_of_closest_segment_origin(snapped_user_location, line);
+        // Snap the user's latitude and longitude to the line,
+        // and get the index of the closest segment origin to the snapped user location.
+        let (current_step_geometry_index, snapped_user_location) =
+            snap_user_location_to_line_near(location, line, previous_geometry_index);
 
         // Snap the user's course to the line if the configuration specifies it.
         let snapped_with_course: UserLocation = match &self.config.snapped_location_course_filtering
@@ -448,6 +583,16 @@ impl JsNavigationController {
         serde_wasm_bindgen::to_value(&JsNavState::from(new_state))
             .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
     }
+
+    #[wasm_bindgen(js_name = getRecording)]
+    pub fn get_recording(&self, events: JsValue) -> Result<JsValue, JsValue> {
+        let events: Vec<NavigationRecordingEvent> = serde_wasm_bindgen::from_value(events)?;
+
+        self.0
+            .get_recording(events)
+            .map(|json| JsValue::from_str(&json))
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -455,24 +600,28 @@ mod tests {
     use super::step_advance::StepAdvanceCondition;
     use super::*;
     use crate::deviation_detection::{RouteDeviation, RouteDeviationTracking};
+    use crate::models::GeographicCoordinate;
     use crate::navigation_controller::models::CourseFiltering;
+    use crate::navigation_controller::step_advance::conditions::ManualStepCondition;
     use crate::navigation_controller::step_advance::conditions::{
         DistanceEntryAndExitCondition, DistanceToEndOfStepCondition,
     };
     use crate::navigation_controller::test_helpers::{
-        get_test_route, nav_controller_insta_settings, TestRoute,
+        gen_dummy_route_step, gen_route_from_steps, get_test_route, nav_controller_insta_settings,
+        test_config, TestRoute,
     };
     use crate::simulation::{
         advance_location_simulation, location_simulation_from_route, LocationBias,
     };
     use std::sync::Arc;
+    use std::time::SystemTime;
 
     fn test_full_route_state_snapshot(
         route: Route,
         step_advance_condition: Arc<dyn StepAdvanceCondition>,
     ) -> Vec<TripState> {
         let mut simulation_state =
-            location_simulation_from_route(&route, Some(10.0), LocationBias::None)
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
                 .expect("Unable to create simulation");
 
         let controller = create_navigator(
@@ -599,4 +748,54 @@ mod tests {
             ));
         });
     }
+
+    #[test]
+    fn manual_advance_does_not_count_the_location_again() {
+        let route = gen_route_from_steps(vec![
+            gen_dummy_route_step(0.0, 0.0, 0.001, 0.0),
+            gen_dummy_route_step(0.001, 0.0, 0.002, 0.0),
+            gen_dummy_route_step(0.002, 0.0, 0.003, 0.0),
+        ]);
+        let controller = NavigationController::new(
+            route,
+            NavigationControllerConfig {
+                route_deviation_tracking: RouteDeviationTracking::StaticThresholdWithHysteresis {
+                    minimum_horizontal_accuracy: 25,
+                    max_acceptable_deviation: 50.0,
+                    clear_deviation: 20.0,
+                    minimum_off_route_fixes: 2,
+                    minimum_off_route_distance: 1000.0,
+                },
+                step_advance_condition: Arc::new(ManualStepCondition),
+                arrival_step_advance_condition: Arc::new(ManualStepCondition),
+                ..test_config()
+            },
+        );
+        // About 110m north of the route.
+        let off_route = |lng| UserLocation {
+            coordinates: GeographicCoordinate { lat: 0.001, lng },
+            horizontal_accuracy: 5.0,
+            course_over_ground: None,
+            timestamp: SystemTime::now(),
+            speed: None,
+        };
+        let deviation = |state: &NavState| match state.trip_state() {
+            TripState::Navigating { deviation, .. } => deviation,
+            _ => panic!("Expected to be navigating"),
+        };
+
+        let state = controller.get_initial_state(UserLocation {
+            coordinates: GeographicCoordinate { lat: 0.0, lng: 0.0 },
+            ..off_route(0.0)
+        });
+        let state = controller.update_user_location(off_route(0.0005), state);
+        assert_eq!(deviation(&state), RouteDeviation::NoDeviation);
+
+        // Advancing manually doesn't count the same location as a second fix off the route.
+        let state = controller.advance_to_next_step(state);
+        assert_eq!(deviation(&state), RouteDeviation::NoDeviation);
+
+        let state = controller.update_user_location(off_route(0.0015), state);
+        assert!(matches!(deviation(&state), RouteDeviation::OffRoute { .. }));
+    }
 }
@@ -3,7 +3,7 @@
 use super::step_advance::conditions::ManualStepCondition;
 use super::step_advance::{SerializableStepAdvanceCondition, StepAdvanceCondition};
 use crate::algorithms::distance_between_locations;
-use crate::deviation_detection::{RouteDeviation, RouteDeviationTracking};
+use crate::deviation_detection::{RouteDeviation, RouteDeviationState, RouteDeviationTracking};
 use crate::models::{
     Route, RouteStep, SpokenInstruction, UserLocation, VisualInstruction, Waypoint,
 };
@@ -28,7 +28,8 @@ pub struct NavState {
     trip_state: TripState,
     // This has to be here because we actually do need to update the internal state that changes throughout navigation.
     step_advance_condition: Arc<dyn StepAdvanceCondition>,
-    recording_events: Option<Vec<NavigationRecordingEvent>>,
+    recording_events: Option<Arc<NavigationRecordingEventLog>>,
+    route_deviation_state: RouteDeviationState,
 }
 
 impl NavState {
@@ -41,6 +42,7 @@ impl NavState {
             trip_state,
             step_advance_condition,
             recording_events: None,
+            route_deviation_state: RouteDeviationState::default(),
         }
     }
 
@@ -50,6 +52,7 @@ impl NavState {
             trip_state: TripState::Idle { user_location },
             step_advance_condition: Arc::new(ManualStepCondition {}), // No op condition.
             recording_events: None,
+            route_deviation_state: RouteDeviationState::default(),
         }
     }
 
@@ -67,6 +70,7 @@ impl NavState {
             },
             step_advance_condition: Arc::new(ManualStepCondition {}), // No op condition.
             recording_events: None,
+            route_deviation_state: RouteDeviationState::default(),
         }
     }
 
@@ -79,6 +83,50 @@ impl NavState {
     pub fn step_advance_condition(&self) -> Arc<dyn StepAdvanceCondition> {
         self.step_advance_condition.clone()
     }
+
+    /// The events recorded so far in this navigation session.
+    ///
+    /// This is only populated when navigating with a recording [`Navigator`](super::Navigator).
+    /// NOTE: This copies the whole log, so avoid calling it on every update.
+    pub fn recording_events(&self) -> Option<Vec<NavigationRecordingEvent>> {
+        self.recording_events
+            .as_deref()
+            .map(NavigationRecordingEventLog::events)
+    }
+
+    /// The log of events recorded so far in this navigation session.
+    #[inline]
+    pub(crate) fn recording_event_log(&self) -> Option<Arc<NavigationRecordingEventLog>> {
+        self.recording_events.clone()
+    }
+
+    /// The state carried between location updates by route deviation detection.
+    #[inline]
+    pub fn route_deviation_state(&self) -> RouteDeviationState {
+        self.route_deviation_state.clone()
+    }
+
+    /// Returns a copy of this state with the given route deviation detection state.
+    pub(crate) fn with_route_deviation_state(
+        self,
+        route_deviation_state: RouteDeviationState,
+    ) -> Self {
+        Self {
+            route_deviation_state,
+            ..self
+        }
+    }
+
+    /// Returns a copy of this state with the given recording events attached.
+    pub(crate) fn with_recording_events(
+        self,
+        recording_events: Option<Arc<NavigationRecordingEventLog>>,
+    ) -> Self {
+        Self {
+            recording_events,
+            ..self
+        }
+    }
 }
 
 #[cfg(feature = "wasm-bindgen")]
@@ -90,6 +138,8 @@ pub struct JsNavState {
     // This has to be here because we actually do need to update the internal state that changes throughout navigation.
     step_advance_condition: SerializableStepAdvanceCondition,
     recording_events: Option<Vec<NavigationRecordingEvent>>,
+    #[serde(default)]
+    route_deviation_state: RouteDeviationState,
 }
 
 #[cfg(feature = "wasm-bindgen")]
@@ -98,7 +148,10 @@ impl From<JsNavState> for NavState {
         Self {
             trip_state: value.trip_state,
             step_advance_condition: value.step_advance_condition.into(),
-            recording_events: value.recording_events,
+            recording_events: value
+                .recording_events
+                .and_then(NavigationRecordingEventLog::from_events),
+            route_deviation_state: value.route_deviation_state,
         }
     }
 }
@@ -109,7 +162,11 @@ impl From<NavState> for JsNavState {
         Self {
             trip_state: value.trip_state,
             step_advance_condition: value.step_advance_condition.to_js(),
-            recording_events: value.recording_events,
+            recording_events: value
+                .recording_events
+                .as_deref()
+                .map(NavigationRecordingEventLog::events),
+            route_deviation_state: value.route_deviation_state,
         }
     }
 }
@@ -320,7 +377,7 @@ pub struct NavigationControllerConfig {
     pub snapped_location_course_filtering: CourseFiltering,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
 #[cfg_attr(feature = "wasm-bindgen", serde(rename_all = "camelCase"))]
 #[cfg_attr(feature = "wasm-bindgen", tsify(from_wasm_abi))]
@@ -372,12 +429,122 @@ impl From<NavigationControllerConfig> for SerializableNavigationControllerConfig
 #[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
 #[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
 pub struct NavigationRecordingEvent {
-    /// The timestamp of the event.
+    /// The position of the event in the recording.
+    ///
+    /// Sequence numbers start at zero and increase monotonically,
+    /// so events can be ordered even when several share the same timestamp.
+    pub sequence: u64,
+    /// The timestamp of the event, in milliseconds since the Unix epoch.
     pub timestamp: i64,
     /// Data associated with the event.
     pub event_data: NavigationRecordingEventData,
 }
 
+/// The events recorded so far in a navigation session.
+///
+/// This is a persistent list: appending an event shares all the earlier ones
+/// with the log it was appended to.
+/// This keeps recording cheap when every [`NavState`] carries the log as of that state,
+/// and the log is passed across the FFI boundary as a reference rather than copied.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct NavigationRecordingEventLog {
+    event: NavigationRecordingEvent,
+    previous: Option<Arc<NavigationRecordingEventLog>>,
+    len: usize,
+}
+
+impl NavigationRecordingEventLog {
+    /// Returns a new log with the event appended to `previous` (if any).
+    pub(crate) fn append(
+        previous: Option<Arc<Self>>,
+        event: NavigationRecordingEvent,
+    ) -> Arc<Self> {
+        let len = previous.as_ref().map_or(0, |log| log.len) + 1;
+        Arc::new(Self {
+            event,
+            previous,
+            len,
+        })
+    }
+
+    /// Builds a log from a list of events, or returns `None` if there are none.
+    #[cfg(feature = "wasm-bindgen")]
+    pub(crate) fn from_events(events: Vec<NavigationRecordingEvent>) -> Option<Arc<Self>> {
+        events
+            .into_iter()
+            .fold(None, |log, event| Some(Self::append(log, event)))
+    }
+
+    /// The most recently recorded event.
+    pub(crate) fn last(&self) -> &NavigationRecordingEvent {
+        &self.event
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl NavigationRecordingEventLog {
+    /// All the events in the log, in the order they were recorded.
+    pub fn events(&self) -> Vec<NavigationRecordingEvent> {
+        let mut events = Vec::with_capacity(self.len);
+        let mut log = Some(self);
+        while let Some(node) = log {
+            events.push(node.event.clone());
+            log = node.previous.as_deref();
+        }
+        events.reverse();
+        events
+    }
+}
+
+impl Drop for NavigationRecordingEventLog {
+    // Logs from long sessions are too deep to drop recursively without overflowing the stack.
+    fn drop(&mut self) {
+        let mut previous = self.previous.take();
+        while let Some(log) = previous {
+            previous = match Arc::try_unwrap(log) {
+                Ok(mut log) => log.previous.take(),
+                // The rest of the log is still shared with another state.
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+/// The changes between two consecutive [`TripState::Navigating`] states.
+///
+/// Recordings store these instead of full trip states wherever possible,
+/// since the remaining steps (including their geometry and instructions)
+/// rarely change from one location update to the next.
+/// The route steps and waypoints are referred to by how far the trip has advanced through them,
+/// and the instructions by their index within the current step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+pub struct TripStateDelta {
+    /// The number of steps which were completed since the previous state.
+    pub steps_advanced: u64,
+    /// The number of waypoints which were visited since the previous state.
+    pub waypoints_advanced: u64,
+    /// The index of the closest coordinate to the user's snapped location.
+    pub current_step_geometry_index: Option<u64>,
+    /// The user's raw location.
+    pub user_location: UserLocation,
+    /// The user's location as if they were exactly on the route.
+    pub snapped_user_location: UserLocation,
+    /// The trip progress.
+    pub progress: TripProgress,
+    /// The trip summary.
+    pub summary: TripSummary,
+    /// The route deviation status.
+    pub deviation: RouteDeviation,
+    /// The index of the active visual instruction within the current step.
+    pub visual_instruction_index: Option<u64>,
+    /// The index of the current spoken instruction within the current step.
+    pub spoken_instruction_index: Option<u64>,
+    /// Annotation data at the current location.
+    pub annotation_json: Option<String>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
 #[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
@@ -390,6 +557,11 @@ pub enum NavigationRecordingEventData {
         /// Updated trip state.
         trip_state: TripState,
     },
+    /// A change to the trip state, relative to the previous trip state in the recording.
+    TripStateDelta {
+        /// Changes since the previous trip state.
+        delta: TripStateDelta,
+    },
     RouteUpdate {
         /// Updated route steps.
         route: Route,
@@ -0,0 +1,209 @@
+//! Delta encoding of trip states in recordings.
+
+use crate::navigation_controller::models::{TripState, TripStateDelta};
+
+impl TripStateDelta {
+    /// Describes the change from `previous` to `next`.
+    ///
+    /// Returns `None` if the change can't be expressed as a delta
+    /// (for example, if either state is not [`TripState::Navigating`]),
+    /// in which case the full trip state should be recorded instead.
+    pub fn between(previous: &TripState, next: &TripState) -> Option<Self> {
+        let (
+            TripState::Navigating {
+                remaining_steps: previous_steps,
+                remaining_waypoints: previous_waypoints,
+                ..
+            },
+            TripState::Navigating {
+                current_step_geometry_index,
+                user_location,
+                snapped_user_location,
+                remaining_steps,
+                remaining_waypoints,
+                progress,
+                summary,
+                deviation,
+                visual_instruction,
+                spoken_instruction,
+                annotation_json,
+            },
+        ) = (previous, next)
+        else {
+            return None;
+        };
+
+        let steps_advanced = previous_steps.len().checked_sub(remaining_steps.len())?;
+        let waypoints_advanced = previous_waypoints
+            .len()
+            .checked_sub(remaining_waypoints.len())?;
+        let (visual_instructions, spoken_instructions) =
+            remaining_steps.first().map_or((&[][..], &[][..]), |step| {
+                (&step.visual_instructions[..], &step.spoken_instructions[..])
+            });
+
+        let visual_instruction_index = match visual_instruction {
+            Some(instruction) => Some(index_of(visual_instructions, instruction)?),
+            None => None,
+        };
+        let spoken_instruction_index = match spoken_instruction {
+            Some(instruction) => Some(index_of(spoken_instructions, instruction)?),
+            None => None,
+        };
+
+        let delta = Self {
+            steps_advanced: steps_advanced as u64,
+            waypoints_advanced: waypoints_advanced as u64,
+            current_step_geometry_index: *current_step_geometry_index,
+            user_location: *user_location,
+            snapped_user_location: *snapped_user_location,
+            progress: progress.clone(),
+            summary: summary.clone(),
+            deviation: *deviation,
+            visual_instruction_index,
+            spoken_instruction_index,
+            annotation_json: annotation_json.clone(),
+        };
+
+        // Only use the delta if it faithfully reproduces the next state.
+        (delta.apply(previous).as_ref() == Some(next)).then_some(delta)
+    }
+
+    /// Rebuilds the full trip state by applying this delta to the `previous` state.
+    ///
+    /// Returns `None` if the delta doesn't fit the previous state.
+    pub fn apply(&self, previous: &TripState) -> Option<TripState> {
+        let TripState::Navigating {
+            remaining_steps,
+            remaining_waypoints,
+            ..
+        } = previous
+        else {
+            return None;
+        };
+
+        let remaining_steps = remaining_steps
+            .get(usize::try_from(self.steps_advanced).ok()?..)?
+            .to_vec();
+        let remaining_waypoints = remaining_waypoints
+            .get(usize::try_from(self.waypoints_advanced).ok()?..)?
+            .to_vec();
+        let (visual_instructions, spoken_instructions) =
+            remaining_steps.first().map_or((&[][..], &[][..]), |step| {
+                (&step.visual_instructions[..], &step.spoken_instructions[..])
+            });
+        let visual_instruction = match self.visual_instruction_index {
+            Some(index) => Some(instruction_at(visual_instructions, index)?),
+            None => None,
+        };
+        let spoken_instruction = match self.spoken_instruction_index {
+            Some(index) => Some(instruction_at(spoken_instructions, index)?),
+            None => None,
+        };
+
+        Some(TripState::Navigating {
+            current_step_geometry_index: self.current_step_geometry_index,
+            user_location: self.user_location,
+            snapped_user_location: self.snapped_user_location,
+            remaining_steps,
+            remaining_waypoints,
+            progress: self.progress.clone(),
+            summary: self.summary.clone(),
+            deviation: self.deviation,
+            visual_instruction,
+            spoken_instruction,
+            annotation_json: self.annotation_json.clone(),
+        })
+    }
+}
+
+/// Finds the index of an instruction within a step.
+fn index_of<T: PartialEq>(instructions: &[T], instruction: &T) -> Option<u64> {
+    instructions
+        .iter()
+        .position(|candidate| candidate == instruction)
+        .map(|index| index as u64)
+}
+
+/// Looks up an instruction within a step by index.
+fn instruction_at<T: Clone>(instructions: &[T], index: u64) -> Option<T> {
+    instructions.get(usize::try_from(index).ok()?).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::navigation_controller::test_helpers::{get_test_route, test_config, TestRoute};
+    use crate::navigation_controller::{NavigationController, Navigator};
+    use crate::simulation::{
+        advance_location_simulation, location_simulation_from_route, LocationBias,
+    };
+
+    #[test]
+    fn deltas_reproduce_every_state_of_a_trip() {
+        let route = get_test_route(TestRoute::Extended);
+        let mut simulation_state =
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let controller = NavigationController::new(route, test_config());
+
+        let mut state = controller.get_initial_state(simulation_state.current_location);
+        let mut steps_advanced = 0;
+        loop {
+            simulation_state = advance_location_simulation(&simulation_state);
+            let next_state =
+                controller.update_user_location(simulation_state.current_location, state.clone());
+            if matches!(next_state.trip_state(), TripState::Complete { .. }) {
+                // Completion can't be described as a delta.
+                assert_eq!(
+                    TripStateDelta::between(&state.trip_state(), &next_state.trip_state()),
+                    None
+                );
+                break;
+            }
+
+            let delta = TripStateDelta::between(&state.trip_state(), &next_state.trip_state())
+                .expect("Expected a delta between navigating states");
+            assert_eq!(
+                delta.apply(&state.trip_state()),
+                Some(next_state.trip_state())
+            );
+            steps_advanced += delta.steps_advanced;
+            state = next_state;
+        }
+
+        assert!(steps_advanced > 0);
+    }
+
+    #[test]
+    fn delta_does_not_apply_to_unrelated_state() {
+        let route = get_test_route(TestRoute::Extended);
+        let location = location_simulation_from_route(&route, None, LocationBias::None, None)
+            .expect("Unable to create simulation")
+            .current_location;
+        let controller = NavigationController::new(route, test_config());
+        let state = controller.get_initial_state(location);
+        let advanced = controller.advance_to_next_step(state.clone());
+
+        let delta = TripStateDelta::between(&state.trip_state(), &advanced.trip_state())
+            .expect("Expected a delta between navigating states");
+        assert_eq!(delta.steps_advanced, 1);
+
+        // The delta can't be applied to a state that has too few steps remaining.
+        let mut exhausted = advanced.trip_state();
+        if let TripState::Navigating {
+            ref mut remaining_steps,
+            ..
+        } = exhausted
+        {
+            remaining_steps.clear();
+        }
+        assert_eq!(delta.apply(&exhausted), None);
+        assert_eq!(
+            delta.apply(&TripState::Idle {
+                user_location: None
+            }),
+            None
+        );
+    }
+}
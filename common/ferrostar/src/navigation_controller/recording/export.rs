@@ -0,0 +1,319 @@
+//! Export of [`NavigationRecording`]s to common GIS formats.
+//!
+//! These make it easy to inspect a recorded session in tools like JOSM or QGIS.
+
+use super::{unix_millis, NavigationRecording, NavigationRecordingError};
+use crate::deviation_detection::RouteDeviation;
+use crate::models::{GeographicCoordinate, UserLocation};
+use crate::navigation_controller::models::{NavigationRecordingEventData, TripState};
+use chrono::{DateTime, SecondsFormat};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+impl NavigationRecording {
+    /// Exports the raw user locations in the recording as a GPX track.
+    pub fn to_gpx(&self) -> String {
+        let mut gpx = String::new();
+        gpx.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        gpx.push('\n');
+        let _ = writeln!(
+            gpx,
+            r#"<gpx version="1.1" creator="Ferrostar {}" xmlns="http://www.topografix.com/GPX/1/1">"#,
+            escape_xml(&self.version)
+        );
+        if let Some(time) = format_time(self.initial_timestamp) {
+            let _ = writeln!(gpx, "  <metadata><time>{time}</time></metadata>");
+        }
+        gpx.push_str("  <trk>\n    <trkseg>\n");
+
+        for user_location in self.user_locations() {
+            let GeographicCoordinate { lat, lng } = user_location.coordinates;
+            let _ = write!(gpx, r#"      <trkpt lat="{lat}" lon="{lng}">"#);
+            if let Some(time) = unix_millis(user_location.timestamp).and_then(format_time) {
+                let _ = write!(gpx, "<time>{time}</time>");
+            }
+            gpx.push_str("</trkpt>\n");
+        }
+
+        gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+        gpx
+    }
+
+    /// Exports the recording as a `GeoJSON` feature collection.
+    ///
+    /// Every feature has a `kind` property, which is one of:
+    ///
+    /// - `route`: The route geometry (one feature for the initial route, and one for each new route).
+    /// - `raw_track`: The raw user locations.
+    /// - `snapped_track`: The user locations snapped to the route.
+    /// - `step_advance`: The point at which navigation advanced to the next step.
+    /// - `off_route`: The point at which the user was first reported to be off route.
+    pub fn to_geojson(&self) -> Result<String, NavigationRecordingError> {
+        let recording = self.clone().with_full_trip_states()?;
+
+        let mut features = vec![line_feature(
+            &recording.initial_route.geometry,
+            &json!({ "kind": "route" }),
+        )];
+        let mut route_geometry = &recording.initial_route.geometry;
+        let mut snapped_track = Vec::new();
+        let mut previous_trip_state: Option<&TripState> = None;
+
+        for event in &recording.events {
+            let time = format_time(event.timestamp);
+            match &event.event_data {
+                NavigationRecordingEventData::RouteUpdate { route } => {
+                    if route.geometry != *route_geometry {
+                        features.push(line_feature(
+                            &route.geometry,
+                            &json!({ "kind": "route", "sequence": event.sequence, "time": time }),
+                        ));
+                        route_geometry = &route.geometry;
+                    }
+                    // Steps and deviation are relative to the new route.
+                    previous_trip_state = None;
+                }
+                NavigationRecordingEventData::TripStateUpdate { trip_state } => {
+                    let TripState::Navigating {
+                        user_location,
+                        snapped_user_location,
+                        remaining_steps,
+                        deviation,
+                        ..
+                    } = trip_state
+                    else {
+                        previous_trip_state = Some(trip_state);
+                        continue;
+                    };
+                    snapped_track.push(snapped_user_location.coordinates);
+
+                    let (previous_step_count, previous_deviation) = match previous_trip_state {
+                        Some(TripState::Navigating {
+                            remaining_steps,
+                            deviation,
+                            ..
+                        }) => (Some(remaining_steps.len()), Some(deviation)),
+                        _ => (None, None),
+                    };
+
+                    if previous_step_count.is_some_and(|count| count > remaining_steps.len()) {
+                        features.push(point_feature(
+                            snapped_user_location.coordinates,
+                            &json!({
+                                "kind": "step_advance",
+                                "sequence": event.sequence,
+                                "time": time,
+                                "instruction": remaining_steps.first().map(|step| &step.instruction),
+                            }),
+                        ));
+                    }
+
+                    if let RouteDeviation::OffRoute {
+                        deviation_from_route_line,
+                    } = deviation
+                    {
+                        if !matches!(previous_deviation, Some(RouteDeviation::OffRoute { .. })) {
+                            features.push(point_feature(
+                                user_location.coordinates,
+                                &json!({
+                                    "kind": "off_route",
+                                    "sequence": event.sequence,
+                                    "time": time,
+                                    "deviation_from_route_line": deviation_from_route_line,
+                                }),
+                            ));
+                        }
+                    }
+
+                    previous_trip_state = Some(trip_state);
+                }
+                _ => {}
+            }
+        }
+
+        let raw_track: Vec<_> = recording
+            .user_locations()
+            .map(|location| location.coordinates)
+            .collect();
+        features.push(line_feature(&raw_track, &json!({ "kind": "raw_track" })));
+        features.push(line_feature(
+            &snapped_track,
+            &json!({ "kind": "snapped_track" }),
+        ));
+
+        Ok(serde_json::to_string(&json!({
+            "type": "FeatureCollection",
+            "features": features,
+        }))?)
+    }
+
+    /// All raw user locations in the recording, in order.
+    fn user_locations(&self) -> impl Iterator<Item = &UserLocation> {
+        self.events
+            .iter()
+            .filter_map(|event| match &event.event_data {
+                NavigationRecordingEventData::LocationUpdate { user_location } => {
+                    Some(user_location)
+                }
+                _ => None,
+            })
+    }
+}
+
+/// Exports a JSON-encoded recording as a GPX track.
+///
+/// See [`NavigationRecording::to_gpx`] for details.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+pub fn recording_to_gpx(json: &str) -> Result<String, NavigationRecordingError> {
+    Ok(NavigationRecording::from_json(json)?.to_gpx())
+}
+
+/// Exports a JSON-encoded recording as `GeoJSON`.
+///
+/// See [`NavigationRecording::to_geojson`] for details.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+pub fn recording_to_geojson(json: &str) -> Result<String, NavigationRecordingError> {
+    NavigationRecording::from_json(json)?.to_geojson()
+}
+
+fn line_feature(coordinates: &[GeographicCoordinate], properties: &Value) -> Value {
+    json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "LineString",
+            "coordinates": coordinates.iter().map(|c| [c.lng, c.lat]).collect::<Vec<_>>(),
+        },
+        "properties": properties,
+    })
+}
+
+fn point_feature(coordinate: GeographicCoordinate, properties: &Value) -> Value {
+    json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "Point",
+            "coordinates": [coordinate.lng, coordinate.lat],
+        },
+        "properties": properties,
+    })
+}
+
+/// Formats a timestamp (in milliseconds since the Unix epoch) as an ISO 8601 string.
+fn format_time(timestamp: i64) -> Option<String> {
+    DateTime::from_timestamp_millis(timestamp)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deviation_detection::RouteDeviationTracking;
+    use crate::navigation_controller::models::NavigationControllerConfig;
+    use crate::navigation_controller::recording::RecordingNavigationController;
+    use crate::navigation_controller::test_helpers::{get_test_route, test_config, TestRoute};
+    use crate::navigation_controller::Navigator;
+    use crate::simulation::{
+        advance_location_simulation, location_simulation_from_route, LocationBias,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Records a full trip with a single excursion off the route.
+    fn record_trip() -> NavigationRecording {
+        let route = get_test_route(TestRoute::Extended);
+        let config = NavigationControllerConfig {
+            route_deviation_tracking: RouteDeviationTracking::StaticThreshold {
+                minimum_horizontal_accuracy: 25,
+                max_acceptable_deviation: 20.0,
+            },
+            ..test_config()
+        };
+        let mut simulation_state =
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let controller = RecordingNavigationController::new(route.clone(), config.clone());
+
+        let mut state = controller.get_initial_state(UserLocation {
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            ..simulation_state.current_location
+        });
+        let mut seconds = 1_700_000_000;
+        while !matches!(state.trip_state(), TripState::Complete { .. }) {
+            simulation_state = advance_location_simulation(&simulation_state);
+            seconds += 1;
+            let mut location = UserLocation {
+                timestamp: UNIX_EPOCH + Duration::from_secs(seconds),
+                ..simulation_state.current_location
+            };
+            if (20..23).contains(&(seconds - 1_700_000_000)) {
+                // Roughly 100m north of the route.
+                location.coordinates.lat += 0.001;
+            }
+            state = controller.update_user_location(location, state);
+        }
+
+        NavigationRecording {
+            events: state.recording_events().expect("Expected recorded events"),
+            ..NavigationRecording::new(config, route)
+        }
+    }
+
+    #[test]
+    fn exports_gpx_track() {
+        let recording = record_trip();
+        let location_count = recording.user_locations().count();
+
+        let gpx = recording.to_gpx();
+
+        assert!(gpx.starts_with("<?xml"));
+        assert_eq!(gpx.matches("<trkpt ").count(), location_count);
+        assert!(gpx.contains("<time>2023-11-14T22:13:20.000Z</time></trkpt>"));
+        assert!(gpx.trim_end().ends_with("</gpx>"));
+    }
+
+    #[test]
+    fn exports_geojson_features() {
+        let recording = record_trip();
+        let step_count = recording.initial_route.steps.len();
+
+        let geojson: Value =
+            serde_json::from_str(&recording.to_geojson().expect("Unable to export GeoJSON"))
+                .expect("Invalid GeoJSON");
+
+        let features = geojson["features"].as_array().expect("Expected features");
+        let features_of_kind = |kind: &str| {
+            features
+                .iter()
+                .filter(|feature| feature["properties"]["kind"] == kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(geojson["type"], "FeatureCollection");
+        assert_eq!(features_of_kind("route").len(), 1);
+        assert_eq!(
+            features_of_kind("raw_track")[0]["geometry"]["coordinates"]
+                .as_array()
+                .map(Vec::len),
+            Some(recording.user_locations().count())
+        );
+        assert!(
+            features_of_kind("snapped_track")[0]["geometry"]["coordinates"]
+                .as_array()
+                .is_some_and(|coordinates| !coordinates.is_empty())
+        );
+        // Every step after the first is advanced into before arrival.
+        assert_eq!(features_of_kind("step_advance").len(), step_count - 1);
+        let off_route = features_of_kind("off_route");
+        assert_eq!(off_route.len(), 1);
+        assert_eq!(off_route[0]["geometry"]["type"], "Point");
+        assert_eq!(
+            off_route[0]["properties"]["time"],
+            "2023-11-14T22:13:40.000Z"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! Upgrades serialized [`NavigationRecording`](super::NavigationRecording)s
+//! made with older versions of the recording format.
+//!
+//! Migrations operate on the raw JSON so that they don't depend on the current models.
+//! Each migration upgrades a recording by exactly one format version.
+
+use super::{NavigationRecordingError, RECORDING_FORMAT_VERSION};
+use crate::navigation_controller::models::TripSummary;
+use chrono::DateTime;
+use serde_json::{json, Value};
+
+type Migration = fn(Value) -> Result<Value, NavigationRecordingError>;
+
+/// All migrations, in order. The migration at index `n` upgrades from version `n + 1`.
+///
+/// The length of this array is tied to [`RECORDING_FORMAT_VERSION`],
+/// so bumping the format version without adding a migration will not compile.
+const MIGRATIONS: [Migration; (RECORDING_FORMAT_VERSION - 1) as usize] =
+    [migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// Upgrades a serialized recording to [`RECORDING_FORMAT_VERSION`].
+pub(super) fn migrate(recording: Value) -> Result<Value, NavigationRecordingError> {
+    let version = format_version(&recording)?;
+    if version == 0 || version > RECORDING_FORMAT_VERSION {
+        return Err(NavigationRecordingError::UnsupportedFormatVersion { version });
+    }
+
+    MIGRATIONS[(version - 1) as usize..]
+        .iter()
+        .try_fold(recording, |recording, migration| migration(recording))
+}
+
+/// Reads the format version of a serialized recording.
+///
+/// Recordings made before the format was versioned are treated as version 1.
+fn format_version(recording: &Value) -> Result<u32, NavigationRecordingError> {
+    let Some(recording) = recording.as_object() else {
+        return Err(invalid("expected the recording to be an object"));
+    };
+
+    match recording.get("format_version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| invalid("format_version must be a non-negative integer")),
+    }
+}
+
+/// Version 2 changed all timestamps from seconds to milliseconds,
+/// added sequence numbers to events,
+/// and fills in fields which were added to the models before the format was versioned.
+fn migrate_v1_to_v2(mut recording: Value) -> Result<Value, NavigationRecordingError> {
+    let Some(fields) = recording.as_object_mut() else {
+        return Err(invalid("expected the recording to be an object"));
+    };
+
+    if let Some(initial_timestamp) = fields.get_mut("initial_timestamp") {
+        seconds_to_millis(initial_timestamp);
+    }
+    // Trips which predate the trip summary are assumed to have started with the recording.
+    let started_at = fields
+        .get("initial_timestamp")
+        .and_then(Value::as_i64)
+        .and_then(DateTime::from_timestamp_millis)
+        .unwrap_or_default();
+
+    if let Some(route) = fields.get_mut("initial_route") {
+        fill_route_defaults(route);
+    }
+    if let Some(trip_state) = fields.get_mut("initial_trip_state") {
+        fill_trip_state_defaults(trip_state, started_at)?;
+    }
+    if let Some(events) = fields.get_mut("events").and_then(Value::as_array_mut) {
+        for (sequence, event) in events.iter_mut().enumerate() {
+            let Some(event) = event.as_object_mut() else {
+                continue;
+            };
+            event.insert("sequence".to_string(), json!(sequence));
+            if let Some(timestamp) = event.get_mut("timestamp") {
+                seconds_to_millis(timestamp);
+            }
+
+            let Some(event_data) = event.get_mut("event_data") else {
+                continue;
+            };
+            if let Some(route) = event_data.pointer_mut("/RouteUpdate/route") {
+                fill_route_defaults(route);
+            }
+            if let Some(trip_state) = event_data.pointer_mut("/TripStateUpdate/trip_state") {
+                fill_trip_state_defaults(trip_state, started_at)?;
+            }
+        }
+    }
+
+    fields.insert("format_version".to_string(), json!(2));
+    Ok(recording)
+}
+
+/// Version 3 added trip state delta events.
+///
+/// Existing events are unaffected, so this only bumps the version.
+fn migrate_v2_to_v3(mut recording: Value) -> Result<Value, NavigationRecordingError> {
+    let Some(fields) = recording.as_object_mut() else {
+        return Err(invalid("expected the recording to be an object"));
+    };
+
+    fields.insert("format_version".to_string(), json!(3));
+    Ok(recording)
+}
+
+fn seconds_to_millis(timestamp: &mut Value) {
+    if let Some(seconds) = timestamp.as_i64() {
+        *timestamp = json!(seconds.saturating_mul(1000));
+    }
+}
+
+fn fill_route_defaults(route: &mut Value) {
+    if let Some(steps) = route.get_mut("steps").and_then(Value::as_array_mut) {
+        steps.iter_mut().for_each(fill_route_step_defaults);
+    }
+}
+
+fn fill_route_step_defaults(step: &mut Value) {
+    insert_default(step, "exits", json!([]));
+    insert_default(step, "incidents", json!([]));
+}
+
+fn fill_trip_state_defaults(
+    trip_state: &mut Value,
+    started_at: DateTime<chrono::Utc>,
+) -> Result<(), NavigationRecordingError> {
+    let summary = serde_json::to_value(TripSummary {
+        distance_traveled: 0.0,
+        snapped_distance_traveled: 0.0,
+        started_at,
+        ended_at: None,
+    })?;
+
+    if let Some(navigating) = trip_state.get_mut("Navigating") {
+        insert_default(navigating, "summary", summary);
+        // NOTE: Field names are camelCase in the web bindings.
+        let remaining_steps = ["remaining_steps", "remainingSteps"]
+            .into_iter()
+            .find_map(|key| navigating.get(key).map(|_| key));
+        if let Some(steps) = remaining_steps
+            .and_then(|key| navigating.get_mut(key))
+            .and_then(Value::as_array_mut)
+        {
+            steps.iter_mut().for_each(fill_route_step_defaults);
+        }
+    } else if let Some(complete) = trip_state.get_mut("Complete") {
+        insert_default(complete, "summary", summary);
+    }
+
+    Ok(())
+}
+
+/// Inserts a value for the field if it is missing.
+fn insert_default(object: &mut Value, key: &str, default: Value) {
+    if let Some(object) = object.as_object_mut() {
+        object.entry(key).or_insert(default);
+    }
+}
+
+fn invalid(error: &str) -> NavigationRecordingError {
+    NavigationRecordingError::DeserializationError {
+        error: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::navigation_controller::recording::{
+        NavigationRecording, RecordingNavigationController,
+    };
+    use crate::navigation_controller::test_helpers::{get_test_route, test_config, TestRoute};
+    use crate::navigation_controller::Navigator;
+    use crate::simulation::{
+        advance_location_simulation, location_simulation_from_route, LocationBias,
+    };
+
+    /// Records a short trip and returns the serialized recording.
+    fn current_recording() -> Value {
+        let route = get_test_route(TestRoute::Extended);
+        let mut simulation_state =
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let controller = RecordingNavigationController::new(route, test_config());
+
+        let mut state = controller.get_initial_state(simulation_state.current_location);
+        for _ in 0..5 {
+            simulation_state = advance_location_simulation(&simulation_state);
+            state = controller.update_user_location(simulation_state.current_location, state);
+        }
+        let json = controller
+            .get_recording(state.recording_events().expect("Expected recorded events"))
+            .expect("Unable to serialize recording");
+
+        let mut recording: Value = serde_json::from_str(&json).expect("Invalid JSON");
+        // Use whole seconds so that the timestamps survive a round trip through version 1.
+        recording["initial_timestamp"] = json!(1_700_000_000_000_i64);
+        let events = recording["events"].as_array_mut().expect("Expected events");
+        for (event, timestamp) in events
+            .iter_mut()
+            .zip((1_700_000_000_000_i64..).step_by(1000))
+        {
+            event["timestamp"] = json!(timestamp);
+        }
+        recording
+    }
+
+    /// Converts a recording to the version 1 format,
+    /// as it would have been written by an older version of Ferrostar.
+    fn downgrade_to_v1(recording: &Value) -> Value {
+        let mut recording = recording.clone();
+        let fields = recording.as_object_mut().expect("Expected an object");
+        fields.remove("format_version");
+        fields["initial_timestamp"] = json!(fields["initial_timestamp"].as_i64().unwrap() / 1000);
+        for step in fields["initial_route"]["steps"].as_array_mut().unwrap() {
+            let step = step.as_object_mut().unwrap();
+            step.remove("exits");
+            step.remove("incidents");
+        }
+        for event in fields["events"].as_array_mut().unwrap() {
+            let event = event.as_object_mut().unwrap();
+            event.remove("sequence");
+            event["timestamp"] = json!(event["timestamp"].as_i64().unwrap() / 1000);
+        }
+        recording
+    }
+
+    #[test]
+    fn current_recording_is_unchanged() {
+        let recording = current_recording();
+        assert_eq!(recording["format_version"], json!(RECORDING_FORMAT_VERSION));
+
+        let migrated = migrate(recording.clone()).expect("Unable to migrate recording");
+
+        assert_eq!(migrated, recording);
+    }
+
+    #[test]
+    fn migrates_v1_recording() {
+        let recording = current_recording();
+
+        let migrated = migrate(downgrade_to_v1(&recording)).expect("Unable to migrate recording");
+
+        assert_eq!(migrated, recording);
+    }
+
+    #[test]
+    fn fills_in_missing_trip_summary() {
+        let mut recording = downgrade_to_v1(&current_recording());
+        let trip_state = recording
+            .pointer_mut("/events/2/event_data/TripStateUpdate/trip_state/Navigating")
+            .and_then(Value::as_object_mut)
+            .expect("Expected a navigating trip state");
+        trip_state.remove("summary");
+
+        let migrated = migrate(recording).expect("Unable to migrate recording");
+
+        let summary: TripSummary = serde_json::from_value(
+            migrated
+                .pointer("/events/2/event_data/TripStateUpdate/trip_state/Navigating/summary")
+                .expect("Expected a trip summary")
+                .clone(),
+        )
+        .expect("Invalid trip summary");
+        assert_eq!(
+            summary,
+            TripSummary {
+                distance_traveled: 0.0,
+                snapped_distance_traveled: 0.0,
+                started_at: DateTime::from_timestamp_millis(1_700_000_000_000)
+                    .expect("Invalid timestamp"),
+                ended_at: None,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_newer_format() {
+        let mut recording = current_recording();
+        recording["format_version"] = json!(RECORDING_FORMAT_VERSION + 1);
+
+        assert!(matches!(
+            NavigationRecording::from_json(&recording.to_string()),
+            Err(NavigationRecordingError::UnsupportedFormatVersion { version })
+                if version == RECORDING_FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_format_version() {
+        let mut recording = current_recording();
+        recording["format_version"] = json!("two");
+
+        assert!(matches!(
+            migrate(recording),
+            Err(NavigationRecordingError::DeserializationError { .. })
+        ));
+    }
+}
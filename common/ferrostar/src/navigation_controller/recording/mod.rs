@@ -0,0 +1,655 @@
+//! Recording of navigation sessions, and tools for working with the recordings.
+
+mod delta;
+pub mod export;
+mod migration;
+pub mod replay;
+pub mod sink;
+
+use super::{NavigationController, Navigator};
+use crate::models::{Route, UserLocation};
+use crate::navigation_controller::models::{
+    NavState, NavigationControllerConfig, NavigationRecordingEvent, NavigationRecordingEventData,
+    NavigationRecordingEventLog, SerializableNavigationControllerConfig, TripState, TripStateDelta,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sink::NavigationRecordingSink;
+use std::sync::Arc;
+
+#[cfg(all(feature = "std", not(feature = "web-time")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "web-time")]
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// The version of the recording format produced by this version of Ferrostar.
+///
+/// This must be incremented whenever a change is made which affects the serialized form
+/// of a recording (including changes to models like [`TripState`] or [`Route`]),
+/// and a corresponding migration added so that older recordings can still be loaded.
+pub const RECORDING_FORMAT_VERSION: u32 = 3;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NavigationRecording {
+    /// Version of Ferrostar that created this recording.
+    pub version: String,
+    /// Version of the recording format.
+    ///
+    /// Recordings made before the format was versioned do not include this field,
+    /// and are treated as version 1.
+    pub format_version: u32,
+    /// The timestamp when the navigation session started, in milliseconds since the Unix epoch.
+    pub initial_timestamp: i64,
+    /// Configuration of the navigation session.
+    pub config: SerializableNavigationControllerConfig,
+    /// The initial route assigned.
+    pub initial_route: Route,
+    /// Initial trip state.
+    pub initial_trip_state: Option<TripState>,
+    /// Collection of events that occurred during the navigation session.
+    pub events: Vec<NavigationRecordingEvent>,
+}
+
+/// Custom error type for navigation recording operations.
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(thiserror::Error))]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum NavigationRecordingError {
+    #[cfg_attr(feature = "std", error("Failed to serialize the recording: {error}."))]
+    SerializationError { error: String },
+    #[cfg_attr(
+        feature = "std",
+        error("Failed to deserialize the recording: {error}.")
+    )]
+    DeserializationError { error: String },
+    #[cfg_attr(
+        feature = "std",
+        error("Recording is not enabled for this navigation controller.")
+    )]
+    RecordingNotEnabled,
+    #[cfg_attr(
+        feature = "std",
+        error(
+            "Unsupported recording format version {version} (the newest supported version is {}).",
+            RECORDING_FORMAT_VERSION
+        )
+    )]
+    UnsupportedFormatVersion { version: u32 },
+    #[cfg_attr(
+        feature = "std",
+        error("Failed to write to the recording sink: {error}.")
+    )]
+    SinkError { error: String },
+}
+
+#[cfg(feature = "uniffi")]
+impl From<uniffi::UnexpectedUniFFICallbackError> for NavigationRecordingError {
+    fn from(e: uniffi::UnexpectedUniFFICallbackError) -> Self {
+        NavigationRecordingError::SinkError { error: e.reason }
+    }
+}
+
+impl From<serde_json::Error> for NavigationRecordingError {
+    fn from(e: serde_json::Error) -> Self {
+        NavigationRecordingError::SerializationError {
+            error: e.to_string(),
+        }
+    }
+}
+
+/// Functionality for the navigation controller that is not exported.
+impl NavigationRecording {
+    /// Creates a new navigation recorder with route configuration and initial state.
+    pub fn new(config: NavigationControllerConfig, initial_route: Route) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            format_version: RECORDING_FORMAT_VERSION,
+            initial_timestamp: Utc::now().timestamp_millis(),
+            config: SerializableNavigationControllerConfig::from(config),
+            initial_route,
+            initial_trip_state: None,
+            events: Vec::new(),
+        }
+    }
+
+    /// Serializes the navigation recording to a JSON string.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(String)` - A JSON string representation of the navigation recording
+    /// - `Err(NavigationRecordingError)` - If there was an error during JSON serialization
+    pub fn to_json(&self) -> Result<String, NavigationRecordingError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Deserializes a navigation recording from a JSON string.
+    ///
+    /// Recordings made with an older recording format are upgraded to the current one.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(NavigationRecording)` - The recording, in the current format
+    /// - `Err(NavigationRecordingError::UnsupportedFormatVersion)` - If the recording was made
+    ///   with a newer format than this version of Ferrostar understands
+    /// - `Err(NavigationRecordingError::DeserializationError)` - If the JSON is not a valid recording
+    pub fn from_json(json: &str) -> Result<Self, NavigationRecordingError> {
+        let value = serde_json::from_str(json).map_err(|e| {
+            NavigationRecordingError::DeserializationError {
+                error: e.to_string(),
+            }
+        })?;
+
+        Self::from_value(value)
+    }
+
+    /// Deserializes a navigation recording from JSON, upgrading it to the current format.
+    fn from_value(value: serde_json::Value) -> Result<Self, NavigationRecordingError> {
+        serde_json::from_value(migration::migrate(value)?).map_err(|e| {
+            NavigationRecordingError::DeserializationError {
+                error: e.to_string(),
+            }
+        })
+    }
+
+    /// Returns the recording with every [`TripStateDelta`] replaced by the full trip state it describes.
+    ///
+    /// This is useful for analyzing a recording,
+    /// since each trip state can then be understood in isolation.
+    pub fn with_full_trip_states(self) -> Result<Self, NavigationRecordingError> {
+        let mut trip_state: Option<TripState> = None;
+        let events = self
+            .events
+            .into_iter()
+            .map(|event| {
+                let full_trip_state = match &event.event_data {
+                    NavigationRecordingEventData::TripStateUpdate { trip_state } => {
+                        trip_state.clone()
+                    }
+                    NavigationRecordingEventData::TripStateDelta { delta } => trip_state
+                        .as_ref()
+                        .and_then(|previous| delta.apply(previous))
+                        .ok_or_else(|| NavigationRecordingError::DeserializationError {
+                            error: format!(
+                                "The trip state delta in event {} does not apply to the previous trip state",
+                                event.sequence
+                            ),
+                        })?,
+                    _ => return Ok(event),
+                };
+                trip_state = Some(full_trip_state.clone());
+
+                Ok(NavigationRecordingEvent {
+                    event_data: NavigationRecordingEventData::TripStateUpdate {
+                        trip_state: full_trip_state,
+                    },
+                    ..event
+                })
+            })
+            .collect::<Result<_, NavigationRecordingError>>()?;
+
+        Ok(Self { events, ..self })
+    }
+
+    /// The first trip state recorded in the events (if any).
+    fn first_trip_state(&self) -> Option<TripState> {
+        self.events
+            .iter()
+            .find_map(|event| match &event.event_data {
+                NavigationRecordingEventData::TripStateUpdate { trip_state } => {
+                    Some(trip_state.clone())
+                }
+                _ => None,
+            })
+    }
+
+    /// Records a location update from the user during navigation.
+    pub fn record_location_update(self, user_location: UserLocation) -> Self {
+        self.add_event(NavigationRecordingEventData::LocationUpdate { user_location })
+    }
+
+    /// Records a trip state update during navigation.
+    pub fn record_trip_state_update(self, trip_state: TripState) -> Self {
+        self.add_event(NavigationRecordingEventData::TripStateUpdate { trip_state })
+    }
+
+    /// Records a route update during navigation.
+    pub fn record_route_update(self, route: Route) -> Self {
+        self.add_event(NavigationRecordingEventData::RouteUpdate { route })
+    }
+
+    /// Records an error that occurred during navigation.
+    pub fn record_navigation_error(self, error_message: String) -> Self {
+        self.add_event(NavigationRecordingEventData::Error { error_message })
+    }
+
+    /// Helper method to add an event to the recording.
+    pub fn add_event(self, event_data: NavigationRecordingEventData) -> Self {
+        let mut new_recording = self;
+        let sequence = new_recording.events.len() as u64;
+        new_recording
+            .events
+            .push(NavigationRecordingEvent::new(sequence, event_data));
+
+        new_recording
+    }
+}
+
+impl NavigationRecordingEvent {
+    /// Creates a new event with the given sequence number.
+    ///
+    /// The timestamp is taken from the user location associated with the event if there is one.
+    /// This ensures that events are timed correctly when the location updates are not live
+    /// (e.g. during simulation or replay).
+    /// Otherwise, the event is timestamped with the current time.
+    pub fn new(sequence: u64, event_data: NavigationRecordingEventData) -> Self {
+        let timestamp = event_data
+            .location_timestamp()
+            .and_then(unix_millis)
+            .unwrap_or_else(|| Utc::now().timestamp_millis());
+
+        Self {
+            sequence,
+            timestamp,
+            event_data,
+        }
+    }
+}
+
+/// Converts a timestamp to milliseconds since the Unix epoch.
+fn unix_millis(time: SystemTime) -> Option<i64> {
+    let duration = time.duration_since(UNIX_EPOCH).ok()?;
+    i64::try_from(duration.as_millis()).ok()
+}
+
+impl NavigationRecordingEventData {
+    /// The timestamp of the user location associated with this event (if any).
+    fn location_timestamp(&self) -> Option<SystemTime> {
+        match self {
+            NavigationRecordingEventData::LocationUpdate { user_location } => {
+                Some(user_location.timestamp)
+            }
+            NavigationRecordingEventData::TripStateUpdate { trip_state } => match trip_state {
+                TripState::Idle { user_location } => {
+                    user_location.map(|location| location.timestamp)
+                }
+                TripState::Navigating { user_location, .. }
+                | TripState::Complete { user_location, .. } => Some(user_location.timestamp),
+            },
+            NavigationRecordingEventData::TripStateDelta { delta } => {
+                Some(delta.user_location.timestamp)
+            }
+            NavigationRecordingEventData::RouteUpdate { .. }
+            | NavigationRecordingEventData::Error { .. } => None,
+        }
+    }
+}
+
+/// A [`Navigator`] which wraps a [`NavigationController`] and records everything that happens.
+///
+/// Like the controller it wraps, this type has no interior mutability.
+/// Recorded events are accumulated in the [`NavState`] returned by each method
+/// (as a [`NavigationRecordingEventLog`], which shares earlier events with the previous state),
+/// and can be serialized at any time via [`Navigator::get_recording`].
+///
+/// Alternatively, events can be streamed to a [`NavigationRecordingSink`] as they happen
+/// (see [`RecordingNavigationController::with_sink`]).
+/// In this case, only the most recent event is kept in the [`NavState`]
+/// so that sequence numbers carry on from one update to the next.
+///
+/// The following are recorded:
+/// - Every location passed to [`Navigator::get_initial_state`] and [`Navigator::update_user_location`].
+/// - Every change to the [`TripState`] (including the initial state).
+///   Where possible, changes are recorded as a [`TripStateDelta`] rather than the full state.
+/// - The route whenever navigation is (re)started via [`Navigator::get_initial_state`].
+pub struct RecordingNavigationController {
+    controller: NavigationController,
+    recording: NavigationRecording,
+    sink: Option<Arc<dyn NavigationRecordingSink>>,
+}
+
+impl RecordingNavigationController {
+    /// Create a recording navigation controller for a route and configuration.
+    pub fn new(route: Route, config: NavigationControllerConfig) -> Self {
+        Self {
+            controller: NavigationController::new(route.clone(), config.clone()),
+            recording: NavigationRecording::new(config, route),
+            sink: None,
+        }
+    }
+
+    /// Create a recording navigation controller which streams events to a sink.
+    ///
+    /// This immediately begins the recording on the sink.
+    /// If the sink later fails to record an event, the failure is logged
+    /// and navigation continues uninterrupted.
+    pub fn with_sink(
+        route: Route,
+        config: NavigationControllerConfig,
+        sink: Arc<dyn NavigationRecordingSink>,
+    ) -> Result<Self, NavigationRecordingError> {
+        let controller = Self::new(route, config);
+        sink.begin_recording(controller.recording.to_json()?)?;
+
+        Ok(Self {
+            sink: Some(sink),
+            ..controller
+        })
+    }
+
+    /// Attaches the events from the previous state, plus any new ones, to the next state.
+    fn record(
+        &self,
+        previous_state: Option<&NavState>,
+        next_state: NavState,
+        new_events: impl IntoIterator<Item = NavigationRecordingEventData>,
+    ) -> NavState {
+        let mut log = previous_state.and_then(NavState::recording_event_log);
+        let next_sequence = log.as_ref().map_or(0, |log| log.last().sequence + 1);
+        let new_events = (next_sequence..)
+            .zip(new_events)
+            .map(|(sequence, event_data)| NavigationRecordingEvent::new(sequence, event_data));
+
+        for event in new_events {
+            if let Some(sink) = &self.sink {
+                if let Err(error) = sink.record_event(event.clone()) {
+                    log::warn!(
+                        "Failed to record navigation event {}: {error}",
+                        event.sequence
+                    );
+                }
+                // Only the latest event is kept when streaming to a sink.
+                log = None;
+            }
+            log = Some(NavigationRecordingEventLog::append(log, event));
+        }
+        next_state.with_recording_events(log)
+    }
+
+    /// Returns a trip state event if the trip state changed.
+    fn trip_state_change(
+        previous_state: &NavState,
+        next_state: &NavState,
+    ) -> Option<NavigationRecordingEventData> {
+        let previous_trip_state = previous_state.trip_state();
+        let trip_state = next_state.trip_state();
+        if previous_trip_state == trip_state {
+            return None;
+        }
+
+        Some(
+            match TripStateDelta::between(&previous_trip_state, &trip_state) {
+                Some(delta) => NavigationRecordingEventData::TripStateDelta { delta },
+                None => NavigationRecordingEventData::TripStateUpdate { trip_state },
+            },
+        )
+    }
+}
+
+impl Navigator for RecordingNavigationController {
+    fn get_initial_state(&self, location: UserLocation) -> NavState {
+        let state = self.controller.get_initial_state(location);
+        let events = [
+            NavigationRecordingEventData::RouteUpdate {
+                route: self.recording.initial_route.clone(),
+            },
+            NavigationRecordingEventData::LocationUpdate {
+                user_location: location,
+            },
+            NavigationRecordingEventData::TripStateUpdate {
+                trip_state: state.trip_state(),
+            },
+        ];
+
+        self.record(None, state, events)
+    }
+
+    fn advance_to_next_step(&self, state: NavState) -> NavState {
+        let next_state = self.controller.advance_to_next_step(state.clone());
+        let events = Self::trip_state_change(&state, &next_state);
+
+        self.record(Some(&state), next_state, events)
+    }
+
+    fn update_user_location(&self, location: UserLocation, state: NavState) -> NavState {
+        let next_state = self
+            .controller
+            .update_user_location(location, state.clone());
+        let events = core::iter::once(NavigationRecordingEventData::LocationUpdate {
+            user_location: location,
+        })
+        .chain(Self::trip_state_change(&state, &next_state));
+
+        self.record(Some(&state), next_state, events)
+    }
+
+    fn get_recording(
+        &self,
+        events: Vec<NavigationRecordingEvent>,
+    ) -> Result<String, NavigationRecordingError> {
+        let recording = NavigationRecording {
+            events,
+            ..self.recording.clone()
+        };
+
+        NavigationRecording {
+            initial_trip_state: recording.first_trip_state(),
+            ..recording
+        }
+        .to_json()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::navigation_controller::test_helpers::{get_test_route, test_config, TestRoute};
+    use crate::simulation::{
+        advance_location_simulation, location_simulation_from_route, LocationBias,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn records_full_route() {
+        let route = get_test_route(TestRoute::Extended);
+        let mut simulation_state =
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let controller = RecordingNavigationController::new(route, test_config());
+
+        let mut state = controller.get_initial_state(simulation_state.current_location);
+        let mut location_updates = 1;
+        let mut trip_states = vec![state.trip_state()];
+        while !matches!(state.trip_state(), TripState::Complete { .. }) {
+            simulation_state = advance_location_simulation(&simulation_state);
+            state = controller.update_user_location(simulation_state.current_location, state);
+            location_updates += 1;
+            if trip_states.last() != Some(&state.trip_state()) {
+                trip_states.push(state.trip_state());
+            }
+        }
+
+        let events = state.recording_events().expect("Expected recorded events");
+        let count_events = |predicate: fn(&NavigationRecordingEventData) -> bool| {
+            events
+                .iter()
+                .filter(|event| predicate(&event.event_data))
+                .count()
+        };
+        assert_eq!(
+            count_events(|data| matches!(
+                data,
+                NavigationRecordingEventData::LocationUpdate { .. }
+            )),
+            location_updates
+        );
+        assert_eq!(
+            count_events(|data| matches!(data, NavigationRecordingEventData::RouteUpdate { .. })),
+            1
+        );
+        assert_eq!(
+            count_events(|data| matches!(
+                data,
+                NavigationRecordingEventData::TripStateUpdate {
+                    trip_state: TripState::Complete { .. }
+                }
+            )),
+            1
+        );
+
+        let json = controller
+            .get_recording(events.clone())
+            .expect("Unable to serialize recording");
+        // NB: Test builds skip serializing timestamps, so we can't deserialize the typed recording.
+        let recording: serde_json::Value =
+            serde_json::from_str(&json).expect("Unable to parse recording JSON");
+        assert_eq!(
+            recording["events"].as_array().map(Vec::len),
+            Some(events.len())
+        );
+        assert!(recording["initial_trip_state"]["Navigating"].is_object());
+
+        // Most trip states are recorded as deltas, but every state can be recovered.
+        assert!(
+            count_events(|data| matches!(
+                data,
+                NavigationRecordingEventData::TripStateDelta { .. }
+            )) > trip_states.len() / 2
+        );
+        let recording = NavigationRecording {
+            events,
+            ..NavigationRecording::new(test_config(), get_test_route(TestRoute::Extended))
+        }
+        .with_full_trip_states()
+        .expect("Unable to rebuild trip states");
+        let recorded_trip_states: Vec<_> = recording
+            .events
+            .into_iter()
+            .filter_map(|event| match event.event_data {
+                NavigationRecordingEventData::TripStateUpdate { trip_state } => Some(trip_state),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(recorded_trip_states, trip_states);
+    }
+
+    #[test]
+    fn manual_advance_is_recorded() {
+        let route = get_test_route(TestRoute::Extended);
+        let location = location_simulation_from_route(&route, None, LocationBias::None, None)
+            .expect("Unable to create simulation")
+            .current_location;
+        let controller = RecordingNavigationController::new(route, test_config());
+
+        let state = controller.get_initial_state(location);
+        let initial_event_count = state.recording_events().map_or(0, |events| events.len());
+        let state = controller.advance_to_next_step(state);
+
+        let events = state.recording_events().expect("Expected recorded events");
+        assert_eq!(events.len(), initial_event_count + 1);
+        assert!(matches!(
+            events.last().map(|event| &event.event_data),
+            Some(NavigationRecordingEventData::TripStateDelta { delta }) if delta.steps_advanced == 1
+        ));
+    }
+
+    #[test]
+    fn earlier_states_keep_their_events() {
+        let route = get_test_route(TestRoute::Extended);
+        let mut simulation_state =
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let controller = RecordingNavigationController::new(route, test_config());
+
+        let initial_state = controller.get_initial_state(simulation_state.current_location);
+        let initial_events = initial_state
+            .recording_events()
+            .expect("Expected recorded events");
+        simulation_state = advance_location_simulation(&simulation_state);
+        let state = controller
+            .update_user_location(simulation_state.current_location, initial_state.clone());
+
+        // The new state extends the log without changing the one in the previous state.
+        let events = state.recording_events().expect("Expected recorded events");
+        assert!(events.len() > initial_events.len());
+        assert_eq!(
+            initial_state.recording_events().map(|events| events.len()),
+            Some(initial_events.len())
+        );
+        assert_eq!(
+            events[initial_events.len() - 1].sequence,
+            initial_events.len() as u64 - 1
+        );
+    }
+
+    #[test]
+    fn long_event_log_can_be_dropped() {
+        let log = (0..1_000_000).fold(None, |log, sequence| {
+            Some(NavigationRecordingEventLog::append(
+                log,
+                NavigationRecordingEvent {
+                    sequence,
+                    timestamp: 0,
+                    event_data: NavigationRecordingEventData::Error {
+                        error_message: String::new(),
+                    },
+                },
+            ))
+        });
+        drop(log);
+    }
+
+    #[test]
+    fn events_are_sequenced_and_timed_by_location() {
+        let route = get_test_route(TestRoute::Extended);
+        let mut simulation_state =
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let controller = RecordingNavigationController::new(route, test_config());
+
+        let mut state = controller.get_initial_state(simulation_state.current_location);
+        for _ in 0..10 {
+            simulation_state = advance_location_simulation(&simulation_state);
+            let location = UserLocation {
+                timestamp: simulation_state.current_location.timestamp
+                    + Duration::from_millis(1234),
+                ..simulation_state.current_location
+            };
+            state = controller.update_user_location(location, state);
+        }
+
+        let events = state.recording_events().expect("Expected recorded events");
+        for (index, event) in events.iter().enumerate() {
+            assert_eq!(event.sequence, index as u64);
+        }
+
+        for event in &events {
+            if let NavigationRecordingEventData::LocationUpdate { user_location } =
+                &event.event_data
+            {
+                let expected = user_location
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Location timestamp before the Unix epoch")
+                    .as_millis() as i64;
+                assert_eq!(event.timestamp, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn plain_controller_does_not_record() {
+        let route = get_test_route(TestRoute::Extended);
+        let location = location_simulation_from_route(&route, None, LocationBias::None, None)
+            .expect("Unable to create simulation")
+            .current_location;
+        let controller = NavigationController::new(route, test_config());
+
+        let state = controller.get_initial_state(location);
+        assert!(state.recording_events().is_none());
+        assert!(matches!(
+            controller.get_recording(Vec::new()),
+            Err(NavigationRecordingError::RecordingNotEnabled)
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+//! Deterministic replay of [`NavigationRecording`]s.
+//!
+//! Replaying a recording feeds every recorded location update through a fresh
+//! [`NavigationController`] built from the recorded configuration and route,
+//! and compares the resulting [`TripState`]s with those that were recorded.
+//! This makes it possible to turn recordings of real-world sessions into regression tests.
+//!
+//! NOTE: Only location updates are replayed.
+//! Manual step advances are not distinguishable from other trip state updates in a recording,
+//! so these will show up as divergences.
+
+use super::{NavigationRecording, NavigationRecordingError};
+use crate::navigation_controller::models::{
+    NavState, NavigationControllerConfig, NavigationRecordingEventData, TripState, TripSummary,
+};
+use crate::navigation_controller::{NavigationController, Navigator};
+
+/// A point at which the replayed trip state differs from the recorded one.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct ReplayDivergence {
+    /// The index of the event in the recording at which the divergence was detected.
+    ///
+    /// This is the index of the last recorded trip state update before the divergence was detected.
+    pub event_index: u64,
+    /// The trip state in the recording.
+    pub recorded: TripState,
+    /// The trip state computed during replay.
+    pub replayed: TripState,
+}
+
+/// The outcome of replaying a [`NavigationRecording`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct ReplayReport {
+    /// The number of location updates which were replayed.
+    pub location_updates: u64,
+    /// All points where the replayed trip state differed from the recording.
+    ///
+    /// After each divergence, replay resumes from the recorded trip state,
+    /// so a single difference will not cascade through the rest of the report.
+    pub divergences: Vec<ReplayDivergence>,
+}
+
+impl ReplayReport {
+    /// Returns true if the replay matched the recording exactly.
+    pub fn is_exact(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Replays a recording and reports where the replayed trip states differ from the recorded ones.
+///
+/// Timestamps in the trip summary are not compared,
+/// since these reflect the wall clock time at which each session took place.
+///
+/// Returns an error if the recorded trip states can't be rebuilt
+/// (see [`NavigationRecording::with_full_trip_states`]).
+pub fn replay_recording(
+    recording: &NavigationRecording,
+) -> Result<ReplayReport, NavigationRecordingError> {
+    let recording = recording.clone().with_full_trip_states()?;
+    let config = NavigationControllerConfig::from(recording.config.clone());
+    let mut controller = NavigationController::new(recording.initial_route.clone(), config.clone());
+    let mut state: Option<NavState> = None;
+    let mut expected: Option<(usize, TripState)> = None;
+    let mut report = ReplayReport {
+        location_updates: 0,
+        divergences: Vec::new(),
+    };
+
+    for (index, event) in recording.events.iter().enumerate() {
+        match &event.event_data {
+            NavigationRecordingEventData::LocationUpdate { user_location } => {
+                // The previous update is now complete, so check it before moving on.
+                state = check_divergence(state, expected.as_ref(), &mut report);
+                state = Some(match state {
+                    Some(state) => controller.update_user_location(*user_location, state),
+                    None => controller.get_initial_state(*user_location),
+                });
+                report.location_updates += 1;
+            }
+            NavigationRecordingEventData::TripStateUpdate { trip_state } => {
+                expected = Some((index, trip_state.clone()));
+            }
+            NavigationRecordingEventData::RouteUpdate { route } => {
+                check_divergence(state, expected.as_ref(), &mut report);
+                controller = NavigationController::new(route.clone(), config.clone());
+                state = None;
+                expected = None;
+            }
+            // Deltas have already been expanded into full trip states.
+            NavigationRecordingEventData::TripStateDelta { .. }
+            | NavigationRecordingEventData::Error { .. } => {}
+        }
+    }
+    check_divergence(state, expected.as_ref(), &mut report);
+
+    Ok(report)
+}
+
+/// Replays a JSON-encoded recording.
+///
+/// See [`replay_recording`] for details.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+pub fn replay_recording_json(json: &str) -> Result<ReplayReport, NavigationRecordingError> {
+    replay_recording(&NavigationRecording::from_json(json)?)
+}
+
+/// Compares the replayed state with the expected one, recording any divergence.
+///
+/// Returns the state from which replay should continue.
+fn check_divergence(
+    state: Option<NavState>,
+    expected: Option<&(usize, TripState)>,
+    report: &mut ReplayReport,
+) -> Option<NavState> {
+    let (Some(state), Some((event_index, recorded))) = (state.as_ref(), expected) else {
+        return state;
+    };
+
+    let replayed = state.trip_state();
+    if with_summary_timestamps_from(replayed.clone(), recorded) == *recorded {
+        return Some(state.clone());
+    }
+
+    report.divergences.push(ReplayDivergence {
+        event_index: *event_index as u64,
+        recorded: recorded.clone(),
+        replayed,
+    });
+
+    // Resume from the recorded state so that one divergence doesn't cascade.
+    // The route deviation state can't be recovered from the recording,
+    // so carry over the replayed one rather than restarting detection from scratch.
+    Some(
+        NavState::new(recorded.clone(), state.step_advance_condition())
+            .with_route_deviation_state(state.route_deviation_state()),
+    )
+}
+
+/// Copies the summary timestamps from `reference` so that states can be compared.
+fn with_summary_timestamps_from(trip_state: TripState, reference: &TripState) -> TripState {
+    let reference_summary = match reference {
+        TripState::Navigating { summary, .. } | TripState::Complete { summary, .. } => summary,
+        TripState::Idle { .. } => return trip_state,
+    };
+    let normalize = |summary: TripSummary| TripSummary {
+        started_at: reference_summary.started_at,
+        ended_at: reference_summary.ended_at,
+        ..summary
+    };
+
+    match trip_state {
+        TripState::Navigating {
+            current_step_geometry_index,
+            user_location,
+            snapped_user_location,
+            remaining_steps,
+            remaining_waypoints,
+            progress,
+            summary,
+            deviation,
+            visual_instruction,
+            spoken_instruction,
+            annotation_json,
+        } => TripState::Navigating {
+            current_step_geometry_index,
+            user_location,
+            snapped_user_location,
+            remaining_steps,
+            remaining_waypoints,
+            progress,
+            summary: normalize(summary),
+            deviation,
+            visual_instruction,
+            spoken_instruction,
+            annotation_json,
+        },
+        TripState::Complete {
+            user_location,
+            summary,
+        } => TripState::Complete {
+            user_location,
+            summary: normalize(summary),
+        },
+        TripState::Idle { .. } => trip_state,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deviation_detection::{RouteDeviation, RouteDeviationTracking};
+    use crate::navigation_controller::recording::RecordingNavigationController;
+    use crate::navigation_controller::step_advance::conditions::DistanceEntryAndExitCondition;
+    use crate::navigation_controller::test_helpers::{get_test_route, test_config, TestRoute};
+    use crate::simulation::{
+        advance_location_simulation, location_simulation_from_route, LocationBias,
+    };
+    use std::sync::Arc;
+
+    /// The shared test config, with route deviation tracking and a stateful step advance condition
+    /// so that replay has some state to reproduce.
+    fn replay_config() -> NavigationControllerConfig {
+        NavigationControllerConfig {
+            route_deviation_tracking: RouteDeviationTracking::StaticThreshold {
+                minimum_horizontal_accuracy: 25,
+                max_acceptable_deviation: 10.0,
+            },
+            step_advance_condition: Arc::new(DistanceEntryAndExitCondition::default()),
+            ..test_config()
+        }
+    }
+
+    /// Records a simulated trip along the route, returning the recording.
+    ///
+    /// Recording stops when the trip is complete or the simulation reaches the end of the route.
+    fn record_trip(bias: LocationBias, config: NavigationControllerConfig) -> NavigationRecording {
+        let route = get_test_route(TestRoute::SelfIntersecting);
+        let mut simulation_state = location_simulation_from_route(&route, Some(10.0), bias, None)
+            .expect("Unable to create simulation");
+        let controller = RecordingNavigationController::new(route.clone(), config.clone());
+
+        let mut state = controller.get_initial_state(simulation_state.current_location);
+        while !matches!(state.trip_state(), TripState::Complete { .. }) {
+            let next_simulation_state = advance_location_simulation(&simulation_state);
+            if next_simulation_state.current_location.coordinates
+                == simulation_state.current_location.coordinates
+            {
+                break;
+            }
+            simulation_state = next_simulation_state;
+            state = controller.update_user_location(simulation_state.current_location, state);
+        }
+
+        NavigationRecording {
+            events: state.recording_events().expect("Expected recorded events"),
+            ..NavigationRecording::new(config, route)
+        }
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let recording = record_trip(LocationBias::None, replay_config());
+        let location_updates = recording
+            .events
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event.event_data,
+                    NavigationRecordingEventData::LocationUpdate { .. }
+                )
+            })
+            .count();
+
+        let report = replay_recording(&recording).expect("Unable to replay recording");
+
+        assert_eq!(report.location_updates, location_updates as u64);
+        assert!(report.is_exact(), "{:?}", report.divergences);
+    }
+
+    #[test]
+    fn replay_reports_divergence() {
+        let mut recording = record_trip(LocationBias::Left(4.0), replay_config())
+            .with_full_trip_states()
+            .expect("Unable to rebuild trip states");
+        let (tampered_index, tampered_state) = tamper_with_trip_state(&mut recording);
+
+        let report = replay_recording(&recording).expect("Unable to replay recording");
+
+        assert_eq!(report.divergences.len(), 1);
+        let divergence = &report.divergences[0];
+        assert_eq!(divergence.event_index, tampered_index as u64);
+        assert_eq!(divergence.recorded, tampered_state);
+    }
+
+    #[test]
+    fn replay_divergence_keeps_route_deviation_state() {
+        // The user is off route for the whole trip,
+        // which is only reported after several consecutive locations.
+        let config = NavigationControllerConfig {
+            route_deviation_tracking: RouteDeviationTracking::StaticThresholdWithHysteresis {
+                minimum_horizontal_accuracy: 25,
+                max_acceptable_deviation: 10.0,
+                clear_deviation: 5.0,
+                minimum_off_route_fixes: 5,
+                minimum_off_route_distance: 1_000.0,
+            },
+            ..replay_config()
+        };
+        let mut recording = record_trip(LocationBias::Left(15.0), config)
+            .with_full_trip_states()
+            .expect("Unable to rebuild trip states");
+        let (tampered_index, tampered_state) = tamper_with_trip_state(&mut recording);
+        assert!(matches!(
+            tampered_state,
+            TripState::Navigating {
+                deviation: RouteDeviation::OffRoute { .. },
+                ..
+            }
+        ));
+
+        let report = replay_recording(&recording).expect("Unable to replay recording");
+
+        // Resuming with fresh deviation state would debounce the deviation all over again.
+        assert_eq!(report.divergences.len(), 1, "{:?}", report.divergences);
+        assert_eq!(report.divergences[0].event_index, tampered_index as u64);
+    }
+
+    /// Tampers with a navigating trip state somewhere in the middle of a recording,
+    /// returning its index and the tampered state.
+    fn tamper_with_trip_state(recording: &mut NavigationRecording) -> (usize, TripState) {
+        recording
+            .events
+            .iter_mut()
+            .enumerate()
+            .skip(20)
+            .find_map(|(index, event)| match &mut event.event_data {
+                NavigationRecordingEventData::TripStateUpdate {
+                    trip_state: trip_state @ TripState::Navigating { .. },
+                } => {
+                    if let TripState::Navigating { user_location, .. } = trip_state {
+                        user_location.horizontal_accuracy = 1234.0;
+                    }
+                    Some((index, trip_state.clone()))
+                }
+                _ => None,
+            })
+            .expect("Expected a navigating trip state")
+    }
+
+    #[test]
+    fn replay_rejects_invalid_json() {
+        assert!(matches!(
+            replay_recording_json("{\"version\": 1}"),
+            Err(NavigationRecordingError::DeserializationError { .. })
+        ));
+    }
+}
@@ -0,0 +1,336 @@
+//! Streaming destinations for navigation recordings.
+//!
+//! By default, a [`RecordingNavigationController`](super::RecordingNavigationController)
+//! accumulates events in memory.
+//! For long sessions, it is preferable to hand each event off to a [`NavigationRecordingSink`]
+//! as soon as it happens, so that memory usage stays flat
+//! and a partial recording survives if the app is terminated unexpectedly.
+
+use super::{NavigationRecording, NavigationRecordingError};
+use crate::navigation_controller::models::NavigationRecordingEvent;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// A destination which receives a navigation recording one event at a time.
+///
+/// Implementations may be in Rust (see [`NdjsonRecordingSink`]) or foreign code.
+/// Methods are called from whichever thread drives the navigator,
+/// so implementations must handle their own synchronization.
+#[cfg_attr(feature = "uniffi", uniffi::export(with_foreign))]
+pub trait NavigationRecordingSink: Send + Sync {
+    /// Begins a new recording.
+    ///
+    /// This is called exactly once, before any events.
+    /// The header is a JSON-encoded [`NavigationRecording`] with no events,
+    /// which includes the configuration and initial route of the session.
+    fn begin_recording(&self, header: String) -> Result<(), NavigationRecordingError>;
+
+    /// Appends an event to the recording.
+    ///
+    /// Events are delivered in order of their sequence numbers.
+    fn record_event(&self, event: NavigationRecordingEvent)
+        -> Result<(), NavigationRecordingError>;
+}
+
+/// A [`NavigationRecordingSink`] which writes newline-delimited JSON.
+///
+/// The first line is the recording header, and each subsequent line is an event.
+/// Every line is written out as soon as it is received,
+/// so the output is always a valid (if possibly incomplete) recording.
+/// Use [`NavigationRecording::from_ndjson`] to load it.
+///
+/// Foreign code should create one with `create_ndjson_recording_sink`,
+/// which returns it as a [`NavigationRecordingSink`].
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct NdjsonRecordingSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl NdjsonRecordingSink {
+    /// Creates a sink which writes to the file at `path`.
+    ///
+    /// Any existing file at the path is replaced.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new(path: &str) -> Result<Self, NavigationRecordingError> {
+        let file = File::create(path).map_err(|e| NavigationRecordingError::SinkError {
+            error: format!("Unable to create {path}: {e}"),
+        })?;
+
+        Ok(Self::from_writer(file))
+    }
+}
+
+impl NdjsonRecordingSink {
+    /// Creates a sink which writes to an arbitrary writer.
+    ///
+    /// NOTE: The writer should not buffer internally,
+    /// or events may be lost if the process is terminated.
+    pub fn from_writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    fn write_line(&self, json: String) -> Result<(), NavigationRecordingError> {
+        let mut line = json;
+        line.push('\n');
+
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|e| NavigationRecordingError::SinkError {
+                error: e.to_string(),
+            })?;
+        writer
+            .write_all(line.as_bytes())
+            .and_then(|()| writer.flush())
+            .map_err(|e| NavigationRecordingError::SinkError {
+                error: e.to_string(),
+            })
+    }
+}
+
+impl NavigationRecordingSink for NdjsonRecordingSink {
+    fn begin_recording(&self, header: String) -> Result<(), NavigationRecordingError> {
+        // Re-encode the header to guarantee that it fits on a single line.
+        let header: serde_json::Value = serde_json::from_str(&header)?;
+        self.write_line(serde_json::to_string(&header)?)
+    }
+
+    fn record_event(
+        &self,
+        event: NavigationRecordingEvent,
+    ) -> Result<(), NavigationRecordingError> {
+        self.write_line(serde_json::to_string(&event)?)
+    }
+}
+
+impl NavigationRecording {
+    /// Loads a recording written by an [`NdjsonRecordingSink`].
+    ///
+    /// An incomplete final line (e.g. if the app was terminated mid-write) is ignored,
+    /// so that everything recorded up to that point can still be recovered.
+    pub fn from_ndjson(ndjson: &str) -> Result<Self, NavigationRecordingError> {
+        let invalid = |e: serde_json::Error| NavigationRecordingError::DeserializationError {
+            error: e.to_string(),
+        };
+        let mut lines = ndjson.lines().filter(|line| !line.trim().is_empty());
+
+        let Some(header) = lines.next() else {
+            return Err(NavigationRecordingError::DeserializationError {
+                error: "The recording is empty".to_string(),
+            });
+        };
+        let mut recording: serde_json::Value = serde_json::from_str(header).map_err(invalid)?;
+
+        let mut events: Vec<serde_json::Value> = Vec::new();
+        let mut lines = lines.peekable();
+        while let Some(line) = lines.next() {
+            match serde_json::from_str(line) {
+                Ok(event) => events.push(event),
+                Err(e) if e.is_eof() && lines.peek().is_none() => break,
+                Err(e) => return Err(invalid(e)),
+            }
+        }
+        recording["events"] = serde_json::Value::Array(events);
+
+        let recording = Self::from_value(recording)?;
+        let initial_trip_state = recording
+            .initial_trip_state
+            .clone()
+            .or_else(|| recording.first_trip_state());
+
+        Ok(Self {
+            initial_trip_state,
+            ..recording
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::navigation_controller::models::NavigationRecordingEventData;
+    use crate::navigation_controller::recording::RecordingNavigationController;
+    use crate::navigation_controller::test_helpers::{
+        gen_dummy_route_step, gen_route_from_steps, get_test_route, test_config, TestRoute,
+    };
+    use crate::navigation_controller::Navigator;
+    use crate::simulation::{
+        advance_location_simulation, location_simulation_from_route, LocationBias,
+    };
+    use std::sync::Arc;
+
+    /// A writer which can be inspected after it has been handed to a sink.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).expect("Invalid UTF-8")
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn streams_events_to_sink() {
+        let route = get_test_route(TestRoute::Extended);
+        let mut simulation_state =
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let buffer = SharedBuffer::default();
+        let controller = RecordingNavigationController::with_sink(
+            route,
+            test_config(),
+            Arc::new(NdjsonRecordingSink::from_writer(buffer.clone())),
+        )
+        .expect("Unable to begin recording");
+
+        let mut state = controller.get_initial_state(simulation_state.current_location);
+        for _ in 0..20 {
+            simulation_state = advance_location_simulation(&simulation_state);
+            state = controller.update_user_location(simulation_state.current_location, state);
+        }
+
+        // Only the latest event is kept in memory.
+        let retained = state.recording_events().expect("Expected recorded events");
+        assert_eq!(retained.len(), 1);
+
+        let output = buffer.contents();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Invalid JSON line"))
+            .collect();
+        assert_eq!(lines[0]["events"], serde_json::json!([]));
+        for (sequence, event) in lines[1..].iter().enumerate() {
+            assert_eq!(event["sequence"], serde_json::json!(sequence));
+        }
+        assert_eq!(
+            lines.last().unwrap()["sequence"],
+            serde_json::json!(retained[0].sequence)
+        );
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| line["event_data"]["LocationUpdate"].is_object())
+                .count(),
+            21
+        );
+    }
+
+    #[test]
+    fn loads_partial_ndjson() {
+        let route = gen_route_from_steps(vec![gen_dummy_route_step(0.0, 0.0, 1.0, 1.0)]);
+        let buffer = SharedBuffer::default();
+        let sink = NdjsonRecordingSink::from_writer(buffer.clone());
+        let recording = NavigationRecording::new(test_config(), route.clone());
+        sink.begin_recording(recording.to_json().unwrap()).unwrap();
+        for (sequence, error_message) in ["first", "second"].into_iter().enumerate() {
+            sink.record_event(NavigationRecordingEvent::new(
+                sequence as u64,
+                NavigationRecordingEventData::Error {
+                    error_message: error_message.to_string(),
+                },
+            ))
+            .unwrap();
+        }
+
+        // Simulate the process being killed part way through writing an event.
+        let output = buffer.contents();
+        let truncated = &output[..output.len() - 10];
+        let loaded = NavigationRecording::from_ndjson(truncated).expect("Unable to load");
+
+        assert_eq!(loaded.initial_route.steps, route.steps);
+        assert_eq!(loaded.events.len(), 1);
+        assert!(matches!(
+            &loaded.events[0].event_data,
+            NavigationRecordingEventData::Error { error_message } if error_message == "first"
+        ));
+
+        // Both events are present if nothing was lost.
+        let loaded = NavigationRecording::from_ndjson(&output).expect("Unable to load");
+        assert_eq!(loaded.events.len(), 2);
+    }
+
+    #[test]
+    fn rejects_corrupt_ndjson() {
+        let route = gen_route_from_steps(vec![gen_dummy_route_step(0.0, 0.0, 1.0, 1.0)]);
+        let header = NavigationRecording::new(test_config(), route)
+            .to_json()
+            .unwrap();
+        let ndjson = format!("{header}\n{{\"not\": \"an event\"}}\n{{}}\n");
+
+        assert!(matches!(
+            NavigationRecording::from_ndjson(&ndjson),
+            Err(NavigationRecordingError::DeserializationError { .. })
+        ));
+        assert!(matches!(
+            NavigationRecording::from_ndjson(""),
+            Err(NavigationRecordingError::DeserializationError { .. })
+        ));
+    }
+
+    #[cfg(feature = "uniffi")]
+    #[test]
+    fn streams_to_ndjson_file_from_foreign_entry_point() {
+        let path = std::env::temp_dir().join(format!(
+            "ferrostar-recording-{}.ndjson",
+            uuid::Uuid::new_v4()
+        ));
+        let path_str = path.to_str().expect("Invalid temporary path");
+        let route = get_test_route(TestRoute::Extended);
+        let mut simulation_state =
+            location_simulation_from_route(&route, Some(10.0), LocationBias::None, None)
+                .expect("Unable to create simulation");
+
+        let sink = crate::create_ndjson_recording_sink(path_str).expect("Unable to create sink");
+        let navigator = crate::navigation_controller::create_recording_navigator(
+            route.clone(),
+            test_config(),
+            sink,
+        )
+        .expect("Unable to begin recording");
+
+        let mut state = navigator.get_initial_state(simulation_state.current_location);
+        for _ in 0..5 {
+            simulation_state = advance_location_simulation(&simulation_state);
+            state = navigator.update_user_location(simulation_state.current_location, state);
+        }
+
+        let output = std::fs::read_to_string(&path).expect("Unable to read recording");
+        std::fs::remove_file(&path).expect("Unable to remove recording");
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Invalid JSON line"))
+            .collect();
+
+        assert_eq!(lines[0]["events"], serde_json::json!([]));
+        assert_eq!(
+            lines[0]["initial_route"]["steps"],
+            serde_json::to_value(&route.steps).unwrap()
+        );
+        let retained = state.recording_events().expect("Expected recorded events");
+        assert_eq!(
+            lines.last().unwrap()["sequence"],
+            serde_json::json!(retained[0].sequence)
+        );
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| line["event_data"]["LocationUpdate"].is_object())
+                .count(),
+            6
+        );
+    }
+}
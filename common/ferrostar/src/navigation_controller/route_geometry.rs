@@ -0,0 +1,271 @@
+//! Route geometry which is precomputed once per route.
+//!
+//! Building [`LineString`]s and measuring them is relatively expensive,
+//! so the navigation controller does this up front rather than on every location update.
+
+use crate::algorithms::{cumulative_distances, distance_along};
+use crate::models::{Route, RouteStep};
+use geo::{BoundingRect, Distance, Haversine, LineString, Point, Rect};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// The geometry of a route step, along with some precomputed measurements.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StepGeometry {
+    linestring: LineString,
+    /// The distance (in meters) along the step to each coordinate in the linestring.
+    cumulative_distances: Vec<f64>,
+    bounding_rect: Option<Rect>,
+}
+
+impl StepGeometry {
+    pub(crate) fn new(step: &RouteStep) -> Self {
+        let linestring = step.get_linestring();
+        let cumulative_distances = cumulative_distances(&linestring);
+        let bounding_rect = linestring.bounding_rect();
+
+        Self {
+            linestring,
+            cumulative_distances,
+            bounding_rect,
+        }
+    }
+
+    pub(crate) fn linestring(&self) -> &LineString {
+        &self.linestring
+    }
+
+    /// The length of the step geometry, in meters.
+    pub(crate) fn length(&self) -> f64 {
+        self.cumulative_distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// An approximate lower bound on the distance (in meters) from a point to the step geometry,
+    /// based on its bounding rectangle.
+    ///
+    /// This is much cheaper than measuring the distance to the geometry itself,
+    /// so it is useful for ruling out steps which are far away.
+    pub(crate) fn minimum_distance_to(&self, point: &Point) -> Option<f64> {
+        let rect = self.bounding_rect?;
+        let (min, max) = (rect.min(), rect.max());
+        if ![min.x, min.y, max.x, max.y]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            return None;
+        }
+
+        let closest = Point::new(point.x().clamp(min.x, max.x), point.y().clamp(min.y, max.y));
+        Some(Haversine.distance(closest, *point))
+    }
+
+    /// The travel distance (in meters) from a point to the end of the step.
+    ///
+    /// The point should already be snapped to the step geometry.
+    /// The result may be [`None`] in case of invalid input such as infinite floats.
+    pub(crate) fn distance_to_end(&self, snapped_location: &Point) -> Option<f64> {
+        distance_along(
+            snapped_location,
+            &self.linestring,
+            &self.cumulative_distances,
+        )
+        .map(|traversed| self.length() - traversed)
+    }
+}
+
+/// A route along with the precomputed geometry of each of its steps.
+#[derive(Debug)]
+pub(crate) struct RouteGeometry {
+    route: Arc<Route>,
+    steps: Vec<StepGeometry>,
+    /// The distance (in meters) along the route to the start of each step,
+    /// followed by the total distance of the steps.
+    step_start_distances: Vec<f64>,
+}
+
+impl RouteGeometry {
+    pub(crate) fn new(route: Route) -> Self {
+        let steps = route.steps.iter().map(StepGeometry::new).collect();
+        let step_start_distances = std::iter::once(0.0)
+            .chain(route.steps.iter().scan(0.0, |distance, step| {
+                *distance += step.distance;
+                Some(*distance)
+            }))
+            .collect();
+        Self {
+            route: Arc::new(route),
+            steps,
+            step_start_distances,
+        }
+    }
+
+    pub(crate) fn route(&self) -> &Route {
+        &self.route
+    }
+
+    /// The distance (in meters) along the route to the start of the step at `index`,
+    /// as given by the distances of the preceding steps.
+    ///
+    /// Indices past the end of the route give the total distance of the steps.
+    pub(crate) fn step_start_distance(&self, index: usize) -> f64 {
+        let index = index.min(self.step_start_distances.len() - 1);
+        self.step_start_distances[index]
+    }
+
+    /// The precomputed geometry of the step at `index` in the route.
+    pub(crate) fn step(&self, index: usize) -> Option<&StepGeometry> {
+        self.steps.get(index)
+    }
+
+    /// The geometry of `step`, which is expected to be at `index` in the route.
+    ///
+    /// The precomputed geometry is used when it belongs to the same step
+    /// (as determined by a cheap check of the coordinates).
+    /// Otherwise, the geometry is computed on the fly.
+    pub(crate) fn step_geometry<'a>(
+        &'a self,
+        index: usize,
+        step: &RouteStep,
+    ) -> Cow<'a, StepGeometry> {
+        match (self.route.steps.get(index), self.steps.get(index)) {
+            (Some(route_step), Some(geometry))
+                if route_step.geometry.len() == step.geometry.len()
+                    && route_step.geometry.first() == step.geometry.first()
+                    && route_step.geometry.last() == step.geometry.last() =>
+            {
+                Cow::Borrowed(geometry)
+            }
+            _ => Cow::Owned(StepGeometry::new(step)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::calculate_trip_progress;
+    use crate::navigation_controller::test_helpers::{
+        gen_dummy_route_step, gen_route_from_steps, gen_route_step_with_coords,
+    };
+    use geo::coord;
+    use std::slice;
+
+    #[test]
+    fn precomputes_step_measurements() {
+        let step = gen_route_step_with_coords(vec![
+            coord! { x: 0.0, y: 0.0 },
+            coord! { x: 0.001, y: 0.0 },
+            coord! { x: 0.001, y: 0.002 },
+        ]);
+        let geometry = StepGeometry::new(&step);
+
+        let first_segment_length = Haversine.distance(Point::new(0.0, 0.0), Point::new(0.001, 0.0));
+        let second_segment_length =
+            Haversine.distance(Point::new(0.001, 0.0), Point::new(0.001, 0.002));
+        assert_eq!(
+            geometry.cumulative_distances,
+            vec![
+                0.0,
+                first_segment_length,
+                first_segment_length + second_segment_length
+            ]
+        );
+        assert_eq!(
+            geometry.length(),
+            first_segment_length + second_segment_length
+        );
+        assert_eq!(
+            geometry.bounding_rect,
+            Some(Rect::new(
+                coord! { x: 0.0, y: 0.0 },
+                coord! { x: 0.001, y: 0.002 }
+            ))
+        );
+        assert_eq!(geometry.linestring(), &step.get_linestring());
+    }
+
+    #[test]
+    fn minimum_distance_is_measured_to_the_bounding_rect() {
+        let geometry = StepGeometry::new(&gen_dummy_route_step(0.0, 0.0, 0.001, 0.001));
+
+        assert_eq!(
+            geometry.minimum_distance_to(&Point::new(0.0005, 0.0005)),
+            Some(0.0)
+        );
+        assert_eq!(
+            geometry.minimum_distance_to(&Point::new(0.0005, 0.002)),
+            Some(Haversine.distance(Point::new(0.0005, 0.001), Point::new(0.0005, 0.002)))
+        );
+        assert_eq!(
+            StepGeometry::new(&gen_route_step_with_coords(vec![
+                coord! { x: f64::NAN, y: 0.0 },
+                coord! { x: 0.0, y: 0.0 },
+            ]))
+            .minimum_distance_to(&Point::new(0.0, 0.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn distance_to_end_matches_trip_progress() {
+        let step = gen_route_step_with_coords(vec![
+            coord! { x: 0.0, y: 0.0 },
+            coord! { x: 0.001, y: 0.0 },
+            coord! { x: 0.001, y: 0.002 },
+        ]);
+        let geometry = StepGeometry::new(&step);
+
+        for point in [
+            Point::new(0.0, 0.0),
+            Point::new(0.0005, 0.0),
+            Point::new(0.001, 0.001),
+            Point::new(0.001, 0.002),
+        ] {
+            let progress =
+                calculate_trip_progress(&point, &step.get_linestring(), slice::from_ref(&step));
+            assert_eq!(
+                geometry.distance_to_end(&point),
+                Some(progress.distance_to_next_maneuver)
+            );
+        }
+    }
+
+    #[test]
+    fn precomputes_step_start_distances() {
+        let route = gen_route_from_steps(vec![
+            gen_dummy_route_step(0.0, 0.0, 0.001, 0.0),
+            gen_dummy_route_step(0.001, 0.0, 0.002, 0.0),
+        ]);
+        let geometry = RouteGeometry::new(route.clone());
+        let first_step_distance = route.steps[0].distance;
+        let total_distance = first_step_distance + route.steps[1].distance;
+
+        assert_eq!(geometry.step_start_distance(0), 0.0);
+        assert_eq!(geometry.step_start_distance(1), first_step_distance);
+        assert_eq!(geometry.step_start_distance(2), total_distance);
+        assert_eq!(geometry.step_start_distance(5), total_distance);
+    }
+
+    #[test]
+    fn only_uses_precomputed_geometry_for_the_same_step() {
+        let route = gen_route_from_steps(vec![
+            gen_dummy_route_step(0.0, 0.0, 0.001, 0.0),
+            gen_dummy_route_step(0.001, 0.0, 0.002, 0.0),
+        ]);
+        let geometry = RouteGeometry::new(route.clone());
+
+        assert!(matches!(
+            geometry.step_geometry(1, &route.steps[1]),
+            Cow::Borrowed(_)
+        ));
+
+        let other_step = gen_dummy_route_step(0.001, 0.0, 0.001, 0.001);
+        let other_geometry = geometry.step_geometry(1, &other_step);
+        assert!(matches!(other_geometry, Cow::Owned(_)));
+        assert_eq!(other_geometry.linestring(), &other_step.get_linestring());
+        assert!(matches!(
+            geometry.step_geometry(2, &other_step),
+            Cow::Owned(_)
+        ));
+    }
+}
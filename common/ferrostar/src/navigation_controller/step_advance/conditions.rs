@@ -1,11 +1,14 @@
 use std::sync::Arc;
 
-use super::{StepAdvanceCondition, StepAdvanceConditionSerializable, StepAdvanceResult};
+use super::{
+    should_advance_step_building_geometry, should_advance_step_with_geometry, StepAdvanceCondition,
+    StepAdvanceConditionSerializable, StepAdvanceConditionWithGeometry, StepAdvanceResult,
+};
 use crate::{
     algorithms::{deviation_from_line, is_within_threshold_to_end_of_linestring},
     models::{RouteStep, UserLocation},
 };
-use geo::Point;
+use geo::{LineString, Point};
 
 #[cfg(test)]
 use proptest::prelude::*;
@@ -29,12 +32,24 @@ use tsify::Tsify;
 pub struct ManualStepCondition;
 
 impl StepAdvanceCondition for ManualStepCondition {
-    #[allow(unused_variables)]
     fn should_advance_step(
         &self,
         user_location: UserLocation,
         current_step: RouteStep,
         next_step: Option<RouteStep>,
+    ) -> StepAdvanceResult {
+        should_advance_step_building_geometry(self, user_location, current_step, next_step)
+    }
+}
+
+impl StepAdvanceConditionWithGeometry for ManualStepCondition {
+    #[allow(unused_variables)]
+    fn should_advance_step_with_geometry(
+        &self,
+        user_location: UserLocation,
+        current_step: RouteStep,
+        current_step_linestring: &LineString,
+        next_step: Option<RouteStep>,
     ) -> StepAdvanceResult {
         StepAdvanceResult {
             should_advance: false,
@@ -47,6 +62,10 @@ impl StepAdvanceConditionSerializable for ManualStepCondition {
     fn to_js(&self) -> SerializableStepAdvanceCondition {
         SerializableStepAdvanceCondition::Manual
     }
+
+    fn as_geometry_condition(&self) -> Option<&dyn StepAdvanceConditionWithGeometry> {
+        Some(self)
+    }
 }
 
 // MARK: Basic Conditions
@@ -66,12 +85,24 @@ pub struct DistanceToEndOfStepCondition {
 }
 
 impl StepAdvanceCondition for DistanceToEndOfStepCondition {
-    #[allow(unused_variables)]
     fn should_advance_step(
         &self,
         user_location: UserLocation,
         current_step: RouteStep,
         next_step: Option<RouteStep>,
+    ) -> StepAdvanceResult {
+        should_advance_step_building_geometry(self, user_location, current_step, next_step)
+    }
+}
+
+impl StepAdvanceConditionWithGeometry for DistanceToEndOfStepCondition {
+    #[allow(unused_variables)]
+    fn should_advance_step_with_geometry(
+        &self,
+        user_location: UserLocation,
+        current_step: RouteStep,
+        current_step_linestring: &LineString,
+        next_step: Option<RouteStep>,
     ) -> StepAdvanceResult {
         let should_advance =
             if user_location.horizontal_accuracy > self.minimum_horizontal_accuracy.into() {
@@ -79,7 +110,7 @@ impl StepAdvanceCondition for DistanceToEndOfStepCondition {
             } else {
                 is_within_threshold_to_end_of_linestring(
                     &user_location.into(),
-                    &current_step.get_linestring(),
+                    current_step_linestring,
                     f64::from(self.distance),
                 )
             };
@@ -101,6 +132,10 @@ impl StepAdvanceConditionSerializable for DistanceToEndOfStepCondition {
             minimum_horizontal_accuracy: self.minimum_horizontal_accuracy,
         }
     }
+
+    fn as_geometry_condition(&self) -> Option<&dyn StepAdvanceConditionWithGeometry> {
+        Some(self)
+    }
 }
 
 /// Requires that the user be at least this far from the current route step.
@@ -124,12 +159,24 @@ pub struct DistanceFromStepCondition {
 }
 
 impl StepAdvanceCondition for DistanceFromStepCondition {
-    #[allow(unused_variables)]
     fn should_advance_step(
         &self,
         user_location: UserLocation,
         current_step: RouteStep,
         next_step: Option<RouteStep>,
+    ) -> StepAdvanceResult {
+        should_advance_step_building_geometry(self, user_location, current_step, next_step)
+    }
+}
+
+impl StepAdvanceConditionWithGeometry for DistanceFromStepCondition {
+    #[allow(unused_variables)]
+    fn should_advance_step_with_geometry(
+        &self,
+        user_location: UserLocation,
+        current_step: RouteStep,
+        current_step_linestring: &LineString,
+        next_step: Option<RouteStep>,
     ) -> StepAdvanceResult {
         // Exit early if the user location is not accurate enough.
         let should_advance =
@@ -137,9 +184,7 @@ impl StepAdvanceCondition for DistanceFromStepCondition {
                 false
             } else {
                 let current_position: Point = user_location.into();
-                let current_step_linestring = current_step.get_linestring();
-
-                deviation_from_line(&current_position, &current_step_linestring)
+                deviation_from_line(&current_position, current_step_linestring)
                     .map(|deviation| deviation > self.distance.into())
                     .unwrap_or(false)
             };
@@ -161,6 +206,10 @@ impl StepAdvanceConditionSerializable for DistanceFromStepCondition {
             minimum_horizontal_accuracy: self.minimum_horizontal_accuracy,
         }
     }
+
+    fn as_geometry_condition(&self) -> Option<&dyn StepAdvanceConditionWithGeometry> {
+        Some(self)
+    }
 }
 
 /// Advance if any of the conditions are met (OR).
@@ -182,10 +231,28 @@ impl StepAdvanceCondition for OrAdvanceConditions {
         user_location: UserLocation,
         current_step: RouteStep,
         next_step: Option<RouteStep>,
+    ) -> StepAdvanceResult {
+        should_advance_step_building_geometry(self, user_location, current_step, next_step)
+    }
+}
+
+impl StepAdvanceConditionWithGeometry for OrAdvanceConditions {
+    fn should_advance_step_with_geometry(
+        &self,
+        user_location: UserLocation,
+        current_step: RouteStep,
+        current_step_linestring: &LineString,
+        next_step: Option<RouteStep>,
     ) -> StepAdvanceResult {
         let should_advance = self.conditions.iter().any(|c| {
-            c.should_advance_step(user_location, current_step.clone(), next_step.clone())
-                .should_advance
+            should_advance_step_with_geometry(
+                c.as_ref(),
+                user_location,
+                current_step.clone(),
+                current_step_linestring,
+                next_step.clone(),
+            )
+            .should_advance
         });
 
         StepAdvanceResult {
@@ -203,6 +270,10 @@ impl StepAdvanceConditionSerializable for OrAdvanceConditions {
             conditions: self.conditions.iter().map(|c| c.to_js()).collect(),
         }
     }
+
+    fn as_geometry_condition(&self) -> Option<&dyn StepAdvanceConditionWithGeometry> {
+        Some(self)
+    }
 }
 
 /// Advance if all of the conditions are met (AND).
@@ -218,10 +289,28 @@ impl StepAdvanceCondition for AndAdvanceConditions {
         user_location: UserLocation,
         current_step: RouteStep,
         next_step: Option<RouteStep>,
+    ) -> StepAdvanceResult {
+        should_advance_step_building_geometry(self, user_location, current_step, next_step)
+    }
+}
+
+impl StepAdvanceConditionWithGeometry for AndAdvanceConditions {
+    fn should_advance_step_with_geometry(
+        &self,
+        user_location: UserLocation,
+        current_step: RouteStep,
+        current_step_linestring: &LineString,
+        next_step: Option<RouteStep>,
     ) -> StepAdvanceResult {
         let should_advance = self.conditions.iter().all(|c| {
-            c.should_advance_step(user_location, current_step.clone(), next_step.clone())
-                .should_advance
+            should_advance_step_with_geometry(
+                c.as_ref(),
+                user_location,
+                current_step.clone(),
+                current_step_linestring,
+                next_step.clone(),
+            )
+            .should_advance
         });
 
         StepAdvanceResult {
@@ -239,6 +328,10 @@ impl StepAdvanceConditionSerializable for AndAdvanceConditions {
             conditions: self.conditions.iter().map(|c| c.to_js()).collect(),
         }
     }
+
+    fn as_geometry_condition(&self) -> Option<&dyn StepAdvanceConditionWithGeometry> {
+        Some(self)
+    }
 }
 
 /// A stateful condition that requires the user to reach the end of the step then proceed past it to advance.
@@ -286,12 +379,24 @@ impl DistanceEntryAndExitCondition {
 }
 
 impl StepAdvanceCondition for DistanceEntryAndExitCondition {
-    #[allow(unused_variables)]
     fn should_advance_step(
         &self,
         user_location: UserLocation,
         current_step: RouteStep,
         next_step: Option<RouteStep>,
+    ) -> StepAdvanceResult {
+        should_advance_step_building_geometry(self, user_location, current_step, next_step)
+    }
+}
+
+impl StepAdvanceConditionWithGeometry for DistanceEntryAndExitCondition {
+    #[allow(unused_variables)]
+    fn should_advance_step_with_geometry(
+        &self,
+        user_location: UserLocation,
+        current_step: RouteStep,
+        current_step_linestring: &LineString,
+        next_step: Option<RouteStep>,
     ) -> StepAdvanceResult {
         if self.has_reached_end_of_current_step {
             let distance_from_end = DistanceFromStepCondition {
@@ -300,7 +405,12 @@ impl StepAdvanceCondition for DistanceEntryAndExitCondition {
             };
 
             let should_advance = distance_from_end
-                .should_advance_step(user_location, current_step, next_step)
+                .should_advance_step_with_geometry(
+                    user_location,
+                    current_step,
+                    current_step_linestring,
+                    next_step,
+                )
                 .should_advance;
 
             if should_advance {
@@ -338,7 +448,12 @@ impl StepAdvanceCondition for DistanceEntryAndExitCondition {
                 distance_to_end_of_step: self.distance_to_end_of_step,
                 distance_after_end_of_step: self.distance_after_end_of_step,
                 has_reached_end_of_current_step: distance_to_end
-                    .should_advance_step(user_location, current_step, next_step)
+                    .should_advance_step_with_geometry(
+                        user_location,
+                        current_step,
+                        current_step_linestring,
+                        next_step,
+                    )
                     .should_advance,
             };
 
@@ -359,6 +474,10 @@ impl StepAdvanceConditionSerializable for DistanceEntryAndExitCondition {
             has_reached_end_of_current_step: self.has_reached_end_of_current_step,
         }
     }
+
+    fn as_geometry_condition(&self) -> Option<&dyn StepAdvanceConditionWithGeometry> {
+        Some(self)
+    }
 }
 
 #[cfg(test)]
@@ -672,6 +791,57 @@ mod tests {
             "Should advance when user has first reached end of step and then moved away"
         );
     }
+
+    /// A custom condition which isn't aware of precomputed geometry.
+    struct AlwaysAdvanceCondition;
+
+    impl StepAdvanceCondition for AlwaysAdvanceCondition {
+        fn should_advance_step(
+            &self,
+            _user_location: UserLocation,
+            _current_step: RouteStep,
+            _next_step: Option<RouteStep>,
+        ) -> StepAdvanceResult {
+            StepAdvanceResult {
+                should_advance: true,
+                next_iteration: Arc::new(AlwaysAdvanceCondition),
+            }
+        }
+    }
+
+    impl StepAdvanceConditionSerializable for AlwaysAdvanceCondition {
+        fn to_js(&self) -> SerializableStepAdvanceCondition {
+            SerializableStepAdvanceCondition::Manual
+        }
+    }
+
+    #[test]
+    fn test_custom_condition_with_geometry() {
+        let condition = AndAdvanceConditions {
+            conditions: vec![
+                Arc::new(AlwaysAdvanceCondition),
+                Arc::new(DistanceToEndOfStepCondition {
+                    minimum_horizontal_accuracy: 10,
+                    distance: 20,
+                }),
+            ],
+        };
+
+        for (location, expected) in [
+            (*LOCATION_NEAR_START_OF_STEP, false),
+            (*LOCATION_NEAR_END_OF_STEP, true),
+        ] {
+            let result = should_advance_step_with_geometry(
+                &condition,
+                location,
+                STRAIGHT_LINE_SHORT_ROUTE_STEP.clone(),
+                &STRAIGHT_LINE_SHORT_ROUTE_STEP.get_linestring(),
+                None,
+            );
+            assert_eq!(result.should_advance, expected);
+        }
+        assert!(AlwaysAdvanceCondition.as_geometry_condition().is_none());
+    }
 }
 
 #[cfg(test)]
@@ -6,6 +6,7 @@ use crate::{
         DistanceToEndOfStepCondition, ManualStepCondition, OrAdvanceConditions,
     },
 };
+use geo::LineString;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -38,6 +39,32 @@ pub struct StepAdvanceResult {
 /// A trait for converting a step advance condition into a JavaScript object for Web/WASM.
 pub trait StepAdvanceConditionSerializable {
     fn to_js(&self) -> SerializableStepAdvanceCondition;
+
+    /// Returns this condition if it can be checked against precomputed step geometry
+    /// (see [`StepAdvanceConditionWithGeometry`]).
+    ///
+    /// Conditions which don't override this are checked with [`StepAdvanceCondition::should_advance_step`].
+    fn as_geometry_condition(&self) -> Option<&dyn StepAdvanceConditionWithGeometry> {
+        None
+    }
+}
+
+/// A trait for checking a step advance condition against the precomputed geometry of the current step.
+///
+/// The navigation controller builds the geometry of each step once per route,
+/// so conditions which implement this (and return themselves from
+/// [`StepAdvanceConditionSerializable::as_geometry_condition`])
+/// don't need to build the linestring of the step on every location update.
+pub trait StepAdvanceConditionWithGeometry {
+    /// Same as [`StepAdvanceCondition::should_advance_step`],
+    /// given the linestring of `current_step`.
+    fn should_advance_step_with_geometry(
+        &self,
+        user_location: UserLocation,
+        current_step: RouteStep,
+        current_step_linestring: &LineString,
+        next_step: Option<RouteStep>,
+    ) -> StepAdvanceResult;
 }
 
 /// When implementing custom step advance logic, this trait allows you to define
@@ -60,6 +87,43 @@ pub trait StepAdvanceCondition: StepAdvanceConditionSerializable + Sync + Send {
     ) -> StepAdvanceResult;
 }
 
+/// Checks `condition` given the linestring of `current_step`,
+/// using the precomputed geometry if the condition supports it.
+pub(crate) fn should_advance_step_with_geometry(
+    condition: &dyn StepAdvanceCondition,
+    user_location: UserLocation,
+    current_step: RouteStep,
+    current_step_linestring: &LineString,
+    next_step: Option<RouteStep>,
+) -> StepAdvanceResult {
+    match condition.as_geometry_condition() {
+        Some(condition) => condition.should_advance_step_with_geometry(
+            user_location,
+            current_step,
+            current_step_linestring,
+            next_step,
+        ),
+        None => condition.should_advance_step(user_location, current_step, next_step),
+    }
+}
+
+/// Checks a geometry-aware `condition` without precomputed geometry,
+/// building the linestring of `current_step`.
+fn should_advance_step_building_geometry(
+    condition: &impl StepAdvanceConditionWithGeometry,
+    user_location: UserLocation,
+    current_step: RouteStep,
+    next_step: Option<RouteStep>,
+) -> StepAdvanceResult {
+    let current_step_linestring = current_step.get_linestring();
+    condition.should_advance_step_with_geometry(
+        user_location,
+        current_step,
+        &current_step_linestring,
+        next_step,
+    )
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
 #[cfg_attr(feature = "wasm-bindgen", tsify(from_wasm_abi))]
@@ -106,7 +170,7 @@ impl From<SerializableStepAdvanceCondition> for Arc<dyn StepAdvanceCondition> {
             SerializableStepAdvanceCondition::DistanceFromStep {
                 distance,
                 minimum_horizontal_accuracy,
-            } => Arc::new(DistanceToEndOfStepCondition {
+            } => Arc::new(DistanceFromStepCondition {
                 distance,
                 minimum_horizontal_accuracy,
             }),
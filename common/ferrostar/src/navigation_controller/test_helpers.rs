@@ -1,3 +1,6 @@
+use super::models::{CourseFiltering, NavigationControllerConfig, WaypointAdvanceMode};
+use super::step_advance::conditions::DistanceToEndOfStepCondition;
+use crate::deviation_detection::RouteDeviationTracking;
 use crate::models::{BoundingBox, GeographicCoordinate, Route, RouteStep, Waypoint, WaypointKind};
 use crate::routing_adapters::{osrm::OsrmResponseParser, RouteResponseParser};
 #[cfg(feature = "alloc")]
@@ -5,6 +8,7 @@ use alloc::string::ToString;
 use chrono::{DateTime, Utc};
 use geo::{point, BoundingRect, Coord, Distance, Haversine, LineString, Point};
 use insta::{dynamic_redaction, Settings};
+use std::sync::Arc;
 
 pub enum TestRoute {
     /// Gets a longer + more complex route.
@@ -113,6 +117,24 @@ pub fn gen_route_step_with_coords(coordinates: Vec<Coord>) -> RouteStep {
     }
 }
 
+/// A simple navigation controller configuration,
+/// which advances steps near their end and never reports deviations.
+pub fn test_config() -> NavigationControllerConfig {
+    NavigationControllerConfig {
+        waypoint_advance: WaypointAdvanceMode::WaypointWithinRange(100.0),
+        route_deviation_tracking: RouteDeviationTracking::None,
+        snapped_location_course_filtering: CourseFiltering::Raw,
+        step_advance_condition: Arc::new(DistanceToEndOfStepCondition {
+            distance: 5,
+            minimum_horizontal_accuracy: 0,
+        }),
+        arrival_step_advance_condition: Arc::new(DistanceToEndOfStepCondition {
+            distance: 5,
+            minimum_horizontal_accuracy: 0,
+        }),
+    }
+}
+
 pub fn gen_route_from_steps(steps: Vec<RouteStep>) -> Route {
     let geometry: Vec<_> = steps
         .iter()
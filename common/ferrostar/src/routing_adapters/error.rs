@@ -13,6 +13,13 @@ pub enum InstantiationError {
         error("Error parsing the JSON options for the request.")
     )]
     OptionsJsonParseError,
+    #[cfg_attr(feature = "std", error("Invalid request options: {error}."))]
+    InvalidOptions { error: String },
+    #[cfg_attr(
+        feature = "std",
+        error("The response format requires the `{feature}` feature, which is not enabled.")
+    )]
+    UnsupportedResponseFormat { feature: String },
 }
 
 // TODO: See comment above
@@ -0,0 +1,587 @@
+//! HTTP request generation and response parsing for `GraphHopper` routing APIs.
+
+pub(crate) mod models;
+
+use super::{RouteRequest, RouteRequestGenerator, RouteResponseParser};
+use crate::models::{
+    BoundingBox, GeographicCoordinate, RouteStep, UserLocation, VisualInstruction,
+    VisualInstructionContent, Waypoint, WaypointKind,
+};
+use crate::routing_adapters::error::InstantiationError;
+use crate::routing_adapters::utilities::get_coordinates_from_geometry;
+use crate::routing_adapters::{ParsingError, Route, RoutingRequestGenerationError};
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::collections::BTreeMap as HashMap;
+#[cfg(feature = "alloc")]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use geo::{BoundingRect, LineString, Point};
+use models::{Instruction, Path, Points, RouteResponse};
+use serde_json::{json, Map, Value as JsonValue};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// A route request generator for `GraphHopper` backends operating over HTTP.
+///
+/// This generates `POST` requests for the `GraphHopper`
+/// [routing API](https://docs.graphhopper.com/#operation/postRoute)
+/// with turn-by-turn instructions,
+/// which can be parsed with a [`GraphHopperResponseParser`].
+///
+/// The user's course over ground (if any) is used as the heading of the start of the route.
+/// `GraphHopper` has no concept of via waypoints,
+/// so all waypoints are treated as [`WaypointKind::Break`]s.
+/// Elevation is never requested, even if it is set in the options.
+///
+/// ```
+/// use ferrostar::routing_adapters::graphhopper::GraphHopperHttpRequestGenerator;
+/// let request_generator = GraphHopperHttpRequestGenerator::with_options_json(
+///     "https://graphhopper.com/api/1/route?key=YOUR-API-KEY".to_string(),
+///     "bike".to_string(),
+///     Some(r#"{"locale": "de"}"#),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct GraphHopperHttpRequestGenerator {
+    /// The full URL of the `GraphHopper` route endpoint.
+    ///
+    /// Users *may* include a query string with an API key.
+    endpoint_url: String,
+    /// The `GraphHopper` profile to use (ex: `car`).
+    profile: String,
+    /// Arbitrary key/value pairs which override the defaults (ex: `locale` or `details`).
+    options: Map<String, JsonValue>,
+}
+
+impl GraphHopperHttpRequestGenerator {
+    /// Creates a new `GraphHopper` request generator given an endpoint URL, a profile name,
+    /// and options to include in the request JSON.
+    pub fn new(endpoint_url: String, profile: String, options: Map<String, JsonValue>) -> Self {
+        Self {
+            endpoint_url,
+            profile,
+            options,
+        }
+    }
+
+    /// Creates a new `GraphHopper` request generator given an endpoint URL, a profile name,
+    /// and options to include in the request JSON.
+    /// Options in this constructor are a JSON fragment representing any
+    /// options you want to add along with the request.
+    pub fn with_options_json(
+        endpoint_url: String,
+        profile: String,
+        options_json: Option<&str>,
+    ) -> Result<Self, InstantiationError> {
+        let parsed_options = match options_json {
+            Some(options) => serde_json::from_str::<JsonValue>(options)?
+                .as_object()
+                .ok_or(InstantiationError::OptionsJsonParseError)?
+                .to_owned(),
+            None => Map::new(),
+        };
+        Ok(Self::new(endpoint_url, profile, parsed_options))
+    }
+}
+
+impl RouteRequestGenerator for GraphHopperHttpRequestGenerator {
+    fn generate_request(
+        &self,
+        user_location: UserLocation,
+        waypoints: Vec<Waypoint>,
+    ) -> Result<RouteRequest, RoutingRequestGenerationError> {
+        if waypoints.is_empty() {
+            return Err(RoutingRequestGenerationError::NotEnoughWaypoints);
+        }
+
+        let headers = HashMap::from([("Content-Type".to_string(), "application/json".to_string())]);
+        let points: Vec<JsonValue> = core::iter::once(user_location.coordinates)
+            .chain(waypoints.iter().map(|waypoint| waypoint.coordinate))
+            .map(|coordinate| json!([coordinate.lng, coordinate.lat]))
+            .collect();
+
+        let mut args = json!({
+            "profile": &self.profile,
+            "points": points,
+            "instructions": true,
+            "calc_points": true,
+            "points_encoded": true,
+            "details": ["max_speed", "road_class", "street_name"],
+        });
+        if let Some(course) = user_location.course_over_ground {
+            // A single heading applies to the start of the route.
+            args["headings"] = json!([course.degrees]);
+            // Headings are not supported by GraphHopper's "speed mode" (contraction hierarchies).
+            args["ch.disable"] = true.into();
+        }
+
+        for (k, v) in &self.options {
+            args[k] = v.clone();
+        }
+        // Encoded points include a third dimension with elevation, which the parser doesn't decode.
+        args["elevation"] = false.into();
+
+        let body = serde_json::to_vec(&args)?;
+        Ok(RouteRequest::HttpPost {
+            url: self.endpoint_url.clone(),
+            headers,
+            body,
+        })
+    }
+}
+
+/// A response parser for `GraphHopper` routing backends.
+///
+/// `GraphHopper` [`details`](https://docs.graphhopper.com/#section/Map-Data-and-Routing-Profiles/OpenStreetMap)
+/// (ex: `max_speed` and `road_class`) are included as the annotations of each step.
+/// The `max_speed` detail is additionally converted to an OSRM-style `maxspeed` annotation,
+/// so that it can be used by the standard annotation publishers on each platform.
+///
+/// `GraphHopper` does not provide banner or voice instructions,
+/// so a visual instruction is synthesized from the text and sign of the upcoming instruction,
+/// and there are no spoken instructions.
+#[derive(Debug, Default)]
+pub struct GraphHopperResponseParser;
+
+impl GraphHopperResponseParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl RouteResponseParser for GraphHopperResponseParser {
+    fn parse_response(&self, response: Vec<u8>) -> Result<Vec<Route>, ParsingError> {
+        let res: RouteResponse = serde_json::from_slice(&response)?;
+
+        if let Some(message) = res.message.as_ref().filter(|_| res.paths.is_empty()) {
+            return Err(ParsingError::InvalidStatusCode {
+                code: res.error_details().unwrap_or("Error").to_string(),
+                description: Some(message.clone()),
+            });
+        }
+
+        res.paths.iter().map(Route::from_graphhopper).collect()
+    }
+}
+
+impl Route {
+    /// Create a route from a `GraphHopper` path.
+    fn from_graphhopper(path: &Path) -> Result<Self, ParsingError> {
+        let geometry = decode_points(&path.points, path.points_encoded_multiplier)?;
+        let Some(bbox) =
+            LineString::from_iter(geometry.iter().map(|coord| Point::from(*coord))).bounding_rect()
+        else {
+            return Err(ParsingError::InvalidGeometry {
+                error: "Bounding box could not be calculated".to_string(),
+            });
+        };
+
+        let waypoints = match &path.snapped_waypoints {
+            Some(points) => decode_points(points, path.points_encoded_multiplier)?,
+            None => geometry
+                .first()
+                .into_iter()
+                .chain(geometry.last())
+                .copied()
+                .collect(),
+        }
+        .into_iter()
+        .map(|coordinate| Waypoint {
+            coordinate,
+            kind: WaypointKind::Break,
+        })
+        .collect();
+
+        let steps = path
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| {
+                // The banner describes the upcoming maneuver, at the end of the step.
+                let upcoming_instruction = path.instructions.get(index + 1).unwrap_or(instruction);
+                RouteStep::from_graphhopper(path, &geometry, instruction, upcoming_instruction)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Route {
+            geometry,
+            bbox: BoundingBox::from(bbox),
+            distance: path.distance,
+            waypoints,
+            steps,
+        })
+    }
+}
+
+impl RouteStep {
+    fn from_graphhopper(
+        path: &Path,
+        route_geometry: &[GeographicCoordinate],
+        instruction: &Instruction,
+        upcoming_instruction: &Instruction,
+    ) -> Result<Self, ParsingError> {
+        let (from, to) = instruction.interval;
+        let mut geometry = route_geometry
+            .get(from..=to)
+            .ok_or_else(|| ParsingError::InvalidGeometry {
+                error: format!("Instruction interval [{from}, {to}] is out of bounds"),
+            })?
+            .to_vec();
+        if let [coordinate] = geometry[..] {
+            // Like other routing engines, represent a point (ex: at the destination)
+            // as two identical coordinates.
+            geometry.push(coordinate);
+        }
+
+        let (maneuver_type, maneuver_modifier) = upcoming_instruction.maneuver();
+        let visual_instructions = vec![VisualInstruction {
+            primary_content: VisualInstructionContent {
+                text: upcoming_instruction.text.clone(),
+                maneuver_type,
+                maneuver_modifier,
+                roundabout_exit_degrees: upcoming_instruction.roundabout_exit_degrees(),
+                lane_info: None,
+                exit_numbers: exit_numbers(upcoming_instruction),
+            },
+            secondary_content: None,
+            sub_content: None,
+            trigger_distance_before_maneuver: instruction.distance,
+        }];
+
+        Ok(RouteStep {
+            geometry,
+            distance: instruction.distance,
+            duration: instruction.time / 1000.0,
+            road_name: instruction
+                .street_name
+                .clone()
+                .filter(|name| !name.is_empty()),
+            exits: exit_numbers(instruction),
+            instruction: instruction.text.clone(),
+            visual_instructions,
+            spoken_instructions: Vec::new(),
+            annotations: annotations(path, from, to),
+            incidents: Vec::new(),
+        })
+    }
+}
+
+fn exit_numbers(instruction: &Instruction) -> Vec<String> {
+    instruction
+        .exit_number
+        .map(|exit_number| exit_number.to_string())
+        .into_iter()
+        .collect()
+}
+
+/// Decodes `GraphHopper` points, which may be either an encoded polyline or `GeoJSON`.
+fn decode_points(
+    points: &Points,
+    encoded_multiplier: Option<f64>,
+) -> Result<Vec<GeographicCoordinate>, ParsingError> {
+    match points {
+        Points::Encoded(polyline) => {
+            let multiplier = encoded_multiplier.unwrap_or(1e5);
+            if !(multiplier.is_finite() && multiplier >= 1.0) {
+                return Err(ParsingError::InvalidGeometry {
+                    error: format!("Invalid polyline multiplier {multiplier}"),
+                });
+            }
+            get_coordinates_from_geometry(polyline, multiplier.log10().round() as u32)
+        }
+        Points::LineString { coordinates } => coordinates
+            .iter()
+            .map(|coordinate| match coordinate[..] {
+                [lng, lat, ..] => Ok(GeographicCoordinate { lat, lng }),
+                _ => Err(ParsingError::InvalidGeometry {
+                    error: format!("Invalid coordinate {coordinate:?}"),
+                }),
+            })
+            .collect(),
+    }
+}
+
+/// Converts the `GraphHopper` details covering the points `from..=to`
+/// into an annotation for each segment between them.
+///
+/// Returns [`None`] if there are no details, or no segments.
+fn annotations(path: &Path, from: usize, to: usize) -> Option<Vec<String>> {
+    if path.details.is_empty() || from >= to {
+        return None;
+    }
+
+    let annotations = (from..to)
+        .map(|segment_index| {
+            let mut annotation: Map<String, JsonValue> = path
+                .details
+                .iter()
+                .filter_map(|(key, ranges)| {
+                    ranges
+                        .iter()
+                        .find(|(start, end, _)| (*start..*end).contains(&segment_index))
+                        .map(|(_, _, value)| (key.clone(), value.clone()))
+                })
+                .collect();
+            if let Some(max_speed) = annotation.get("max_speed") {
+                let maxspeed = match max_speed.as_f64() {
+                    Some(speed) => json!({ "speed": speed, "unit": "km/h" }),
+                    None => json!({ "unknown": true }),
+                };
+                annotation.insert("maxspeed".to_string(), maxspeed);
+            }
+            JsonValue::Object(annotation).to_string()
+        })
+        .collect();
+    Some(annotations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CourseOverGround, ManeuverModifier, ManeuverType};
+    use assert_json_diff::assert_json_include;
+    use serde_json::from_slice;
+
+    #[cfg(all(feature = "std", not(feature = "web-time")))]
+    use std::time::SystemTime;
+
+    #[cfg(feature = "web-time")]
+    use web_time::SystemTime;
+
+    const GRAPHHOPPER_RESPONSE: &str = include_str!("fixtures/graphhopper_response.json");
+
+    const ENDPOINT_URL: &str = "https://graphhopper.com/api/1/route";
+    const PROFILE: &str = "car";
+    const USER_LOCATION: UserLocation = UserLocation {
+        coordinates: GeographicCoordinate { lat: 0.0, lng: 0.0 },
+        horizontal_accuracy: 6.0,
+        course_over_ground: None,
+        timestamp: SystemTime::UNIX_EPOCH,
+        speed: None,
+    };
+    const WAYPOINTS: [Waypoint; 2] = [
+        Waypoint {
+            coordinate: GeographicCoordinate { lat: 0.0, lng: 1.0 },
+            kind: WaypointKind::Break,
+        },
+        Waypoint {
+            coordinate: GeographicCoordinate { lat: 2.0, lng: 3.0 },
+            kind: WaypointKind::Via,
+        },
+    ];
+
+    fn generate_body(user_location: UserLocation, options_json: Option<&str>) -> JsonValue {
+        let generator = GraphHopperHttpRequestGenerator::with_options_json(
+            ENDPOINT_URL.to_string(),
+            PROFILE.to_string(),
+            options_json,
+        )
+        .expect("Unable to create request generator");
+
+        match generator.generate_request(user_location, WAYPOINTS.to_vec()) {
+            Ok(RouteRequest::HttpPost { url, headers, body }) => {
+                assert_eq!(ENDPOINT_URL, url);
+                assert_eq!(headers["Content-Type"], "application/json".to_string());
+                from_slice(&body).expect("Failed to parse request body as JSON")
+            }
+            Ok(RouteRequest::HttpGet { .. }) => {
+                unreachable!("The GraphHopper HTTP request generator only generates POST requests")
+            }
+            Err(e) => panic!("Failed to generate request: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn not_enough_locations() {
+        let generator = GraphHopperHttpRequestGenerator::new(
+            ENDPOINT_URL.to_string(),
+            PROFILE.to_string(),
+            Map::new(),
+        );
+
+        assert!(matches!(
+            generator.generate_request(USER_LOCATION, Vec::new()),
+            Err(RoutingRequestGenerationError::NotEnoughWaypoints)
+        ));
+    }
+
+    #[test]
+    fn request_body_without_course() {
+        let body_json = generate_body(USER_LOCATION, None);
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "profile": PROFILE,
+                "points": [[0.0, 0.0], [1.0, 0.0], [3.0, 2.0]],
+                "instructions": true,
+                "points_encoded": true,
+            })
+        );
+        assert!(body_json["headings"].is_null());
+        assert!(body_json["ch.disable"].is_null());
+    }
+
+    #[test]
+    fn request_body_with_course_and_options() {
+        let user_location = UserLocation {
+            course_over_ground: Some(CourseOverGround {
+                degrees: 42,
+                accuracy: Some(12),
+            }),
+            ..USER_LOCATION
+        };
+        let body_json = generate_body(
+            user_location,
+            Some(r#"{"locale": "de", "details": ["surface"]}"#),
+        );
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "profile": PROFILE,
+                "headings": [42],
+                "ch.disable": true,
+                "locale": "de",
+                "details": ["surface"],
+            })
+        );
+    }
+
+    #[test]
+    fn request_body_never_includes_elevation() {
+        let body_json = generate_body(USER_LOCATION, Some(r#"{"elevation": true}"#));
+
+        assert_eq!(body_json["elevation"], json!(false));
+    }
+
+    #[test]
+    fn parse_graphhopper() {
+        let parser = GraphHopperResponseParser::new();
+        let routes = parser
+            .parse_response(GRAPHHOPPER_RESPONSE.into())
+            .expect("Unable to parse GraphHopper response");
+        insta::assert_yaml_snapshot!(routes);
+    }
+
+    #[test]
+    fn parse_graphhopper_steps() {
+        let parser = GraphHopperResponseParser::new();
+        let routes = parser
+            .parse_response(GRAPHHOPPER_RESPONSE.into())
+            .expect("Unable to parse GraphHopper response");
+        let route = &routes[0];
+
+        // The steps cover the whole route, sharing their start and end coordinates.
+        assert_eq!(route.steps.len(), 5);
+        for (step, next_step) in route.steps.iter().zip(route.steps.iter().skip(1)) {
+            assert_eq!(step.geometry.last(), next_step.geometry.first());
+        }
+        let arrival = route.steps.last().expect("Expected an arrival step");
+        assert_eq!(arrival.geometry.len(), 2);
+        assert_eq!(arrival.geometry.first(), route.geometry.last());
+
+        // Each banner describes the upcoming maneuver.
+        let roundabout_banner = &route.steps[1].visual_instructions[0].primary_content;
+        assert_eq!(
+            roundabout_banner.maneuver_type,
+            Some(ManeuverType::Roundabout)
+        );
+        assert_eq!(roundabout_banner.roundabout_exit_degrees, Some(173));
+        assert_eq!(roundabout_banner.exit_numbers, vec!["2".to_string()]);
+        let fork_banner = &route.steps[2].visual_instructions[0].primary_content;
+        assert_eq!(fork_banner.maneuver_type, Some(ManeuverType::Fork));
+        assert_eq!(
+            fork_banner.maneuver_modifier,
+            Some(ManeuverModifier::SlightLeft)
+        );
+
+        // There is one annotation per segment, including speed limits in the OSRM format.
+        let annotations = route.steps[0]
+            .annotations
+            .as_ref()
+            .expect("Expected annotations");
+        assert_eq!(annotations.len(), route.steps[0].geometry.len() - 1);
+        let annotation: JsonValue =
+            serde_json::from_str(&annotations[0]).expect("Invalid annotation");
+        assert_json_include!(
+            actual: annotation,
+            expected: json!({
+                "max_speed": 50.0,
+                "maxspeed": { "speed": 50.0, "unit": "km/h" },
+                "road_class": "primary",
+            })
+        );
+        assert_eq!(arrival.annotations, None);
+    }
+
+    #[test]
+    fn parse_graphhopper_error() {
+        let parser = GraphHopperResponseParser::new();
+        let result = parser.parse_response(
+            r#"{
+                "message": "Cannot find point 0: 52.0,13.0",
+                "hints": [{
+                    "message": "Cannot find point 0: 52.0,13.0",
+                    "details": "com.graphhopper.util.exceptions.PointNotFoundException"
+                }]
+            }"#
+            .into(),
+        );
+
+        let Err(ParsingError::InvalidStatusCode { code, description }) = result else {
+            panic!("Expected an InvalidStatusCode error");
+        };
+        assert_eq!(
+            code,
+            "com.graphhopper.util.exceptions.PointNotFoundException"
+        );
+        assert_eq!(
+            description.as_deref(),
+            Some("Cannot find point 0: 52.0,13.0")
+        );
+    }
+
+    #[test]
+    fn parse_graphhopper_geojson_points() {
+        let parser = GraphHopperResponseParser::new();
+        let routes = parser
+            .parse_response(
+                r#"{
+                    "paths": [{
+                        "distance": 100.0,
+                        "time": 10000,
+                        "points": {"type": "LineString", "coordinates": [[13.0, 52.0, 34.5], [13.001, 52.0, 35.0]]},
+                        "instructions": [
+                            {"distance": 100.0, "time": 10000, "sign": 0, "interval": [0, 1], "text": "Continue"},
+                            {"distance": 0.0, "time": 0, "sign": 4, "interval": [1, 1], "text": "Arrive at destination"}
+                        ]
+                    }]
+                }"#
+                .into(),
+            )
+            .expect("Unable to parse GraphHopper response");
+
+        let route = &routes[0];
+        assert_eq!(
+            route.geometry,
+            vec![
+                GeographicCoordinate {
+                    lat: 52.0,
+                    lng: 13.0
+                },
+                GeographicCoordinate {
+                    lat: 52.0,
+                    lng: 13.001
+                }
+            ]
+        );
+        assert_eq!(route.waypoints.len(), 2);
+        assert_eq!(route.steps[0].duration, 10.0);
+        assert_eq!(route.steps[0].annotations, None);
+    }
+}
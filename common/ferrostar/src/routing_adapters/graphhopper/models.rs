@@ -0,0 +1,193 @@
+//! `GraphHopper` models from the API spec: <https://docs.graphhopper.com/#operation/postRoute>
+//!
+//! We omit some fields which are not needed for navigation.
+
+use crate::models::{ManeuverModifier, ManeuverType};
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use serde::Deserialize;
+use serde_json::Value;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug)]
+pub struct RouteResponse {
+    #[serde(default)]
+    pub paths: Vec<Path>,
+    /// A description of the error, if the request failed.
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Details of what went wrong for failed requests.
+    ///
+    /// NOTE: Successful responses also have hints, but in a different format.
+    #[serde(default)]
+    pub hints: Value,
+}
+
+impl RouteResponse {
+    /// The type of error (ex: `PointNotFoundException`), if the request failed.
+    pub fn error_details(&self) -> Option<&str> {
+        self.hints
+            .as_array()?
+            .iter()
+            .find_map(|hint| hint.get("details")?.as_str())
+    }
+}
+
+/// A route between two or more points.
+#[derive(Deserialize, Debug)]
+pub struct Path {
+    /// The distance traveled by the route, in meters.
+    pub distance: f64,
+    /// The geometry of the route.
+    pub points: Points,
+    /// The multiplier used to encode the points, if they are encoded.
+    ///
+    /// This is absent from the responses of older servers, which always use `1e5`.
+    #[serde(default)]
+    pub points_encoded_multiplier: Option<f64>,
+    /// The input points, snapped to the road network.
+    #[serde(default)]
+    pub snapped_waypoints: Option<Points>,
+    /// Turn-by-turn instructions.
+    #[serde(default)]
+    pub instructions: Vec<Instruction>,
+    /// Details (ex: `max_speed` or `road_class`) along the route,
+    /// as `[from, to, value]` ranges of point indices.
+    #[serde(default)]
+    pub details: HashMap<String, Vec<(usize, usize, Value)>>,
+}
+
+/// A list of points, which is either an encoded polyline or a `GeoJSON` `LineString`.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Points {
+    Encoded(String),
+    LineString {
+        /// Coordinates as `[lng, lat]` (optionally followed by the elevation).
+        coordinates: Vec<Vec<f64>>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Instruction {
+    /// The distance to travel after the maneuver, in meters.
+    pub distance: f64,
+    /// The estimated travel time after the maneuver, in milliseconds.
+    pub time: f64,
+    /// A code for the type of maneuver (see [`Instruction::maneuver`]).
+    pub sign: i32,
+    /// The first and last indices of the points covered by this instruction.
+    pub interval: (usize, usize),
+    /// A description of the maneuver (ex: "Turn right onto Main Street").
+    pub text: String,
+    #[serde(default)]
+    pub street_name: Option<String>,
+    /// The exit to take when this is a roundabout maneuver.
+    #[serde(default)]
+    pub exit_number: Option<u32>,
+    /// The angle (in radians) travelled around a roundabout,
+    /// which is negative for clockwise travel.
+    #[serde(default)]
+    pub turn_angle: Option<f64>,
+}
+
+impl Instruction {
+    /// Maps the [`sign`](Instruction::sign) of the instruction
+    /// to a standard maneuver type and modifier.
+    ///
+    /// Signs which don't have a sensible mapping (ex: public transit signs) have no maneuver type.
+    pub fn maneuver(&self) -> (Option<ManeuverType>, Option<ManeuverModifier>) {
+        match self.sign {
+            -98 | -8 | 8 => (Some(ManeuverType::Turn), Some(ManeuverModifier::UTurn)),
+            -7 => (Some(ManeuverType::Fork), Some(ManeuverModifier::SlightLeft)),
+            -6 => (Some(ManeuverType::ExitRoundabout), None),
+            -3 => (Some(ManeuverType::Turn), Some(ManeuverModifier::SharpLeft)),
+            -2 => (Some(ManeuverType::Turn), Some(ManeuverModifier::Left)),
+            -1 => (Some(ManeuverType::Turn), Some(ManeuverModifier::SlightLeft)),
+            0 => (
+                Some(ManeuverType::Continue),
+                Some(ManeuverModifier::Straight),
+            ),
+            1 => (
+                Some(ManeuverType::Turn),
+                Some(ManeuverModifier::SlightRight),
+            ),
+            2 => (Some(ManeuverType::Turn), Some(ManeuverModifier::Right)),
+            3 => (Some(ManeuverType::Turn), Some(ManeuverModifier::SharpRight)),
+            // Reaching the destination or a via point
+            4 | 5 => (Some(ManeuverType::Arrive), None),
+            6 => (Some(ManeuverType::Roundabout), None),
+            7 => (
+                Some(ManeuverType::Fork),
+                Some(ManeuverModifier::SlightRight),
+            ),
+            _ => (None, None),
+        }
+    }
+
+    /// The number of degrees travelled around a roundabout, if applicable.
+    pub fn roundabout_exit_degrees(&self) -> Option<u16> {
+        self.turn_angle
+            .filter(|angle| angle.is_finite())
+            .map(|angle| angle.abs().to_degrees().round() as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_error_response() {
+        let response: RouteResponse = serde_json::from_str(
+            r#"{
+                "message": "Cannot find point 0: 52.0,13.0",
+                "hints": [{
+                    "message": "Cannot find point 0: 52.0,13.0",
+                    "details": "com.graphhopper.util.exceptions.PointNotFoundException",
+                    "point_index": 0
+                }]
+            }"#,
+        )
+        .expect("Unable to deserialize GraphHopper error response");
+
+        assert!(response.paths.is_empty());
+        assert_eq!(
+            response.message.as_deref(),
+            Some("Cannot find point 0: 52.0,13.0")
+        );
+        assert_eq!(
+            response.error_details(),
+            Some("com.graphhopper.util.exceptions.PointNotFoundException")
+        );
+    }
+
+    #[test]
+    fn deserialize_geojson_points() {
+        let points: Points = serde_json::from_str(
+            r#"{"type": "LineString", "coordinates": [[13.3888, 52.517, 35.2], [13.3891, 52.5178, 36.0]]}"#,
+        )
+        .expect("Unable to deserialize GeoJSON points");
+
+        let Points::LineString { coordinates } = points else {
+            panic!("Expected a GeoJSON LineString");
+        };
+        assert_eq!(coordinates.len(), 2);
+    }
+
+    #[test]
+    fn roundabout_exit_degrees() {
+        let instruction: Instruction = serde_json::from_str(
+            r#"{"distance": 61.2, "time": 11015, "sign": 6, "interval": [4, 7], "text": "At roundabout, take exit 2", "exit_number": 2, "turn_angle": -3.14159}"#,
+        )
+        .expect("Unable to deserialize GraphHopper instruction");
+
+        assert_eq!(
+            instruction.maneuver(),
+            (Some(ManeuverType::Roundabout), None)
+        );
+        assert_eq!(instruction.roundabout_exit_degrees(), Some(180));
+        assert_eq!(instruction.exit_number, Some(2));
+    }
+}
@@ -50,10 +50,16 @@ use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 #[cfg(feature = "alloc")]
 use alloc::{string::String, sync::Arc, vec::Vec};
 
-use crate::routing_adapters::osrm::OsrmResponseParser;
-use crate::routing_adapters::valhalla::ValhallaHttpRequestGenerator;
+use crate::routing_adapters::graphhopper::{
+    GraphHopperHttpRequestGenerator, GraphHopperResponseParser,
+};
+use crate::routing_adapters::osrm::{OsrmHttpRequestGenerator, OsrmResponseParser};
+#[cfg(feature = "wasm-bindgen")]
+use crate::routing_adapters::valhalla::JsValhallaRequestOptions;
+use crate::routing_adapters::valhalla::{ValhallaHttpRequestGenerator, ValhallaRequestOptions};
 
 pub mod error;
+pub mod graphhopper;
 pub mod osrm;
 pub mod utilities;
 pub mod valhalla;
@@ -155,12 +161,68 @@ impl RouteAdapter {
         profile: String,
         options_json: Option<String>,
     ) -> Result<Self, InstantiationError> {
-        let request_generator = Arc::new(ValhallaHttpRequestGenerator::with_options_json(
+        Self::new_valhalla_http_with_options(
+            endpoint_url,
+            profile,
+            Arc::new(ValhallaRequestOptions::default()),
+            options_json,
+        )
+    }
+
+    /// Creates a route adapter for a Valhalla server with typed request options
+    /// (see [`ValhallaRequestOptionsBuilder`](crate::routing_adapters::valhalla::ValhallaRequestOptionsBuilder)).
+    ///
+    /// The response parser matches the response format in the options
+    /// (see [`ValhallaResponseFormat`](crate::routing_adapters::valhalla::ValhallaResponseFormat)).
+    /// Any options with the same keys in `options_json` take precedence over the typed options.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new_valhalla_http_with_options(
+        endpoint_url: String,
+        profile: String,
+        request_options: Arc<ValhallaRequestOptions>,
+        options_json: Option<String>,
+    ) -> Result<Self, InstantiationError> {
+        let response_parser = request_options.response_format().response_parser()?;
+        let request_generator = Arc::new(
+            ValhallaHttpRequestGenerator::with_options_json(
+                endpoint_url,
+                profile,
+                options_json.as_deref(),
+            )?
+            .with_request_options(Arc::unwrap_or_clone(request_options)),
+        );
+        Ok(Self::new(request_generator, response_parser))
+    }
+
+    /// Creates a route adapter for an OSRM server.
+    ///
+    /// See [`OsrmHttpRequestGenerator`] for details of the parameters.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new_osrm_http(endpoint_url: String, profile: String, annotations: Vec<String>) -> Self {
+        let request_generator = Arc::new(OsrmHttpRequestGenerator::new(
+            endpoint_url,
+            profile,
+            annotations,
+        ));
+        let response_parser = Arc::new(OsrmResponseParser::new(6));
+        Self::new(request_generator, response_parser)
+    }
+
+    /// Creates a route adapter for a `GraphHopper` server.
+    ///
+    /// See [`GraphHopperHttpRequestGenerator`] for details of the parameters.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new_graphhopper_http(
+        endpoint_url: String,
+        profile: String,
+        options_json: Option<String>,
+    ) -> Result<Self, InstantiationError> {
+        let request_generator = Arc::new(GraphHopperHttpRequestGenerator::with_options_json(
             endpoint_url,
             profile,
             options_json.as_deref(),
         )?);
-        let response_parser = Arc::new(OsrmResponseParser::new(6));
+        let response_parser = Arc::new(GraphHopperResponseParser::new());
         Ok(Self::new(request_generator, response_parser))
     }
 
@@ -204,6 +266,25 @@ impl JsRouteAdapter {
         // TODO: We should have a better error handling strategy here. Same for the other methods.
     }
 
+    /// Creates a new RouteAdapter with a Valhalla HTTP request generator using typed request options,
+    /// and a response parser for the response format in the options.
+    #[wasm_bindgen(js_name = newValhallaHttpWithOptions)]
+    pub fn new_valhalla_http_with_options(
+        endpoint_url: String,
+        profile: String,
+        request_options: &JsValhallaRequestOptions,
+        costing_options_json: Option<String>,
+    ) -> Result<JsRouteAdapter, JsValue> {
+        RouteAdapter::new_valhalla_http_with_options(
+            endpoint_url,
+            profile,
+            Arc::clone(&request_options.0),
+            costing_options_json,
+        )
+        .map(JsRouteAdapter)
+        .map_err(|e| JsValue::from_str(&format!("{}", e)))
+    }
+
     #[wasm_bindgen(js_name = generateRequest)]
     pub fn generate_request(
         &self,
@@ -1,28 +1,166 @@
-//! Response parsing for OSRM-compatible JSON (including Stadia Maps, Valhalla, Mapbox, etc.).
+//! HTTP request generation for OSRM servers,
+//! and response parsing for OSRM-compatible JSON (including Stadia Maps, Valhalla, Mapbox, etc.).
 
 pub(crate) mod models;
 pub mod utilities;
 
-use super::RouteResponseParser;
+use super::{RouteRequest, RouteRequestGenerator, RouteResponseParser};
 use crate::models::{
     AnyAnnotationValue, GeographicCoordinate, Incident, LaneInfo, RouteStep, SpokenInstruction,
-    VisualInstruction, VisualInstructionContent, Waypoint, WaypointKind,
+    UserLocation, VisualInstruction, VisualInstructionContent, Waypoint, WaypointKind,
 };
 use crate::routing_adapters::utilities::get_coordinates_from_geometry;
 use crate::routing_adapters::{
     osrm::models::{
         Route as OsrmRoute, RouteResponse, RouteStep as OsrmRouteStep, Waypoint as OsrmWaypoint,
     },
-    ParsingError, Route,
+    ParsingError, Route, RoutingRequestGenerationError,
 };
 #[cfg(all(not(feature = "std"), feature = "alloc"))]
-use alloc::{string::ToString, vec, vec::Vec};
+use alloc::collections::BTreeMap as HashMap;
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 use geo::BoundingRect;
 use models::BannerContent;
 use polyline::decode_polyline;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 use utilities::get_annotation_slice;
 use uuid::Uuid;
 
+/// The range (in degrees) of bearings to allow at the start of the route
+/// when the accuracy of the user's course is unknown.
+const DEFAULT_BEARING_RANGE: u16 = 45;
+
+/// A route request generator for OSRM servers operating over HTTP.
+///
+/// This generates requests for the standard OSRM
+/// [route service](https://project-osrm.org/docs/v5.24.0/api/#route-service),
+/// with full route geometry (encoded with a precision of 6 digits) and turn-by-turn steps,
+/// which can be parsed with an [`OsrmResponseParser`].
+///
+/// The user's course over ground (if any) is used as the bearing of the start of the route,
+/// and their horizontal accuracy is used as the search radius.
+/// [`WaypointKind::Via`] waypoints are passed through without starting a new leg.
+///
+/// ```
+/// use ferrostar::routing_adapters::osrm::OsrmHttpRequestGenerator;
+/// let request_generator = OsrmHttpRequestGenerator::new(
+///     "https://router.project-osrm.org".to_string(),
+///     "driving".to_string(),
+///     vec!["duration".to_string(), "distance".to_string(), "speed".to_string()],
+/// );
+/// ```
+#[derive(Debug)]
+pub struct OsrmHttpRequestGenerator {
+    /// The base URL of the OSRM server, without the service path.
+    ///
+    /// Users *may* include a query string with an API key.
+    endpoint_url: String,
+    /// The OSRM profile to use (ex: `driving`).
+    profile: String,
+    /// The annotations to include for each segment of the route (ex: `duration` and `speed`).
+    ///
+    /// No annotations are requested if this is empty.
+    annotations: Vec<String>,
+}
+
+impl OsrmHttpRequestGenerator {
+    /// Creates a new OSRM request generator given the base URL of the server, a profile name,
+    /// and the annotations to request.
+    pub fn new(endpoint_url: String, profile: String, annotations: Vec<String>) -> Self {
+        Self {
+            endpoint_url,
+            profile,
+            annotations,
+        }
+    }
+}
+
+impl RouteRequestGenerator for OsrmHttpRequestGenerator {
+    fn generate_request(
+        &self,
+        user_location: UserLocation,
+        waypoints: Vec<Waypoint>,
+    ) -> Result<RouteRequest, RoutingRequestGenerationError> {
+        if waypoints.is_empty() {
+            return Err(RoutingRequestGenerationError::NotEnoughWaypoints);
+        }
+
+        let coordinates = core::iter::once(user_location.coordinates)
+            .chain(waypoints.iter().map(|waypoint| waypoint.coordinate))
+            .map(|coordinate| format!("{},{}", coordinate.lng, coordinate.lat))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        // Only the start of the route has a known bearing and radius.
+        let start_bearing = user_location
+            .course_over_ground
+            .map_or(String::new(), |course| {
+                let range = course
+                    .accuracy
+                    .map_or(DEFAULT_BEARING_RANGE, |accuracy| accuracy.min(180));
+                format!("{},{range}", course.degrees)
+            });
+        let start_radius = core::cmp::max(5, user_location.horizontal_accuracy as u16);
+        let empty_for_waypoints = ";".repeat(waypoints.len());
+
+        let mut parameters = vec![
+            "steps=true".to_string(),
+            "overview=full".to_string(),
+            "geometries=polyline6".to_string(),
+            format!("bearings={start_bearing}{empty_for_waypoints}"),
+            format!("radiuses={start_radius}{empty_for_waypoints}"),
+        ];
+        if !self.annotations.is_empty() {
+            parameters.push(format!("annotations={}", self.annotations.join(",")));
+        }
+        if waypoints
+            .iter()
+            .any(|waypoint| waypoint.kind == WaypointKind::Via)
+        {
+            // The start of the route is always a leg boundary.
+            let leg_boundaries = core::iter::once(0)
+                .chain(
+                    waypoints
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, waypoint)| {
+                            waypoint.kind == WaypointKind::Break || *index == waypoints.len() - 1
+                        })
+                        .map(|(index, _)| index + 1),
+                )
+                .map(|index| index.to_string())
+                .collect::<Vec<_>>()
+                .join(";");
+            parameters.push(format!("waypoints={leg_boundaries}"));
+        }
+
+        let (base_url, query) = self
+            .endpoint_url
+            .split_once('?')
+            .unwrap_or((&self.endpoint_url, ""));
+        if !query.is_empty() {
+            parameters.push(query.to_string());
+        }
+
+        Ok(RouteRequest::HttpGet {
+            url: format!(
+                "{}/route/v1/{}/{coordinates}?{}",
+                base_url.trim_end_matches('/'),
+                self.profile,
+                parameters.join("&")
+            ),
+            headers: HashMap::new(),
+        })
+    }
+}
+
 /// A response parser for OSRM-compatible routing backends.
 ///
 /// The parser is NOT limited to only the standard OSRM format; many Valhalla/Mapbox tags are also
@@ -327,6 +465,13 @@ impl RouteStep {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::CourseOverGround;
+
+    #[cfg(all(feature = "std", not(feature = "web-time")))]
+    use std::time::SystemTime;
+
+    #[cfg(feature = "web-time")]
+    use web_time::SystemTime;
 
     const STANDARD_OSRM_POLYLINE6_RESPONSE: &str =
         include_str!("fixtures/standard_osrm_polyline6_response.json");
@@ -338,6 +483,132 @@ mod tests {
     const VALHALLA_OSRM_RESPONSE_WITH_EXITS: &str =
         include_str!("fixtures/valhalla_osrm_response_with_exit_info.json");
 
+    const OSRM_ENDPOINT_URL: &str = "https://router.project-osrm.org";
+    const USER_LOCATION: UserLocation = UserLocation {
+        coordinates: GeographicCoordinate {
+            lat: 52.51,
+            lng: 13.38,
+        },
+        horizontal_accuracy: 6.0,
+        course_over_ground: None,
+        timestamp: SystemTime::UNIX_EPOCH,
+        speed: None,
+    };
+    const WAYPOINTS: [Waypoint; 2] = [
+        Waypoint {
+            coordinate: GeographicCoordinate {
+                lat: 52.52,
+                lng: 13.39,
+            },
+            kind: WaypointKind::Break,
+        },
+        Waypoint {
+            coordinate: GeographicCoordinate {
+                lat: 52.53,
+                lng: 13.42,
+            },
+            kind: WaypointKind::Break,
+        },
+    ];
+
+    fn generate_url(
+        generator: &OsrmHttpRequestGenerator,
+        user_location: UserLocation,
+        waypoints: Vec<Waypoint>,
+    ) -> String {
+        match generator.generate_request(user_location, waypoints) {
+            Ok(RouteRequest::HttpGet { url, headers }) => {
+                assert!(headers.is_empty());
+                url
+            }
+            Ok(RouteRequest::HttpPost { .. }) => {
+                unreachable!("The OSRM HTTP request generator only generates GET requests")
+            }
+            Err(e) => panic!("Failed to generate request: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn osrm_request_not_enough_waypoints() {
+        let generator = OsrmHttpRequestGenerator::new(
+            OSRM_ENDPOINT_URL.to_string(),
+            "driving".to_string(),
+            Vec::new(),
+        );
+
+        assert!(matches!(
+            generator.generate_request(USER_LOCATION, Vec::new()),
+            Err(RoutingRequestGenerationError::NotEnoughWaypoints)
+        ));
+    }
+
+    #[test]
+    fn osrm_request_url() {
+        let generator = OsrmHttpRequestGenerator::new(
+            format!("{OSRM_ENDPOINT_URL}/"),
+            "driving".to_string(),
+            vec!["duration".to_string(), "speed".to_string()],
+        );
+
+        assert_eq!(
+            generate_url(&generator, USER_LOCATION, WAYPOINTS.to_vec()),
+            "https://router.project-osrm.org/route/v1/driving/\
+            13.38,52.51;13.39,52.52;13.42,52.53\
+            ?steps=true&overview=full&geometries=polyline6\
+            &bearings=;;&radiuses=6;;&annotations=duration,speed"
+        );
+    }
+
+    #[test]
+    fn osrm_request_url_with_course_and_api_key() {
+        let generator = OsrmHttpRequestGenerator::new(
+            format!("{OSRM_ENDPOINT_URL}?api_key=YOUR-API-KEY"),
+            "bike".to_string(),
+            Vec::new(),
+        );
+        let user_location = UserLocation {
+            horizontal_accuracy: -1.0,
+            course_over_ground: Some(CourseOverGround {
+                degrees: 42,
+                accuracy: Some(12),
+            }),
+            ..USER_LOCATION
+        };
+
+        assert_eq!(
+            generate_url(&generator, user_location, WAYPOINTS.to_vec()),
+            "https://router.project-osrm.org/route/v1/bike/\
+            13.38,52.51;13.39,52.52;13.42,52.53\
+            ?steps=true&overview=full&geometries=polyline6\
+            &bearings=42,12;;&radiuses=5;;&api_key=YOUR-API-KEY"
+        );
+
+        let user_location = UserLocation {
+            course_over_ground: Some(CourseOverGround {
+                degrees: 42,
+                accuracy: None,
+            }),
+            ..USER_LOCATION
+        };
+        assert!(generate_url(&generator, user_location, WAYPOINTS.to_vec())
+            .contains("&bearings=42,45;;&"));
+    }
+
+    #[test]
+    fn osrm_request_url_with_via_waypoints() {
+        let generator = OsrmHttpRequestGenerator::new(
+            OSRM_ENDPOINT_URL.to_string(),
+            "driving".to_string(),
+            Vec::new(),
+        );
+        let mut waypoints = vec![WAYPOINTS[0], WAYPOINTS[0], WAYPOINTS[1], WAYPOINTS[1]];
+        waypoints[0].kind = WaypointKind::Via;
+        waypoints[3].kind = WaypointKind::Via;
+
+        // The final waypoint always ends a leg, even when it is a via waypoint.
+        assert!(generate_url(&generator, USER_LOCATION, waypoints).ends_with("&waypoints=0;2;3;4"));
+    }
+
     #[test]
     fn parse_standard_osrm() {
         let parser = OsrmResponseParser::new(6);
@@ -0,0 +1,1419 @@
+//! High-level HTTP request generation for Valhalla HTTP APIs,
+//! and response parsing for Valhalla's native JSON format.
+
+pub(crate) mod models;
+mod options;
+#[cfg(feature = "valhalla-pbf")]
+pub(crate) mod proto;
+
+#[cfg(feature = "wasm-bindgen")]
+pub use options::{JsValhallaRequestOptions, JsValhallaRequestOptionsBuilder};
+pub use options::{
+    ValhallaDateTime, ValhallaDirectionsType, ValhallaRequestOptions,
+    ValhallaRequestOptionsBuilder, ValhallaUnits,
+};
+
+use super::{RouteRequest, RouteResponseParser, RoutingRequestGenerationError};
+use crate::models::{
+    BoundingBox, GeographicCoordinate, RouteStep, SpokenInstruction, UserLocation,
+    VisualInstruction, VisualInstructionContent, Waypoint, WaypointKind,
+};
+use crate::routing_adapters::osrm::OsrmResponseParser;
+use crate::routing_adapters::utilities::get_coordinates_from_geometry;
+use crate::routing_adapters::{ParsingError, Route, RouteRequestGenerator};
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::collections::BTreeMap as HashMap;
+use geo::{BoundingRect, LineString, Point};
+use models::{Maneuver, RouteResponse, Trip};
+use serde_json::{json, Map, Value as JsonValue};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[cfg(feature = "wasm-bindgen")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wasm-bindgen")]
+use tsify::Tsify;
+
+use crate::routing_adapters::error::InstantiationError;
+#[cfg(feature = "alloc")]
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+
+/// The format of the responses requested by a [`ValhallaHttpRequestGenerator`].
+///
+/// This is set with [`ValhallaRequestOptionsBuilder::with_response_format`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Serialize, Deserialize, Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(from_wasm_abi))]
+pub enum ValhallaResponseFormat {
+    /// OSRM-compatible JSON with banner and voice instructions,
+    /// which can be parsed with an [`OsrmResponseParser`].
+    ///
+    /// This is the richest format, so it is used by default.
+    #[default]
+    Osrm,
+    /// Valhalla's native JSON format,
+    /// which can be parsed with a [`ValhallaResponseParser`].
+    ///
+    /// This is useful for servers which do not include the OSRM serializer.
+    Json,
+    /// Valhalla's protobuf format,
+    /// which can be parsed with a [`ValhallaPbfResponseParser`].
+    ///
+    /// This has the smallest responses, which are also the fastest to parse.
+    /// It includes the same information as the native JSON format, except for lanes.
+    ///
+    /// Parsing this format requires the `valhalla-pbf` feature.
+    Pbf,
+}
+
+impl ValhallaResponseFormat {
+    /// Creates a response parser for responses in this format.
+    ///
+    /// Returns an error for [`ValhallaResponseFormat::Pbf`]
+    /// if the `valhalla-pbf` feature is not enabled.
+    pub fn response_parser(self) -> Result<Arc<dyn RouteResponseParser>, InstantiationError> {
+        match self {
+            ValhallaResponseFormat::Osrm => Ok(Arc::new(OsrmResponseParser::new(SHAPE_PRECISION))),
+            ValhallaResponseFormat::Json => Ok(Arc::new(ValhallaResponseParser::new())),
+            #[cfg(feature = "valhalla-pbf")]
+            ValhallaResponseFormat::Pbf => Ok(Arc::new(ValhallaPbfResponseParser::new())),
+            #[cfg(not(feature = "valhalla-pbf"))]
+            ValhallaResponseFormat::Pbf => Err(InstantiationError::UnsupportedResponseFormat {
+                feature: String::from("valhalla-pbf"),
+            }),
+        }
+    }
+}
+
+/// A route request generator for Valhalla backends operating over HTTP.
+///
+/// Valhalla supports the [`WaypointKind`] field of [`Waypoint`]s. Variants have the same meaning as their
+/// [`type` strings in Valhalla API](https://valhalla.github.io/valhalla/api/turn-by-turn/api-reference/#locations)
+/// having the same name.
+///
+/// ```
+/// use serde_json::{json, Map, Value};
+/// use ferrostar::routing_adapters::valhalla::ValhallaHttpRequestGenerator;
+/// let options: Map<String, Value> = json!({
+///     "costing_options": {
+///         "low_speed_vehicle": {
+///             "vehicle_type": "golf_cart"
+///         }
+///     }
+/// }).as_object().unwrap().to_owned();;
+/// let request_generator = ValhallaHttpRequestGenerator::new("https://api.stadiamaps.com/route/v1?api_key=YOUR-API-KEY".to_string(), "low_speed_vehicle".to_string(), options);
+/// ```
+#[derive(Debug)]
+pub struct ValhallaHttpRequestGenerator {
+    /// The full URL of the Valhalla endpoint to access. This will normally be the route endpoint,
+    /// but the optimized route endpoint should be interchangeable.
+    ///
+    /// Users *may* include a query string with an API key.
+    endpoint_url: String,
+    /// The Valhalla costing model to use.
+    profile: String,
+    /// Typed options, including the response format and top-level parameters like the language and units.
+    request_options: ValhallaRequestOptions,
+    /// Arbitrary key/value pairs which override the defaults.
+    ///
+    /// These can contain complex nested structures,
+    /// as in the case of `costing_options`.
+    options: Map<String, JsonValue>,
+}
+
+impl ValhallaHttpRequestGenerator {
+    /// Creates a new Valhalla request generator given an endpoint URL, a profile name,
+    /// and options to include in the request JSON.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde_json::{json, Map, Value as JsonValue};
+    /// # use ferrostar::routing_adapters::valhalla::ValhallaHttpRequestGenerator;
+    /// // Example for illustration; you should do proper error checking when parsing this way,
+    /// // or else use [`ValhallaHttpRequestGenerator::with_options_json`]
+    /// let options: Map<String, JsonValue> = json!({
+    ///     "costing_options": {
+    ///         "low_speed_vehicle": {
+    ///             "vehicle_type": "golf_cart"
+    ///         }
+    ///     }
+    /// }).as_object().unwrap().to_owned();
+    ///
+    /// // Without options
+    /// let request_generator_no_opts = ValhallaHttpRequestGenerator::new(
+    ///     "https://api.stadiamaps.com/route/v1?api_key=YOUR-API-KEY".to_string(),
+    ///     "low_speed_vehicle".to_string(),
+    ///     Map::new()
+    /// );
+    ///
+    /// // With options
+    /// let request_generator_opts = ValhallaHttpRequestGenerator::new(
+    ///     "https://api.stadiamaps.com/route/v1?api_key=YOUR-API-KEY".to_string(),
+    ///     "low_speed_vehicle".to_string(),
+    ///     options
+    /// );
+    /// ```
+    pub fn new(endpoint_url: String, profile: String, options: Map<String, JsonValue>) -> Self {
+        Self {
+            endpoint_url,
+            profile,
+            request_options: ValhallaRequestOptions::default(),
+            options,
+        }
+    }
+
+    /// Creates a new Valhalla request generator given an endpoint URL, a profile name,
+    /// and options to include in the request JSON.
+    /// Options in this constructor are a JSON fragment representing any
+    /// options you want to add along with the request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ferrostar::routing_adapters::valhalla::ValhallaHttpRequestGenerator;
+    /// let options = r#"{
+    ///     "costing_options": {
+    ///         "low_speed_vehicle": {
+    ///             "vehicle_type": "golf_cart"
+    ///         }
+    ///     }
+    /// }"#;
+    ///
+    /// // Without options
+    /// let request_generator_no_opts = ValhallaHttpRequestGenerator::with_options_json(
+    ///     "https://api.stadiamaps.com/route/v1?api_key=YOUR-API-KEY".to_string(),
+    ///     "low_speed_vehicle".to_string(),
+    ///     None,
+    /// );
+    ///
+    /// // With options
+    /// let request_generator_opts = ValhallaHttpRequestGenerator::with_options_json(
+    ///     "https://api.stadiamaps.com/route/v1?api_key=YOUR-API-KEY".to_string(),
+    ///     "low_speed_vehicle".to_string(),
+    ///     Some(options),
+    /// );
+    /// ```
+    pub fn with_options_json(
+        endpoint_url: String,
+        profile: String,
+        options_json: Option<&str>,
+    ) -> Result<Self, InstantiationError> {
+        let parsed_options = match options_json {
+            // TODO: Another error variant
+            Some(options) => serde_json::from_str::<JsonValue>(options)?
+                .as_object()
+                .ok_or(InstantiationError::OptionsJsonParseError)?
+                .to_owned(),
+            None => Map::new(),
+        };
+        Ok(Self::new(endpoint_url, profile, parsed_options))
+    }
+
+    /// Sets the typed request options (see [`ValhallaRequestOptionsBuilder`]),
+    /// including the format of the responses to request.
+    ///
+    /// The responses must be parsed with a matching [`RouteResponseParser`]
+    /// (see [`ValhallaResponseFormat::response_parser`]).
+    /// Any options with the same keys in the untyped options take precedence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ferrostar::routing_adapters::valhalla::{ValhallaHttpRequestGenerator, ValhallaRequestOptionsBuilder, ValhallaUnits};
+    /// let request_options = ValhallaRequestOptionsBuilder::new()
+    ///     .with_language("fr".to_string())
+    ///     .with_units(ValhallaUnits::Kilometers)
+    ///     .build()
+    ///     .expect("Invalid options");
+    /// let request_generator = ValhallaHttpRequestGenerator::with_options_json(
+    ///     "https://valhalla.example.com/route".to_string(),
+    ///     "auto".to_string(),
+    ///     None,
+    /// )
+    /// .expect("Invalid options")
+    /// .with_request_options((*request_options).clone());
+    /// ```
+    #[must_use]
+    pub fn with_request_options(mut self, request_options: ValhallaRequestOptions) -> Self {
+        self.request_options = request_options;
+        self
+    }
+}
+
+impl RouteRequestGenerator for ValhallaHttpRequestGenerator {
+    fn generate_request(
+        &self,
+        user_location: UserLocation,
+        waypoints: Vec<Waypoint>,
+    ) -> Result<RouteRequest, RoutingRequestGenerationError> {
+        if waypoints.is_empty() {
+            Err(RoutingRequestGenerationError::NotEnoughWaypoints)
+        } else {
+            let headers =
+                HashMap::from([("Content-Type".to_string(), "application/json".to_string())]);
+            let mut start = json!({
+                "lat": user_location.coordinates.lat,
+                "lon": user_location.coordinates.lng,
+                "street_side_tolerance": self
+                    .request_options
+                    .street_side_tolerance(user_location.horizontal_accuracy),
+            });
+            if let Some(course) = user_location
+                .course_over_ground
+                .filter(|_| self.request_options.include_heading())
+            {
+                start["heading"] = course.degrees.into();
+                if let Some(heading_tolerance) = self.request_options.heading_tolerance() {
+                    start["heading_tolerance"] = heading_tolerance.into();
+                }
+            }
+
+            let locations: Vec<JsonValue> = core::iter::once(start)
+                .chain(waypoints.iter().map(|waypoint| {
+                    json!({
+                        "lat": waypoint.coordinate.lat,
+                        "lon": waypoint.coordinate.lng,
+                        "type": match waypoint.kind {
+                            WaypointKind::Break => "break",
+                            WaypointKind::Via => "via",
+                        },
+                    })
+                }))
+                .collect();
+
+            // NOTE: We use the OSRM format by default, as it is the richest one.
+            // Certain info (like banners) are only available in the OSRM format.
+            // TODO: Trace attributes as we go rather than pulling a fat payload upfront that we might ditch later?
+            let mut args = match self.request_options.response_format() {
+                ValhallaResponseFormat::Osrm => json!({
+                    "format": "osrm",
+                    "filters": {
+                        "action": "include",
+                        "attributes": [
+                          "shape_attributes.speed",
+                          "shape_attributes.speed_limit",
+                          "shape_attributes.time",
+                          "shape_attributes.length"
+                        ]
+                    },
+                    "banner_instructions": true,
+                    "voice_instructions": true,
+                }),
+                ValhallaResponseFormat::Json => json!({
+                    "format": "json",
+                }),
+                ValhallaResponseFormat::Pbf => json!({
+                    "format": "pbf",
+                    // The options are needed for the units of the route.
+                    "pbf_field_selector": {
+                        "options": true,
+                        "directions": true,
+                    },
+                }),
+            };
+            args["costing"] = self.profile.clone().into();
+            args["locations"] = locations.into();
+            self.request_options.apply_to(&mut args);
+
+            for (k, v) in &self.options {
+                args[k] = v.clone();
+            }
+
+            let body = serde_json::to_vec(&args)?;
+            Ok(RouteRequest::HttpPost {
+                url: self.endpoint_url.clone(),
+                headers,
+                body,
+            })
+        }
+    }
+}
+
+/// The precision of the polylines in Valhalla responses.
+const SHAPE_PRECISION: u32 = 6;
+/// How far ahead of a maneuver (in seconds of travel) to announce it.
+const PRE_TRANSITION_SECONDS: f64 = 10.0;
+/// How far ahead of a maneuver (in seconds of travel) to alert the user that it is coming up.
+const TRANSITION_ALERT_SECONDS: f64 = 30.0;
+
+/// A response parser for Valhalla's native JSON format.
+///
+/// Routes are built from the maneuvers of each leg,
+/// including the alternates (if any) after the main route.
+///
+/// The native format does not include banner instructions,
+/// so a visual instruction is synthesized from the upcoming maneuver
+/// (including its lanes and exit signs, if any).
+/// Spoken instructions are built from the `verbal_*` instructions of the maneuvers,
+/// with trigger distances estimated from the average speed along each step.
+#[derive(Debug, Default)]
+pub struct ValhallaResponseParser;
+
+impl ValhallaResponseParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl RouteResponseParser for ValhallaResponseParser {
+    fn parse_response(&self, response: Vec<u8>) -> Result<Vec<Route>, ParsingError> {
+        let res: RouteResponse = serde_json::from_slice(&response)?;
+
+        let Some(trip) = &res.trip else {
+            return Err(match res.error {
+                Some(error) => ParsingError::InvalidStatusCode {
+                    code: res
+                        .error_code
+                        .map_or_else(|| "Error".to_string(), |code| code.to_string()),
+                    description: Some(error),
+                },
+                None => ParsingError::InvalidRouteObject {
+                    error: "Response does not contain a trip".to_string(),
+                },
+            });
+        };
+
+        core::iter::once(trip)
+            .chain(res.alternates.iter().map(|alternate| &alternate.trip))
+            .map(Route::from_valhalla)
+            .collect()
+    }
+}
+
+/// A response parser for Valhalla's protobuf format.
+///
+/// The routes are parsed in the same way as by a [`ValhallaResponseParser`],
+/// except that lanes are not available.
+#[cfg(feature = "valhalla-pbf")]
+#[derive(Debug, Default)]
+pub struct ValhallaPbfResponseParser;
+
+#[cfg(feature = "valhalla-pbf")]
+impl ValhallaPbfResponseParser {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(feature = "valhalla-pbf")]
+impl RouteResponseParser for ValhallaPbfResponseParser {
+    fn parse_response(&self, response: Vec<u8>) -> Result<Vec<Route>, ParsingError> {
+        use prost::Message;
+
+        // Some errors (ex: invalid requests) are returned as JSON, even when requesting PBF.
+        // An `Api` message never starts with an opening brace, as it has no field 15.
+        if response.first() == Some(&b'{') {
+            return ValhallaResponseParser.parse_response(response);
+        }
+
+        let api = proto::Api::decode(response.as_slice()).map_err(|error| {
+            ParsingError::InvalidRouteObject {
+                error: error.to_string(),
+            }
+        })?;
+        if let Some(error) = api.error() {
+            return Err(ParsingError::InvalidStatusCode {
+                code: error.code.to_string(),
+                description: Some(error.description.clone()),
+            });
+        }
+
+        let trips = api.into_trips();
+        if trips.is_empty() {
+            return Err(ParsingError::InvalidRouteObject {
+                error: "Response does not contain any routes".to_string(),
+            });
+        }
+        trips.iter().map(Route::from_valhalla).collect()
+    }
+}
+
+impl Route {
+    /// Create a route from a Valhalla trip.
+    fn from_valhalla(trip: &Trip) -> Result<Self, ParsingError> {
+        let meters_per_unit = trip.units.meters();
+        let mut geometry: Vec<GeographicCoordinate> = Vec::new();
+        let mut steps = Vec::new();
+
+        for leg in &trip.legs {
+            let shape = get_coordinates_from_geometry(&leg.shape, SHAPE_PRECISION)?;
+            for (index, maneuver) in leg.maneuvers.iter().enumerate() {
+                steps.push(RouteStep::from_valhalla(
+                    &shape,
+                    maneuver,
+                    leg.maneuvers.get(index + 1),
+                    meters_per_unit,
+                )?);
+            }
+
+            // Each leg starts where the previous one ended.
+            let skip = usize::from(!geometry.is_empty() && geometry.last() == shape.first());
+            geometry.extend(shape.into_iter().skip(skip));
+        }
+
+        let Some(bbox) =
+            LineString::from_iter(geometry.iter().map(|coord| Point::from(*coord))).bounding_rect()
+        else {
+            return Err(ParsingError::InvalidGeometry {
+                error: "Bounding box could not be calculated".to_string(),
+            });
+        };
+
+        let waypoints = trip
+            .locations
+            .iter()
+            .map(|location| Waypoint {
+                coordinate: GeographicCoordinate {
+                    lat: location.lat,
+                    lng: location.lon,
+                },
+                kind: location.waypoint_kind(),
+            })
+            .collect();
+
+        Ok(Route {
+            geometry,
+            bbox: BoundingBox::from(bbox),
+            distance: steps.iter().map(|step| step.distance).sum(),
+            waypoints,
+            steps,
+        })
+    }
+}
+
+impl RouteStep {
+    fn from_valhalla(
+        leg_geometry: &[GeographicCoordinate],
+        maneuver: &Maneuver,
+        next_maneuver: Option<&Maneuver>,
+        meters_per_unit: f64,
+    ) -> Result<Self, ParsingError> {
+        let (begin, end) = (maneuver.begin_shape_index, maneuver.end_shape_index);
+        let mut geometry = leg_geometry
+            .get(begin..=end)
+            .ok_or_else(|| ParsingError::InvalidGeometry {
+                error: format!("Maneuver shape indices [{begin}, {end}] are out of bounds"),
+            })?
+            .to_vec();
+        if let [coordinate] = geometry[..] {
+            // Represent a point (ex: at the destination) as two identical coordinates,
+            // as in the OSRM format.
+            geometry.push(coordinate);
+        }
+
+        let distance = maneuver.length * meters_per_unit;
+        // The banner describes the upcoming maneuver, at the end of the step.
+        let upcoming_maneuver = next_maneuver.unwrap_or(maneuver);
+        let (maneuver_type, maneuver_modifier) = upcoming_maneuver.maneuver();
+        let visual_instructions = vec![VisualInstruction {
+            primary_content: VisualInstructionContent {
+                text: upcoming_maneuver
+                    .road_name()
+                    .unwrap_or_else(|| upcoming_maneuver.instruction.clone()),
+                maneuver_type,
+                maneuver_modifier,
+                roundabout_exit_degrees: None,
+                lane_info: None,
+                exit_numbers: upcoming_maneuver.exit_numbers(),
+            },
+            secondary_content: upcoming_maneuver
+                .sign
+                .as_ref()
+                .and_then(models::Sign::toward)
+                .map(|text| VisualInstructionContent {
+                    text,
+                    maneuver_type: None,
+                    maneuver_modifier: None,
+                    roundabout_exit_degrees: None,
+                    lane_info: None,
+                    exit_numbers: Vec::new(),
+                }),
+            sub_content: (!upcoming_maneuver.lanes.is_empty()).then(|| VisualInstructionContent {
+                text: String::new(),
+                maneuver_type,
+                maneuver_modifier,
+                roundabout_exit_degrees: None,
+                lane_info: Some(
+                    upcoming_maneuver
+                        .lanes
+                        .iter()
+                        .map(models::Lane::lane_info)
+                        .collect(),
+                ),
+                exit_numbers: Vec::new(),
+            }),
+            trigger_distance_before_maneuver: distance,
+        }];
+
+        Ok(RouteStep {
+            geometry,
+            distance,
+            duration: maneuver.time,
+            road_name: maneuver.road_name(),
+            exits: maneuver.exit_numbers(),
+            instruction: maneuver.instruction.clone(),
+            visual_instructions,
+            spoken_instructions: spoken_instructions(
+                maneuver,
+                next_maneuver,
+                distance,
+                maneuver.time,
+            ),
+            annotations: None,
+            incidents: Vec::new(),
+        })
+    }
+}
+
+/// Builds the spoken instructions for a step from the verbal instructions
+/// of its maneuver and the next one.
+///
+/// The step starts with the post-transition instruction of its maneuver
+/// (or the pre-transition instruction, for the start of a leg),
+/// followed by the transition alert and pre-transition instructions for the next maneuver.
+fn spoken_instructions(
+    maneuver: &Maneuver,
+    next_maneuver: Option<&Maneuver>,
+    distance: f64,
+    duration: f64,
+) -> Vec<SpokenInstruction> {
+    // Assume a constant speed along the step.
+    let speed = if duration > 0.0 {
+        distance / duration
+    } else {
+        0.0
+    };
+    let trigger_distance = |seconds: f64| distance.min(speed * seconds);
+    let pre_transition_distance = trigger_distance(PRE_TRANSITION_SECONDS);
+    let transition_alert_distance = trigger_distance(TRANSITION_ALERT_SECONDS);
+
+    let spoken_instruction = |text: &String, trigger_distance_before_maneuver| SpokenInstruction {
+        text: text.clone(),
+        ssml: None,
+        trigger_distance_before_maneuver,
+        utterance_id: Uuid::new_v4(),
+    };
+
+    let mut instructions = Vec::new();
+    let initial_instruction = if maneuver.is_start() {
+        &maneuver.verbal_pre_transition_instruction
+    } else {
+        &maneuver.verbal_post_transition_instruction
+    };
+    if let Some(text) = initial_instruction.as_ref() {
+        if pre_transition_distance < distance {
+            instructions.push(spoken_instruction(text, distance));
+        }
+    }
+    if let Some(next_maneuver) = next_maneuver {
+        if let Some(text) = &next_maneuver.verbal_transition_alert_instruction {
+            if pre_transition_distance < transition_alert_distance
+                && transition_alert_distance < distance
+            {
+                instructions.push(spoken_instruction(text, transition_alert_distance));
+            }
+        }
+        if let Some(text) = &next_maneuver.verbal_pre_transition_instruction {
+            instructions.push(spoken_instruction(text, pre_transition_distance));
+        }
+    }
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CourseOverGround, GeographicCoordinate, ManeuverModifier, ManeuverType};
+    use assert_json_diff::assert_json_include;
+    use serde_json::{from_slice, json};
+
+    #[cfg(all(feature = "std", not(feature = "web-time")))]
+    use std::time::SystemTime;
+
+    #[cfg(feature = "web-time")]
+    use web_time::SystemTime;
+    const VALHALLA_JSON_RESPONSE: &str = include_str!("fixtures/valhalla_json_response.json");
+    const VALHALLA_JSON_RESPONSE_MULTI_LEG: &str =
+        include_str!("fixtures/valhalla_json_response_multi_leg.json");
+
+    const ENDPOINT_URL: &str = "https://api.stadiamaps.com/route/v1";
+    const COSTING: &str = "bicycle";
+    const USER_LOCATION: UserLocation = UserLocation {
+        coordinates: GeographicCoordinate { lat: 0.0, lng: 0.0 },
+        horizontal_accuracy: 6.0,
+        course_over_ground: None,
+        timestamp: SystemTime::UNIX_EPOCH,
+        speed: None,
+    };
+    const USER_LOCATION_WITH_COURSE: UserLocation = UserLocation {
+        coordinates: GeographicCoordinate { lat: 0.0, lng: 0.0 },
+        horizontal_accuracy: 6.0,
+        course_over_ground: Some(CourseOverGround {
+            degrees: 42,
+            accuracy: Some(12),
+        }),
+        timestamp: SystemTime::UNIX_EPOCH,
+        speed: None,
+    };
+    const WAYPOINTS: [Waypoint; 2] = [
+        Waypoint {
+            coordinate: GeographicCoordinate { lat: 0.0, lng: 1.0 },
+            kind: WaypointKind::Break,
+        },
+        Waypoint {
+            coordinate: GeographicCoordinate { lat: 2.0, lng: 3.0 },
+            kind: WaypointKind::Break,
+        },
+    ];
+
+    #[test]
+    fn not_enough_locations() {
+        let generator = ValhallaHttpRequestGenerator::new(
+            ENDPOINT_URL.to_string(),
+            COSTING.to_string(),
+            Map::new(),
+        );
+
+        // At least two locations are required
+        assert!(matches!(
+            generator.generate_request(USER_LOCATION, Vec::new()),
+            Err(RoutingRequestGenerationError::NotEnoughWaypoints)
+        ));
+    }
+
+    fn generate_body(
+        user_location: UserLocation,
+        waypoints: Vec<Waypoint>,
+        options_json: Option<&str>,
+    ) -> JsonValue {
+        let generator = ValhallaHttpRequestGenerator::with_options_json(
+            ENDPOINT_URL.to_string(),
+            COSTING.to_string(),
+            options_json,
+        )
+        .expect("Unable to create request generator");
+
+        match generator.generate_request(user_location, waypoints) {
+            Ok(RouteRequest::HttpPost {
+                url: request_url,
+                headers,
+                body,
+            }) => {
+                assert_eq!(ENDPOINT_URL, request_url);
+                assert_eq!(headers["Content-Type"], "application/json".to_string());
+                from_slice(&body).expect("Failed to parse request body as JSON")
+            }
+            Ok(RouteRequest::HttpGet { .. }) => unreachable!(
+                "The Valhalla HTTP request generator currently only generates POST requests"
+            ),
+            Err(e) => {
+                println!("Failed to generate request: {:?}", e);
+                json!(null)
+            }
+        }
+    }
+
+    #[test]
+    fn request_body_without_course() {
+        let body_json = generate_body(USER_LOCATION, WAYPOINTS.to_vec(), None);
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "costing": COSTING,
+                "locations": [
+                    {
+                        "lat": 0.0,
+                        "lon": 0.0,
+                        "street_side_tolerance": 6,
+                    },
+                    {
+                        "lat": 0.0,
+                        "lon": 1.0
+                    },
+                    {
+                        "lat": 2.0,
+                        "lon": 3.0,
+                    }
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn request_body_with_course() {
+        let body_json = generate_body(USER_LOCATION_WITH_COURSE, WAYPOINTS.to_vec(), None);
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "costing": COSTING,
+                "locations": [
+                    {
+                        "lat": 0.0,
+                        "lon": 0.0,
+                        "street_side_tolerance": 6,
+                        "heading": 42,
+                    },
+                    {
+                        "lat": 0.0,
+                        "lon": 1.0
+                    },
+                    {
+                        "lat": 2.0,
+                        "lon": 3.0,
+                    }
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn request_body_with_json_format() {
+        let generator = ValhallaHttpRequestGenerator::new(
+            ENDPOINT_URL.to_string(),
+            COSTING.to_string(),
+            Map::new(),
+        )
+        .with_request_options(
+            (*ValhallaRequestOptionsBuilder::new()
+                .with_response_format(ValhallaResponseFormat::Json)
+                .build()
+                .expect("Options should be valid"))
+            .clone(),
+        );
+
+        let Ok(RouteRequest::HttpPost { body, .. }) =
+            generator.generate_request(USER_LOCATION, WAYPOINTS.to_vec())
+        else {
+            unreachable!(
+                "The Valhalla HTTP request generator currently only generates POST requests"
+            );
+        };
+        let body_json: JsonValue = from_slice(&body).expect("Failed to parse request body as JSON");
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "format": "json",
+                "costing": COSTING,
+            })
+        );
+        assert!(body_json["banner_instructions"].is_null());
+        assert!(body_json["voice_instructions"].is_null());
+    }
+
+    #[test]
+    fn request_body_without_costing_options() {
+        let body_json = generate_body(USER_LOCATION, WAYPOINTS.to_vec(), None);
+
+        assert!(body_json["costing_options"].is_null());
+    }
+
+    #[test]
+    #[should_panic]
+    fn request_body_invalid_costing_options() {
+        // Valid JSON, but it's not an object.
+        let body_json = generate_body(
+            USER_LOCATION,
+            WAYPOINTS.to_vec(),
+            Some(r#"["costing_options"]"#),
+        );
+
+        assert!(body_json["costing_options"].is_null());
+    }
+
+    #[test]
+    fn request_body_with_costing_options() {
+        let body_json = generate_body(
+            USER_LOCATION,
+            WAYPOINTS.to_vec(),
+            Some(r#"{"costing_options": {"bicycle": {"bicycle_type": "Road"}}}"#),
+        );
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "costing_options": {
+                    "bicycle": {
+                        "bicycle_type": "Road",
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn request_body_with_multiple_options() {
+        let body_json = generate_body(
+            USER_LOCATION,
+            WAYPOINTS.to_vec(),
+            Some(r#"{"units": "mi", "costing_options": {"bicycle": {"bicycle_type": "Road"}}}"#),
+        );
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "costing_options": {
+                    "bicycle": {
+                        "bicycle_type": "Road",
+                    },
+                },
+                "units": "mi"
+            })
+        );
+    }
+
+    fn generate_body_with_request_options(
+        user_location: UserLocation,
+        request_options: ValhallaRequestOptions,
+        options_json: Option<&str>,
+    ) -> JsonValue {
+        let generator = ValhallaHttpRequestGenerator::with_options_json(
+            ENDPOINT_URL.to_string(),
+            COSTING.to_string(),
+            options_json,
+        )
+        .expect("Unable to create request generator")
+        .with_request_options(request_options);
+
+        let Ok(RouteRequest::HttpPost { body, .. }) =
+            generator.generate_request(user_location, WAYPOINTS.to_vec())
+        else {
+            unreachable!(
+                "The Valhalla HTTP request generator currently only generates POST requests"
+            );
+        };
+        from_slice(&body).expect("Failed to parse request body as JSON")
+    }
+
+    #[test]
+    fn request_body_with_request_options() {
+        let request_options = ValhallaRequestOptionsBuilder::new()
+            .with_language("de-DE".to_string())
+            .with_units(ValhallaUnits::Miles)
+            .with_alternates(1)
+            .with_date_time(ValhallaDateTime::DepartAt {
+                value: "2025-06-01T08:30".to_string(),
+            })
+            .with_street_side_tolerance(15)
+            .with_heading_tolerance(45)
+            .build()
+            .expect("Invalid request options");
+        let body_json = generate_body_with_request_options(
+            USER_LOCATION_WITH_COURSE,
+            (*request_options).clone(),
+            None,
+        );
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "costing": COSTING,
+                "language": "de-DE",
+                "units": "miles",
+                "alternates": 1,
+                "date_time": {
+                    "type": 1,
+                    "value": "2025-06-01T08:30",
+                },
+                "locations": [
+                    {
+                        "lat": 0.0,
+                        "lon": 0.0,
+                        "street_side_tolerance": 15,
+                        "heading": 42,
+                        "heading_tolerance": 45,
+                    },
+                ],
+            })
+        );
+        assert!(body_json["directions_type"].is_null());
+    }
+
+    #[test]
+    fn request_body_without_heading() {
+        let request_options = ValhallaRequestOptionsBuilder::new()
+            .with_include_heading(false)
+            .with_heading_tolerance(45)
+            .build()
+            .expect("Invalid request options");
+        let body_json = generate_body_with_request_options(
+            USER_LOCATION_WITH_COURSE,
+            (*request_options).clone(),
+            None,
+        );
+
+        assert!(body_json["locations"][0]["heading"].is_null());
+        assert!(body_json["locations"][0]["heading_tolerance"].is_null());
+    }
+
+    #[test]
+    fn request_body_untyped_options_take_precedence() {
+        let request_options = ValhallaRequestOptionsBuilder::new()
+            .with_units(ValhallaUnits::Kilometers)
+            .build()
+            .expect("Invalid request options");
+        let body_json = generate_body_with_request_options(
+            USER_LOCATION,
+            (*request_options).clone(),
+            Some(r#"{"units": "miles"}"#),
+        );
+
+        assert_eq!(body_json["units"], "miles");
+    }
+
+    #[test]
+    fn request_body_with_invalid_horizontal_accuracy() {
+        let generator = ValhallaHttpRequestGenerator::new(
+            ENDPOINT_URL.to_string(),
+            COSTING.to_string(),
+            Map::new(),
+        );
+        let location = UserLocation {
+            coordinates: GeographicCoordinate { lat: 0.0, lng: 0.0 },
+            horizontal_accuracy: -6.0,
+            course_over_ground: None,
+            timestamp: SystemTime::now(),
+            speed: None,
+        };
+
+        let RouteRequest::HttpPost {
+            url: request_url,
+            headers,
+            body,
+        } = generator
+            .generate_request(location, WAYPOINTS.to_vec())
+            .unwrap()
+        else {
+            unreachable!(
+                "The Valhalla HTTP request generator currently only generates POST requests"
+            );
+        };
+
+        assert_eq!(ENDPOINT_URL, request_url);
+        assert_eq!(headers["Content-Type"], "application/json".to_string());
+
+        let body_json: JsonValue = from_slice(&body).expect("Failed to parse request body as JSON");
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "costing": COSTING,
+                "locations": [
+                    {
+                        "lat": 0.0,
+                        "lon": 0.0,
+                        "street_side_tolerance": 5,
+                    },
+                    {
+                        "lat": 0.0,
+                        "lon": 1.0
+                    },
+                    {
+                        "lat": 2.0,
+                        "lon": 3.0,
+                    }
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn parse_valhalla_json() {
+        let parser = ValhallaResponseParser::new();
+        let routes = parser
+            .parse_response(VALHALLA_JSON_RESPONSE.into())
+            .expect("Unable to parse Valhalla JSON response");
+        insta::assert_yaml_snapshot!(routes);
+    }
+
+    #[test]
+    fn parse_valhalla_json_steps() {
+        let parser = ValhallaResponseParser::new();
+        let routes = parser
+            .parse_response(VALHALLA_JSON_RESPONSE.into())
+            .expect("Unable to parse Valhalla JSON response");
+        let route = &routes[0];
+
+        assert_eq!(route.steps.len(), 4);
+        assert_eq!(route.distance, 835.0);
+        assert_eq!(route.steps[0].distance, 241.0);
+        for (step, next_step) in route.steps.iter().zip(route.steps.iter().skip(1)) {
+            assert_eq!(step.geometry.last(), next_step.geometry.first());
+        }
+
+        // The first step announces the turn onto 1st Avenue, including its lanes.
+        let banner = &route.steps[0].visual_instructions[0];
+        assert_eq!(banner.primary_content.text, "1st Avenue");
+        assert_eq!(
+            banner.primary_content.maneuver_type,
+            Some(ManeuverType::Turn)
+        );
+        assert_eq!(
+            banner.primary_content.maneuver_modifier,
+            Some(ManeuverModifier::Right)
+        );
+        let lanes = banner
+            .sub_content
+            .as_ref()
+            .and_then(|content| content.lane_info.as_ref())
+            .expect("Expected lane info");
+        assert_eq!(lanes.len(), 3);
+        assert_eq!(lanes[2].active_direction.as_deref(), Some("right"));
+
+        // The exit is announced with its number and destination.
+        let exit_banner = &route.steps[1].visual_instructions[0];
+        assert_eq!(exit_banner.primary_content.exit_numbers, vec!["165A"]);
+        assert_eq!(
+            exit_banner
+                .secondary_content
+                .as_ref()
+                .map(|content| content.text.as_str()),
+            Some("Aurora Avenue")
+        );
+        assert_eq!(route.steps[2].exits, vec!["165A"]);
+
+        // Spoken instructions are ordered by decreasing distance to the maneuver.
+        let spoken_instructions = &route.steps[0].spoken_instructions;
+        assert_eq!(
+            spoken_instructions
+                .iter()
+                .map(|instruction| instruction.text.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Drive northwest on Pike Street. Then Turn right onto 1st Avenue.",
+                "Turn right onto 1st Avenue.",
+                "Turn right onto 1st Avenue.",
+            ]
+        );
+        assert!(spoken_instructions.windows(2).all(|pair| {
+            pair[0].trigger_distance_before_maneuver > pair[1].trigger_distance_before_maneuver
+        }));
+        assert_eq!(route.steps[3].spoken_instructions, Vec::new());
+    }
+
+    #[test]
+    fn parse_valhalla_json_without_narrative() {
+        // Valhalla omits instructions when only maneuvers are requested.
+        let mut response: JsonValue =
+            serde_json::from_str(VALHALLA_JSON_RESPONSE).expect("Invalid fixture");
+        for maneuver in response["trip"]["legs"][0]["maneuvers"]
+            .as_array_mut()
+            .expect("Expected maneuvers")
+        {
+            let maneuver = maneuver
+                .as_object_mut()
+                .expect("Expected a maneuver object");
+            maneuver.retain(|key, _| !key.contains("instruction"));
+        }
+
+        let parser = ValhallaResponseParser::new();
+        let routes = parser
+            .parse_response(serde_json::to_vec(&response).unwrap())
+            .expect("Unable to parse Valhalla JSON response");
+        let route = &routes[0];
+
+        assert_eq!(route.steps.len(), 4);
+        assert!(route.steps.iter().all(|step| step.instruction.is_empty()));
+        assert!(route
+            .steps
+            .iter()
+            .all(|step| step.spoken_instructions.is_empty()));
+        assert_eq!(
+            route.steps[0].visual_instructions[0].primary_content.text,
+            "1st Avenue"
+        );
+    }
+
+    #[test]
+    fn parse_valhalla_json_multi_leg() {
+        let parser = ValhallaResponseParser::new();
+        let routes = parser
+            .parse_response(VALHALLA_JSON_RESPONSE_MULTI_LEG.into())
+            .expect("Unable to parse Valhalla JSON response");
+        let route = &routes[0];
+
+        // The shared point between the legs is only included once.
+        assert_eq!(route.geometry.len(), 4);
+        assert_eq!(route.waypoints.len(), 3);
+        assert_eq!(route.steps.len(), 4);
+        assert_eq!(route.steps[2].geometry.first(), route.geometry.get(2));
+        assert_eq!(route.steps[3].geometry.last(), route.geometry.last());
+        // Lengths are given in miles.
+        assert_eq!(route.steps[0].distance, 0.15 * 1_609.344);
+    }
+
+    #[test]
+    fn parse_valhalla_json_error() {
+        let parser = ValhallaResponseParser::new();
+        let result = parser.parse_response(
+            r#"{"error_code": 171, "error": "No suitable edges near location", "status_code": 400, "status": "Bad Request"}"#
+                .into(),
+        );
+
+        let Err(ParsingError::InvalidStatusCode { code, description }) = result else {
+            panic!("Expected an InvalidStatusCode error");
+        };
+        assert_eq!(code, "171");
+        assert_eq!(
+            description.as_deref(),
+            Some("No suitable edges near location")
+        );
+    }
+
+    #[test]
+    fn request_body_with_pbf_format() {
+        let generator = ValhallaHttpRequestGenerator::new(
+            ENDPOINT_URL.to_string(),
+            COSTING.to_string(),
+            Map::new(),
+        )
+        .with_request_options(
+            (*ValhallaRequestOptionsBuilder::new()
+                .with_response_format(ValhallaResponseFormat::Pbf)
+                .build()
+                .expect("Options should be valid"))
+            .clone(),
+        );
+
+        let Ok(RouteRequest::HttpPost { body, .. }) =
+            generator.generate_request(USER_LOCATION, WAYPOINTS.to_vec())
+        else {
+            unreachable!(
+                "The Valhalla HTTP request generator currently only generates POST requests"
+            );
+        };
+        let body_json: JsonValue = from_slice(&body).expect("Failed to parse request body as JSON");
+
+        assert_json_include!(
+            actual: body_json,
+            expected: json!({
+                "format": "pbf",
+                "pbf_field_selector": {
+                    "options": true,
+                    "directions": true,
+                },
+                "costing": COSTING,
+            })
+        );
+    }
+
+    #[cfg(not(feature = "valhalla-pbf"))]
+    #[test]
+    fn pbf_format_requires_feature() {
+        assert!(matches!(
+            ValhallaResponseFormat::Pbf.response_parser(),
+            Err(InstantiationError::UnsupportedResponseFormat { .. })
+        ));
+        assert!(matches!(
+            crate::routing_adapters::RouteAdapter::new_valhalla_http_with_options(
+                ENDPOINT_URL.to_string(),
+                COSTING.to_string(),
+                ValhallaRequestOptionsBuilder::new()
+                    .with_response_format(ValhallaResponseFormat::Pbf)
+                    .build()
+                    .expect("Options should be valid"),
+                None,
+            ),
+            Err(InstantiationError::UnsupportedResponseFormat { .. })
+        ));
+    }
+
+    /// Encodes a response in the native JSON format as the equivalent protobuf.
+    #[cfg(feature = "valhalla-pbf")]
+    fn encode_as_pbf(json_response: &str) -> Vec<u8> {
+        use prost::Message;
+        use proto::{
+            Api, Directions, DirectionsLeg, DirectionsLocation, DirectionsManeuver,
+            DirectionsRoute, LatLng, Options, StreetName, TripSign, TripSignElement,
+        };
+
+        let response: JsonValue =
+            serde_json::from_str(json_response).expect("Invalid JSON response");
+        let trip = &response["trip"];
+        let string = |value: &JsonValue| value.as_str().unwrap_or_default().to_string();
+        let index = |value: &JsonValue| value.as_u64().expect("Invalid index") as u32;
+        let sign_elements = |value: &JsonValue| {
+            value
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|element| TripSignElement {
+                    text: string(&element["text"]),
+                })
+                .collect()
+        };
+        let locations = trip["locations"].as_array().expect("Missing locations");
+
+        let legs = trip["legs"]
+            .as_array()
+            .expect("Missing legs")
+            .iter()
+            .enumerate()
+            .map(|(leg_index, leg)| DirectionsLeg {
+                location: locations[leg_index..=leg_index + 1]
+                    .iter()
+                    .map(|location| DirectionsLocation {
+                        ll: Some(LatLng {
+                            lat: location["lat"].as_f64(),
+                            lng: location["lon"].as_f64(),
+                        }),
+                        kind: 0,
+                    })
+                    .collect(),
+                maneuver: leg["maneuvers"]
+                    .as_array()
+                    .expect("Missing maneuvers")
+                    .iter()
+                    .map(|maneuver| DirectionsManeuver {
+                        kind: maneuver["type"].as_i64().expect("Missing type") as i32,
+                        text_instruction: string(&maneuver["instruction"]),
+                        street_name: maneuver["street_names"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .map(|name| StreetName {
+                                value: string(name),
+                            })
+                            .collect(),
+                        length: maneuver["length"].as_f64().expect("Missing length") as f32,
+                        time: maneuver["time"].as_f64().expect("Missing time"),
+                        begin_shape_index: index(&maneuver["begin_shape_index"]),
+                        end_shape_index: index(&maneuver["end_shape_index"]),
+                        verbal_transition_alert_instruction: string(
+                            &maneuver["verbal_transition_alert_instruction"],
+                        ),
+                        verbal_pre_transition_instruction: string(
+                            &maneuver["verbal_pre_transition_instruction"],
+                        ),
+                        verbal_post_transition_instruction: string(
+                            &maneuver["verbal_post_transition_instruction"],
+                        ),
+                        sign: maneuver.get("sign").map(|sign| TripSign {
+                            exit_numbers: sign_elements(&sign["exit_number_elements"]),
+                            exit_toward_locations: sign_elements(&sign["exit_toward_elements"]),
+                        }),
+                    })
+                    .collect(),
+                shape: string(&leg["shape"]),
+            })
+            .collect();
+
+        Api {
+            options: Some(Options {
+                units: Some(i32::from(trip["units"] == "miles")),
+            }),
+            directions: Some(Directions {
+                routes: vec![DirectionsRoute { legs }],
+            }),
+            info: None,
+        }
+        .encode_to_vec()
+    }
+
+    #[cfg(feature = "valhalla-pbf")]
+    #[test]
+    fn parse_valhalla_pbf_matches_json() {
+        for response in [VALHALLA_JSON_RESPONSE, VALHALLA_JSON_RESPONSE_MULTI_LEG] {
+            let json_routes = ValhallaResponseParser::new()
+                .parse_response(response.into())
+                .expect("Unable to parse Valhalla JSON response");
+            let pbf_routes = ValhallaPbfResponseParser::new()
+                .parse_response(encode_as_pbf(response))
+                .expect("Unable to parse Valhalla PBF response");
+
+            assert_eq!(pbf_routes.len(), json_routes.len());
+            for (pbf_route, json_route) in pbf_routes.iter().zip(&json_routes) {
+                assert_eq!(pbf_route.geometry, json_route.geometry);
+                assert_eq!(pbf_route.bbox, json_route.bbox);
+                assert_eq!(pbf_route.waypoints, json_route.waypoints);
+                // Lengths are single precision floats in the protobuf format.
+                assert!((pbf_route.distance - json_route.distance).abs() < 0.01);
+                assert_eq!(pbf_route.steps.len(), json_route.steps.len());
+
+                for (pbf_step, json_step) in pbf_route.steps.iter().zip(&json_route.steps) {
+                    assert_eq!(pbf_step.geometry, json_step.geometry);
+                    assert!((pbf_step.distance - json_step.distance).abs() < 0.01);
+                    assert_eq!(pbf_step.duration, json_step.duration);
+                    assert_eq!(pbf_step.road_name, json_step.road_name);
+                    assert_eq!(pbf_step.exits, json_step.exits);
+                    assert_eq!(pbf_step.instruction, json_step.instruction);
+                    assert_eq!(
+                        pbf_step.visual_instructions[0].primary_content,
+                        json_step.visual_instructions[0].primary_content
+                    );
+                    assert_eq!(
+                        pbf_step.visual_instructions[0].secondary_content,
+                        json_step.visual_instructions[0].secondary_content
+                    );
+                    assert_eq!(
+                        pbf_step
+                            .spoken_instructions
+                            .iter()
+                            .map(|instruction| &instruction.text)
+                            .collect::<Vec<_>>(),
+                        json_step
+                            .spoken_instructions
+                            .iter()
+                            .map(|instruction| &instruction.text)
+                            .collect::<Vec<_>>()
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "valhalla-pbf")]
+    #[test]
+    fn parse_valhalla_pbf_error() {
+        use prost::Message;
+        use proto::{Api, CodedDescription, Info};
+
+        let response = Api {
+            options: None,
+            directions: None,
+            info: Some(Info {
+                errors: vec![CodedDescription {
+                    description: "No suitable edges near location".to_string(),
+                    code: 171,
+                }],
+            }),
+        }
+        .encode_to_vec();
+
+        let Err(ParsingError::InvalidStatusCode { code, description }) =
+            ValhallaPbfResponseParser::new().parse_response(response)
+        else {
+            panic!("Expected an InvalidStatusCode error");
+        };
+        assert_eq!(code, "171");
+        assert_eq!(
+            description.as_deref(),
+            Some("No suitable edges near location")
+        );
+    }
+
+    #[cfg(feature = "valhalla-pbf")]
+    #[test]
+    fn parse_valhalla_pbf_json_error() {
+        let result = ValhallaPbfResponseParser::new().parse_response(
+            r#"{"error_code": 154, "error": "Path distance exceeds the max distance limit", "status_code": 400, "status": "Bad Request"}"#
+                .into(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(ParsingError::InvalidStatusCode { code, .. }) if code == "154"
+        ));
+    }
+}
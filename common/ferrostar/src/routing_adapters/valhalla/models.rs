@@ -0,0 +1,347 @@
+//! Valhalla models from the native JSON
+//! [API spec](https://valhalla.github.io/valhalla/api/turn-by-turn/api-reference/#outputs-of-a-route).
+//!
+//! We omit some fields which are not needed for navigation.
+
+use crate::models::{LaneInfo, ManeuverModifier, ManeuverType, WaypointKind};
+#[cfg(feature = "alloc")]
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct RouteResponse {
+    /// The route, if one was found.
+    pub trip: Option<Trip>,
+    /// Alternative routes, if any were requested.
+    #[serde(default)]
+    pub alternates: Vec<Alternate>,
+    /// A Valhalla-specific code for the error, if the request failed.
+    pub error_code: Option<u32>,
+    /// A description of the error, if the request failed.
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Alternate {
+    pub trip: Trip,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Trip {
+    pub locations: Vec<Location>,
+    pub legs: Vec<Leg>,
+    /// The unit of distance used for all lengths in the trip.
+    #[serde(default)]
+    pub units: Units,
+}
+
+#[derive(Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    #[default]
+    #[serde(alias = "km")]
+    Kilometers,
+    #[serde(alias = "mi")]
+    Miles,
+}
+
+impl Units {
+    /// The number of meters in one unit of distance.
+    pub fn meters(self) -> f64 {
+        match self {
+            Units::Kilometers => 1_000.0,
+            Units::Miles => 1_609.344,
+        }
+    }
+}
+
+/// A location on the route, as given in the request.
+#[derive(Deserialize, Debug)]
+pub struct Location {
+    pub lat: f64,
+    pub lon: f64,
+    /// The type of location (ex: `break` or `via`).
+    #[serde(default, rename = "type")]
+    pub kind: Option<String>,
+}
+
+impl Location {
+    pub fn waypoint_kind(&self) -> WaypointKind {
+        match self.kind.as_deref() {
+            Some("via" | "through") => WaypointKind::Via,
+            _ => WaypointKind::Break,
+        }
+    }
+}
+
+/// The part of a trip between two break locations.
+#[derive(Deserialize, Debug)]
+pub struct Leg {
+    pub maneuvers: Vec<Maneuver>,
+    /// The geometry of the leg, encoded as a polyline with a precision of 6 digits.
+    pub shape: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Maneuver {
+    /// A code for the type of maneuver (see [`Maneuver::maneuver`]).
+    #[serde(rename = "type")]
+    pub kind: u8,
+    /// A description of the maneuver (ex: "Turn right onto Main Street.").
+    ///
+    /// This is empty if narrative instructions were not requested
+    /// (ex: with the `maneuvers` directions type).
+    #[serde(default)]
+    pub instruction: String,
+    /// An instruction to be spoken well before the maneuver.
+    pub verbal_transition_alert_instruction: Option<String>,
+    /// An instruction to be spoken just before the maneuver.
+    pub verbal_pre_transition_instruction: Option<String>,
+    /// An instruction to be spoken after the maneuver.
+    pub verbal_post_transition_instruction: Option<String>,
+    /// The names of the street after the maneuver.
+    #[serde(default)]
+    pub street_names: Vec<String>,
+    /// The estimated travel time after the maneuver, in seconds.
+    pub time: f64,
+    /// The distance to travel after the maneuver, in the [`Units`] of the trip.
+    pub length: f64,
+    /// The index of the first point of the maneuver in the leg shape.
+    pub begin_shape_index: usize,
+    /// The index of the last point of the maneuver in the leg shape.
+    pub end_shape_index: usize,
+    pub sign: Option<Sign>,
+    #[serde(default)]
+    pub lanes: Vec<Lane>,
+}
+
+impl Maneuver {
+    /// Maps the Valhalla maneuver type to a standard maneuver type and modifier,
+    /// following the conventions of the Valhalla OSRM serializer.
+    ///
+    /// Types which don't have a sensible mapping (ex: public transit types) have no maneuver type.
+    pub fn maneuver(&self) -> (Option<ManeuverType>, Option<ManeuverModifier>) {
+        match self.kind {
+            1 => (Some(ManeuverType::Depart), None),
+            2 => (Some(ManeuverType::Depart), Some(ManeuverModifier::Right)),
+            3 => (Some(ManeuverType::Depart), Some(ManeuverModifier::Left)),
+            4 => (Some(ManeuverType::Arrive), None),
+            5 => (Some(ManeuverType::Arrive), Some(ManeuverModifier::Right)),
+            6 => (Some(ManeuverType::Arrive), Some(ManeuverModifier::Left)),
+            7 => (
+                Some(ManeuverType::NewName),
+                Some(ManeuverModifier::Straight),
+            ),
+            8 => (
+                Some(ManeuverType::Continue),
+                Some(ManeuverModifier::Straight),
+            ),
+            9 => (
+                Some(ManeuverType::Turn),
+                Some(ManeuverModifier::SlightRight),
+            ),
+            10 => (Some(ManeuverType::Turn), Some(ManeuverModifier::Right)),
+            11 => (Some(ManeuverType::Turn), Some(ManeuverModifier::SharpRight)),
+            12 | 13 => (Some(ManeuverType::Turn), Some(ManeuverModifier::UTurn)),
+            14 => (Some(ManeuverType::Turn), Some(ManeuverModifier::SharpLeft)),
+            15 => (Some(ManeuverType::Turn), Some(ManeuverModifier::Left)),
+            16 => (Some(ManeuverType::Turn), Some(ManeuverModifier::SlightLeft)),
+            17 => (Some(ManeuverType::OnRamp), Some(ManeuverModifier::Straight)),
+            18 => (Some(ManeuverType::OnRamp), Some(ManeuverModifier::Right)),
+            19 => (Some(ManeuverType::OnRamp), Some(ManeuverModifier::Left)),
+            20 => (Some(ManeuverType::OffRamp), Some(ManeuverModifier::Right)),
+            21 => (Some(ManeuverType::OffRamp), Some(ManeuverModifier::Left)),
+            22 => (Some(ManeuverType::Fork), Some(ManeuverModifier::Straight)),
+            23 => (
+                Some(ManeuverType::Fork),
+                Some(ManeuverModifier::SlightRight),
+            ),
+            24 => (Some(ManeuverType::Fork), Some(ManeuverModifier::SlightLeft)),
+            25 => (Some(ManeuverType::Merge), Some(ManeuverModifier::Straight)),
+            26 => (Some(ManeuverType::Roundabout), None),
+            27 => (Some(ManeuverType::ExitRoundabout), None),
+            // Entering or exiting a ferry
+            28 | 29 => (Some(ManeuverType::Notification), None),
+            37 => (
+                Some(ManeuverType::Merge),
+                Some(ManeuverModifier::SlightRight),
+            ),
+            38 => (
+                Some(ManeuverType::Merge),
+                Some(ManeuverModifier::SlightLeft),
+            ),
+            _ => (None, None),
+        }
+    }
+
+    /// Whether this maneuver is the departure from a break location (the start of a leg).
+    pub fn is_start(&self) -> bool {
+        matches!(self.kind, 1..=3)
+    }
+
+    /// The name of the road after the maneuver.
+    pub fn road_name(&self) -> Option<String> {
+        if self.street_names.is_empty() {
+            None
+        } else {
+            Some(self.street_names.join("/"))
+        }
+    }
+
+    /// The exit numbers on the sign for this maneuver (if any).
+    pub fn exit_numbers(&self) -> Vec<String> {
+        self.sign
+            .as_ref()
+            .map(|sign| Sign::texts(&sign.exit_number_elements))
+            .unwrap_or_default()
+    }
+}
+
+/// The text on a guide sign (ex: at a highway exit).
+#[derive(Deserialize, Debug)]
+pub struct Sign {
+    #[serde(default)]
+    pub exit_number_elements: Vec<SignElement>,
+    #[serde(default)]
+    pub exit_toward_elements: Vec<SignElement>,
+}
+
+impl Sign {
+    fn texts(elements: &[SignElement]) -> Vec<String> {
+        elements
+            .iter()
+            .map(|element| element.text.clone())
+            .collect()
+    }
+
+    /// The destinations the maneuver leads toward, if any (ex: "Downtown/Airport").
+    pub fn toward(&self) -> Option<String> {
+        if self.exit_toward_elements.is_empty() {
+            None
+        } else {
+            Some(Self::texts(&self.exit_toward_elements).join("/"))
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SignElement {
+    pub text: String,
+}
+
+/// A lane at the maneuver.
+///
+/// Directions are bit masks of the turn lane directions
+/// (ex: `2` for through and `8` for left).
+#[derive(Deserialize, Debug)]
+pub struct Lane {
+    /// All the directions which are marked on the lane.
+    pub directions: u16,
+    /// The directions which may be used to follow the route, if any.
+    pub valid: Option<u16>,
+    /// The preferred direction for following the route, if any.
+    pub active: Option<u16>,
+}
+
+impl Lane {
+    /// Names of the turn lane bits, in the same format as OSRM lane indications.
+    const DIRECTIONS: [(u16, &'static str); 9] = [
+        (1, "none"),
+        (2, "straight"),
+        (4, "sharp left"),
+        (8, "left"),
+        (16, "slight left"),
+        (32, "slight right"),
+        (64, "right"),
+        (128, "sharp right"),
+        (256, "uturn"),
+    ];
+
+    fn direction_names(mask: u16) -> impl Iterator<Item = &'static str> {
+        Self::DIRECTIONS
+            .iter()
+            .filter(move |(bit, _)| mask & bit != 0)
+            .map(|(_, name)| *name)
+    }
+
+    pub fn lane_info(&self) -> LaneInfo {
+        let used_direction = self.active.or(self.valid);
+        LaneInfo {
+            active: used_direction.is_some(),
+            directions: Self::direction_names(self.directions)
+                .map(ToString::to_string)
+                .collect(),
+            active_direction: used_direction
+                .and_then(|mask| Self::direction_names(mask).next())
+                .map(ToString::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_error_response() {
+        let response: RouteResponse = serde_json::from_str(
+            r#"{"error_code": 171, "error": "No suitable edges near location", "status_code": 400, "status": "Bad Request"}"#,
+        )
+        .expect("Unable to deserialize Valhalla error response");
+
+        assert!(response.trip.is_none());
+        assert_eq!(response.error_code, Some(171));
+        assert_eq!(
+            response.error.as_deref(),
+            Some("No suitable edges near location")
+        );
+    }
+
+    #[test]
+    fn lane_info() {
+        let lanes: Vec<Lane> = serde_json::from_str(
+            r#"[{"directions": 10, "valid": 8}, {"directions": 2}, {"directions": 96, "valid": 96, "active": 64}]"#,
+        )
+        .expect("Unable to deserialize lanes");
+
+        assert_eq!(
+            lanes.iter().map(Lane::lane_info).collect::<Vec<_>>(),
+            vec![
+                LaneInfo {
+                    active: true,
+                    directions: vec!["straight".to_string(), "left".to_string()],
+                    active_direction: Some("left".to_string()),
+                },
+                LaneInfo {
+                    active: false,
+                    directions: vec!["straight".to_string()],
+                    active_direction: None,
+                },
+                LaneInfo {
+                    active: true,
+                    directions: vec!["slight right".to_string(), "right".to_string()],
+                    active_direction: Some("right".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn location_waypoint_kind() {
+        let locations: Vec<Location> = serde_json::from_str(
+            r#"[{"lat": 0.0, "lon": 0.0, "type": "break"}, {"lat": 0.0, "lon": 0.0, "type": "through"}, {"lat": 0.0, "lon": 0.0}]"#,
+        )
+        .expect("Unable to deserialize locations");
+
+        assert_eq!(
+            locations
+                .iter()
+                .map(Location::waypoint_kind)
+                .collect::<Vec<_>>(),
+            vec![WaypointKind::Break, WaypointKind::Via, WaypointKind::Break]
+        );
+    }
+}
@@ -0,0 +1,520 @@
+//! Typed options for Valhalla route requests.
+
+use super::ValhallaResponseFormat;
+use crate::routing_adapters::error::InstantiationError;
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String, sync::Arc};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+#[cfg(feature = "wasm-bindgen")]
+use tsify::Tsify;
+#[cfg(feature = "wasm-bindgen")]
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+/// The maximum heading tolerance (in degrees) which is meaningful to Valhalla.
+const MAX_HEADING_TOLERANCE: u16 = 180;
+
+/// The units of distance used in Valhalla responses (including narrative instructions).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(from_wasm_abi))]
+#[serde(rename_all = "lowercase")]
+pub enum ValhallaUnits {
+    Kilometers,
+    Miles,
+}
+
+/// The level of detail of the directions in Valhalla responses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(from_wasm_abi))]
+#[serde(rename_all = "lowercase")]
+pub enum ValhallaDirectionsType {
+    /// No maneuvers or instructions; only the shape of the route.
+    ///
+    /// NOTE: Routes without maneuvers cannot be used for navigation,
+    /// so [`ValhallaRequestOptionsBuilder::build`] rejects this.
+    None,
+    /// Maneuvers without narrative instructions.
+    Maneuvers,
+    /// Maneuvers with narrative instructions (the default).
+    Instructions,
+}
+
+/// The date and time to use for time-dependent routing.
+///
+/// Values are local times at the relevant location, formatted as `YYYY-MM-DDThh:mm`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(from_wasm_abi))]
+pub enum ValhallaDateTime {
+    /// Depart now.
+    Current,
+    /// Depart from the origin at the given time.
+    DepartAt { value: String },
+    /// Arrive at the destination by the given time.
+    ArriveBy { value: String },
+    /// Use the given time for every location along the route,
+    /// regardless of how long it takes to get there.
+    Invariant { value: String },
+}
+
+impl ValhallaDateTime {
+    fn validate(&self) -> Result<(), InstantiationError> {
+        match self {
+            ValhallaDateTime::Current => Ok(()),
+            ValhallaDateTime::DepartAt { value }
+            | ValhallaDateTime::ArriveBy { value }
+            | ValhallaDateTime::Invariant { value } => {
+                NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+                    .map(|_| ())
+                    .map_err(|_| InstantiationError::InvalidOptions {
+                        error: format!("{value} is not a date time in the format YYYY-MM-DDThh:mm"),
+                    })
+            }
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        match self {
+            ValhallaDateTime::Current => json!({ "type": 0 }),
+            ValhallaDateTime::DepartAt { value } => json!({ "type": 1, "value": value }),
+            ValhallaDateTime::ArriveBy { value } => json!({ "type": 2, "value": value }),
+            ValhallaDateTime::Invariant { value } => json!({ "type": 3, "value": value }),
+        }
+    }
+}
+
+/// Validated options for Valhalla route requests.
+///
+/// These are created with a [`ValhallaRequestOptionsBuilder`],
+/// which checks the values before they are ever sent to a server.
+/// Untyped options (ex: `costing_options`) passed to the request generator
+/// take precedence over these.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct ValhallaRequestOptions {
+    response_format: ValhallaResponseFormat,
+    language: Option<String>,
+    units: Option<ValhallaUnits>,
+    directions_type: Option<ValhallaDirectionsType>,
+    alternates: Option<u32>,
+    date_time: Option<ValhallaDateTime>,
+    street_side_tolerance: Option<u16>,
+    heading_tolerance: Option<u16>,
+    include_heading: bool,
+}
+
+impl Default for ValhallaRequestOptions {
+    fn default() -> Self {
+        Self {
+            response_format: ValhallaResponseFormat::default(),
+            language: None,
+            units: None,
+            directions_type: None,
+            alternates: None,
+            date_time: None,
+            street_side_tolerance: None,
+            heading_tolerance: None,
+            include_heading: true,
+        }
+    }
+}
+
+impl ValhallaRequestOptions {
+    /// The format of the responses to request.
+    pub(crate) fn response_format(&self) -> ValhallaResponseFormat {
+        self.response_format
+    }
+
+    /// The street side tolerance (in meters) for the user's location.
+    ///
+    /// Unless it is set explicitly, this is the horizontal accuracy of the location,
+    /// with a minimum of 5 meters.
+    pub(crate) fn street_side_tolerance(&self, horizontal_accuracy: f64) -> u16 {
+        self.street_side_tolerance
+            .unwrap_or_else(|| core::cmp::max(5, horizontal_accuracy as u16))
+    }
+
+    /// The heading tolerance (in degrees) for the user's course, if set.
+    pub(crate) fn heading_tolerance(&self) -> Option<u16> {
+        self.heading_tolerance
+    }
+
+    /// Whether to send the user's course as the heading of the start of the route.
+    pub(crate) fn include_heading(&self) -> bool {
+        self.include_heading
+    }
+
+    /// Adds the top-level request parameters to the request JSON.
+    pub(crate) fn apply_to(&self, args: &mut JsonValue) {
+        if let Some(language) = &self.language {
+            args["language"] = language.clone().into();
+        }
+        if let Some(units) = self.units {
+            args["units"] = json!(units);
+        }
+        if let Some(directions_type) = self.directions_type {
+            args["directions_type"] = json!(directions_type);
+        }
+        if let Some(alternates) = self.alternates {
+            args["alternates"] = alternates.into();
+        }
+        if let Some(date_time) = &self.date_time {
+            args["date_time"] = date_time.to_json();
+        }
+    }
+}
+
+/// A builder for [`ValhallaRequestOptions`].
+///
+/// All options are unset by default, so the server defaults are used,
+/// except that OSRM-compatible responses are requested
+/// and the user's course is sent as the heading of the start of the route.
+///
+/// # Examples
+///
+/// ```
+/// # use ferrostar::routing_adapters::valhalla::{ValhallaRequestOptionsBuilder, ValhallaUnits};
+/// let options = ValhallaRequestOptionsBuilder::new()
+///     .with_language("de-DE".to_string())
+///     .with_units(ValhallaUnits::Kilometers)
+///     .with_heading_tolerance(45)
+///     .build()
+///     .expect("Invalid options");
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct ValhallaRequestOptionsBuilder {
+    options: ValhallaRequestOptions,
+}
+
+impl ValhallaRequestOptionsBuilder {
+    /// Returns a copy of the builder with the options updated.
+    fn with(self: Arc<Self>, update: impl FnOnce(&mut ValhallaRequestOptions)) -> Arc<Self> {
+        let mut builder = Arc::unwrap_or_clone(self);
+        update(&mut builder.options);
+        Arc::new(builder)
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl ValhallaRequestOptionsBuilder {
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Sets the format of the responses to request (OSRM-compatible JSON by default).
+    pub fn with_response_format(
+        self: Arc<Self>,
+        response_format: ValhallaResponseFormat,
+    ) -> Arc<Self> {
+        self.with(|options| options.response_format = response_format)
+    }
+
+    /// Sets the language of the narrative instructions, as an IETF BCP 47 language tag (ex: `en-US`).
+    pub fn with_language(self: Arc<Self>, language: String) -> Arc<Self> {
+        self.with(|options| options.language = Some(language))
+    }
+
+    /// Sets the units of distance used in the response (including narrative instructions).
+    pub fn with_units(self: Arc<Self>, units: ValhallaUnits) -> Arc<Self> {
+        self.with(|options| options.units = Some(units))
+    }
+
+    /// Sets the level of detail of the directions.
+    pub fn with_directions_type(
+        self: Arc<Self>,
+        directions_type: ValhallaDirectionsType,
+    ) -> Arc<Self> {
+        self.with(|options| options.directions_type = Some(directions_type))
+    }
+
+    /// Sets the number of alternate routes to request.
+    ///
+    /// The server may return fewer alternates (or none).
+    pub fn with_alternates(self: Arc<Self>, alternates: u32) -> Arc<Self> {
+        self.with(|options| options.alternates = Some(alternates))
+    }
+
+    /// Sets the date and time to use for time-dependent routing.
+    pub fn with_date_time(self: Arc<Self>, date_time: ValhallaDateTime) -> Arc<Self> {
+        self.with(|options| options.date_time = Some(date_time))
+    }
+
+    /// Sets a fixed street side tolerance (in meters) for the user's location.
+    ///
+    /// If the user is within this distance of a street,
+    /// they are assumed to be on it rather than to one side of it.
+    /// By default, this is the horizontal accuracy of the location, with a minimum of 5 meters.
+    pub fn with_street_side_tolerance(self: Arc<Self>, street_side_tolerance: u16) -> Arc<Self> {
+        self.with(|options| options.street_side_tolerance = Some(street_side_tolerance))
+    }
+
+    /// Sets how far (in degrees) the heading of the start of the route may differ
+    /// from the user's course.
+    pub fn with_heading_tolerance(self: Arc<Self>, heading_tolerance: u16) -> Arc<Self> {
+        self.with(|options| options.heading_tolerance = Some(heading_tolerance))
+    }
+
+    /// Sets whether to send the user's course (if known) as the heading of the start of the route.
+    pub fn with_include_heading(self: Arc<Self>, include_heading: bool) -> Arc<Self> {
+        self.with(|options| options.include_heading = include_heading)
+    }
+
+    /// Validates the options.
+    pub fn build(&self) -> Result<Arc<ValhallaRequestOptions>, InstantiationError> {
+        let options = &self.options;
+        if options.directions_type == Some(ValhallaDirectionsType::None) {
+            return Err(InstantiationError::InvalidOptions {
+                error: String::from("Routes without maneuvers cannot be used for navigation"),
+            });
+        }
+        if let Some(language) = &options.language {
+            if !is_language_tag(language) {
+                return Err(InstantiationError::InvalidOptions {
+                    error: format!("{language} is not a valid language tag"),
+                });
+            }
+        }
+        if let Some(date_time) = &options.date_time {
+            date_time.validate()?;
+        }
+        if let Some(heading_tolerance) = options.heading_tolerance {
+            if heading_tolerance > MAX_HEADING_TOLERANCE {
+                return Err(InstantiationError::InvalidOptions {
+                    error: format!(
+                        "The heading tolerance must be at most {MAX_HEADING_TOLERANCE} degrees"
+                    ),
+                });
+            }
+        }
+        Ok(Arc::new(options.clone()))
+    }
+}
+
+/// Checks the basic structure of an IETF BCP 47 language tag:
+/// a 2-3 letter language code, optionally followed by subtags of 1-8 letters or digits.
+fn is_language_tag(language: &str) -> bool {
+    let mut subtags = language.split('-');
+    let is_language_code = subtags.next().is_some_and(|code| {
+        (2..=3).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphabetic())
+    });
+    is_language_code
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// JavaScript wrapper for `ValhallaRequestOptions`.
+#[cfg(feature = "wasm-bindgen")]
+#[wasm_bindgen(js_name = ValhallaRequestOptions)]
+pub struct JsValhallaRequestOptions(pub(crate) Arc<ValhallaRequestOptions>);
+
+/// JavaScript wrapper for `ValhallaRequestOptionsBuilder`.
+#[cfg(feature = "wasm-bindgen")]
+#[wasm_bindgen(js_name = ValhallaRequestOptionsBuilder)]
+pub struct JsValhallaRequestOptionsBuilder(Arc<ValhallaRequestOptionsBuilder>);
+
+#[cfg(feature = "wasm-bindgen")]
+#[wasm_bindgen(js_class = ValhallaRequestOptionsBuilder)]
+impl JsValhallaRequestOptionsBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(ValhallaRequestOptionsBuilder::new())
+    }
+
+    #[wasm_bindgen(js_name = withResponseFormat)]
+    pub fn with_response_format(
+        self,
+        response_format: ValhallaResponseFormat,
+    ) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_response_format(response_format))
+    }
+
+    #[wasm_bindgen(js_name = withLanguage)]
+    pub fn with_language(self, language: String) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_language(language))
+    }
+
+    #[wasm_bindgen(js_name = withUnits)]
+    pub fn with_units(self, units: ValhallaUnits) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_units(units))
+    }
+
+    #[wasm_bindgen(js_name = withDirectionsType)]
+    pub fn with_directions_type(
+        self,
+        directions_type: ValhallaDirectionsType,
+    ) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_directions_type(directions_type))
+    }
+
+    #[wasm_bindgen(js_name = withAlternates)]
+    pub fn with_alternates(self, alternates: u32) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_alternates(alternates))
+    }
+
+    #[wasm_bindgen(js_name = withDateTime)]
+    pub fn with_date_time(self, date_time: ValhallaDateTime) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_date_time(date_time))
+    }
+
+    #[wasm_bindgen(js_name = withStreetSideTolerance)]
+    pub fn with_street_side_tolerance(
+        self,
+        street_side_tolerance: u16,
+    ) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_street_side_tolerance(street_side_tolerance))
+    }
+
+    #[wasm_bindgen(js_name = withHeadingTolerance)]
+    pub fn with_heading_tolerance(self, heading_tolerance: u16) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_heading_tolerance(heading_tolerance))
+    }
+
+    #[wasm_bindgen(js_name = withIncludeHeading)]
+    pub fn with_include_heading(self, include_heading: bool) -> JsValhallaRequestOptionsBuilder {
+        JsValhallaRequestOptionsBuilder(self.0.with_include_heading(include_heading))
+    }
+
+    pub fn build(&self) -> Result<JsValhallaRequestOptions, JsValue> {
+        self.0
+            .build()
+            .map(JsValhallaRequestOptions)
+            .map_err(|e| JsValue::from_str(&format!("{e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_default_options() {
+        let options = ValhallaRequestOptionsBuilder::new()
+            .build()
+            .expect("Default options should be valid");
+
+        assert_eq!(*options, ValhallaRequestOptions::default());
+        assert_eq!(options.response_format(), ValhallaResponseFormat::Osrm);
+        assert!(options.include_heading());
+        assert_eq!(options.street_side_tolerance(2.0), 5);
+        assert_eq!(options.street_side_tolerance(12.5), 12);
+
+        let mut args = json!({});
+        options.apply_to(&mut args);
+        assert_eq!(args, json!({}));
+    }
+
+    #[test]
+    fn build_options() {
+        let options = ValhallaRequestOptionsBuilder::new()
+            .with_response_format(ValhallaResponseFormat::Json)
+            .with_language("sr-Latn-RS".to_string())
+            .with_units(ValhallaUnits::Miles)
+            .with_directions_type(ValhallaDirectionsType::Maneuvers)
+            .with_alternates(2)
+            .with_date_time(ValhallaDateTime::ArriveBy {
+                value: "2025-06-01T08:30".to_string(),
+            })
+            .with_street_side_tolerance(10)
+            .with_heading_tolerance(30)
+            .with_include_heading(false)
+            .build()
+            .expect("Options should be valid");
+
+        assert_eq!(options.response_format(), ValhallaResponseFormat::Json);
+        assert!(!options.include_heading());
+        assert_eq!(options.heading_tolerance(), Some(30));
+        assert_eq!(options.street_side_tolerance(25.0), 10);
+
+        let mut args = json!({});
+        options.apply_to(&mut args);
+        assert_eq!(
+            args,
+            json!({
+                "language": "sr-Latn-RS",
+                "units": "miles",
+                "directions_type": "maneuvers",
+                "alternates": 2,
+                "date_time": {
+                    "type": 2,
+                    "value": "2025-06-01T08:30",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn builder_is_reusable() {
+        let builder = ValhallaRequestOptionsBuilder::new().with_alternates(1);
+        let with_units = builder.clone().with_units(ValhallaUnits::Miles);
+
+        assert_eq!(builder.options.units, None);
+        assert_eq!(with_units.options.units, Some(ValhallaUnits::Miles));
+        assert_eq!(with_units.options.alternates, Some(1));
+    }
+
+    #[test]
+    fn reject_invalid_language() {
+        for language in ["", "e", "english", "en_US", "en-", "en-US-thisistoolong"] {
+            assert!(
+                matches!(
+                    ValhallaRequestOptionsBuilder::new()
+                        .with_language(language.to_string())
+                        .build(),
+                    Err(InstantiationError::InvalidOptions { .. })
+                ),
+                "{language} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn reject_invalid_date_time() {
+        for value in ["2025-06-01", "2025-06-01 08:30", "2025-13-01T08:30", "now"] {
+            assert!(
+                matches!(
+                    ValhallaRequestOptionsBuilder::new()
+                        .with_date_time(ValhallaDateTime::DepartAt {
+                            value: value.to_string(),
+                        })
+                        .build(),
+                    Err(InstantiationError::InvalidOptions { .. })
+                ),
+                "{value} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn reject_invalid_heading_tolerance() {
+        assert!(ValhallaRequestOptionsBuilder::new()
+            .with_heading_tolerance(180)
+            .build()
+            .is_ok());
+        assert!(matches!(
+            ValhallaRequestOptionsBuilder::new()
+                .with_heading_tolerance(181)
+                .build(),
+            Err(InstantiationError::InvalidOptions { .. })
+        ));
+    }
+
+    #[test]
+    fn reject_directions_type_none() {
+        assert!(matches!(
+            ValhallaRequestOptionsBuilder::new()
+                .with_directions_type(ValhallaDirectionsType::None)
+                .build(),
+            Err(InstantiationError::InvalidOptions { .. })
+        ));
+    }
+}
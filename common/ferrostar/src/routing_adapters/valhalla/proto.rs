@@ -0,0 +1,252 @@
+//! Valhalla protobuf models from the
+//! [`Api` message](https://github.com/valhalla/valhalla/blob/master/proto/api.proto)
+//! and its dependencies.
+//!
+//! These are hand-written to avoid needing `protoc` at build time.
+//! Only the fields which are needed for navigation are included;
+//! the tags match the upstream definitions, so other fields are skipped when decoding.
+//! Enums are represented as their raw values.
+
+use super::models::{Leg, Location, Maneuver, Sign, SignElement, Trip, Units};
+#[cfg(feature = "alloc")]
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Api {
+    #[prost(message, optional, tag = "1")]
+    pub options: Option<Options>,
+    #[prost(message, optional, tag = "3")]
+    pub directions: Option<Directions>,
+    #[prost(message, optional, tag = "20")]
+    pub info: Option<Info>,
+}
+
+impl Api {
+    /// Converts the routes into the same models as the native JSON format.
+    pub fn into_trips(self) -> Vec<Trip> {
+        let units = match self.options.and_then(|options| options.units) {
+            Some(1) => Units::Miles,
+            _ => Units::Kilometers,
+        };
+
+        self.directions
+            .map(|directions| directions.routes)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|route| route.into_trip(units))
+            .collect()
+    }
+
+    /// The first error reported by the server, if any.
+    pub fn error(&self) -> Option<&CodedDescription> {
+        self.info.as_ref()?.errors.first()
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Options {
+    /// `0` for kilometers, or `1` for miles.
+    #[prost(int32, optional, tag = "1")]
+    pub units: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Info {
+    #[prost(message, repeated, tag = "2")]
+    pub errors: Vec<CodedDescription>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CodedDescription {
+    #[prost(string, tag = "1")]
+    pub description: String,
+    #[prost(uint64, tag = "2")]
+    pub code: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Directions {
+    #[prost(message, repeated, tag = "1")]
+    pub routes: Vec<DirectionsRoute>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DirectionsRoute {
+    #[prost(message, repeated, tag = "1")]
+    pub legs: Vec<DirectionsLeg>,
+}
+
+impl DirectionsRoute {
+    fn into_trip(self, units: Units) -> Trip {
+        // Each leg includes its start and end locations,
+        // so the start of each leg after the first is the end of the previous one.
+        let locations = self
+            .legs
+            .iter()
+            .enumerate()
+            .flat_map(|(index, leg)| leg.location.iter().skip(usize::from(index > 0)))
+            .map(Location::from)
+            .collect();
+
+        Trip {
+            locations,
+            legs: self.legs.into_iter().map(Leg::from).collect(),
+            units,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DirectionsLeg {
+    #[prost(message, repeated, tag = "4")]
+    pub location: Vec<DirectionsLocation>,
+    #[prost(message, repeated, tag = "6")]
+    pub maneuver: Vec<DirectionsManeuver>,
+    #[prost(string, tag = "7")]
+    pub shape: String,
+}
+
+impl From<DirectionsLeg> for Leg {
+    fn from(leg: DirectionsLeg) -> Self {
+        Leg {
+            maneuvers: leg.maneuver.into_iter().map(Maneuver::from).collect(),
+            shape: leg.shape,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DirectionsLocation {
+    #[prost(message, optional, tag = "1")]
+    pub ll: Option<LatLng>,
+    /// `0` for break, `1` for through, `2` for via, or `3` for break through.
+    #[prost(int32, tag = "2")]
+    pub kind: i32,
+}
+
+impl From<&DirectionsLocation> for Location {
+    fn from(location: &DirectionsLocation) -> Self {
+        let ll = location.ll.unwrap_or_default();
+        Location {
+            lat: ll.lat.unwrap_or_default(),
+            lon: ll.lng.unwrap_or_default(),
+            kind: Some(
+                match location.kind {
+                    1 => "through",
+                    2 => "via",
+                    3 => "break_through",
+                    _ => "break",
+                }
+                .to_string(),
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct LatLng {
+    #[prost(double, optional, tag = "1")]
+    pub lat: Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub lng: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DirectionsManeuver {
+    /// The maneuver type, which has the same values as in the JSON format.
+    #[prost(int32, tag = "1")]
+    pub kind: i32,
+    #[prost(string, tag = "2")]
+    pub text_instruction: String,
+    #[prost(message, repeated, tag = "3")]
+    pub street_name: Vec<StreetName>,
+    #[prost(float, tag = "4")]
+    pub length: f32,
+    #[prost(double, tag = "5")]
+    pub time: f64,
+    #[prost(uint32, tag = "8")]
+    pub begin_shape_index: u32,
+    #[prost(uint32, tag = "9")]
+    pub end_shape_index: u32,
+    #[prost(string, tag = "12")]
+    pub verbal_transition_alert_instruction: String,
+    #[prost(string, tag = "13")]
+    pub verbal_pre_transition_instruction: String,
+    #[prost(string, tag = "14")]
+    pub verbal_post_transition_instruction: String,
+    #[prost(message, optional, tag = "16")]
+    pub sign: Option<TripSign>,
+}
+
+/// Converts an empty string (the protobuf default) to [`None`].
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+impl From<DirectionsManeuver> for Maneuver {
+    fn from(maneuver: DirectionsManeuver) -> Self {
+        Maneuver {
+            kind: u8::try_from(maneuver.kind).unwrap_or_default(),
+            instruction: maneuver.text_instruction,
+            verbal_transition_alert_instruction: non_empty(
+                maneuver.verbal_transition_alert_instruction,
+            ),
+            verbal_pre_transition_instruction: non_empty(
+                maneuver.verbal_pre_transition_instruction,
+            ),
+            verbal_post_transition_instruction: non_empty(
+                maneuver.verbal_post_transition_instruction,
+            ),
+            street_names: maneuver
+                .street_name
+                .into_iter()
+                .map(|name| name.value)
+                .collect(),
+            time: maneuver.time,
+            length: f64::from(maneuver.length),
+            begin_shape_index: maneuver.begin_shape_index as usize,
+            end_shape_index: maneuver.end_shape_index as usize,
+            sign: maneuver.sign.map(Sign::from),
+            // Lanes are only available in the JSON format.
+            lanes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreetName {
+    #[prost(string, tag = "1")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TripSign {
+    #[prost(message, repeated, tag = "1")]
+    pub exit_numbers: Vec<TripSignElement>,
+    #[prost(message, repeated, tag = "3")]
+    pub exit_toward_locations: Vec<TripSignElement>,
+}
+
+impl From<TripSign> for Sign {
+    fn from(sign: TripSign) -> Self {
+        let elements = |elements: Vec<TripSignElement>| {
+            elements
+                .into_iter()
+                .map(|element| SignElement { text: element.text })
+                .collect()
+        };
+        Sign {
+            exit_number_elements: elements(sign.exit_numbers),
+            exit_toward_elements: elements(sign.exit_toward_locations),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TripSignElement {
+    #[prost(string, tag = "1")]
+    pub text: String,
+}
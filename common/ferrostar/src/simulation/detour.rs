@@ -0,0 +1,398 @@
+//! Location simulation which leaves the route (and optionally rejoins it).
+//!
+//! This makes it possible to deterministically exercise route deviation detection
+//! and rerouting end to end, without hand-crafting coordinate lists.
+
+use super::{
+    location_simulation_from_coordinates, LocationBias, LocationSimulationState, SimulationError,
+};
+use crate::models::{GeographicCoordinate, Route};
+use geo::{Destination, Distance, Haversine, InterpolatePoint, Point};
+
+#[cfg(any(feature = "wasm-bindgen", test))]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "wasm-bindgen")]
+use wasm_bindgen::{prelude::*, JsValue};
+
+#[cfg(feature = "wasm-bindgen")]
+use tsify::Tsify;
+
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String, vec::Vec};
+
+/// Where the simulation goes after leaving the route.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(any(feature = "wasm-bindgen", test), derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(into_wasm_abi, from_wasm_abi))]
+pub enum SimulatedDetour {
+    /// Follows an alternative path from the point where the simulation leaves the route.
+    ///
+    /// The path should not include the point where the route is left.
+    Path {
+        coordinates: Vec<GeographicCoordinate>,
+    },
+    /// Travels in a straight line at a fixed bearing (in degrees).
+    Bearing {
+        bearing: f64,
+        /// The distance to travel, in meters.
+        distance: f64,
+    },
+}
+
+/// Creates a location simulation which leaves the route part way along.
+///
+/// The simulation follows the route for `leave_at_distance` meters, then follows the `detour`.
+/// If `rejoin_at_distance` is set, it then heads straight back to the route at that distance along it,
+/// and follows the rest of the route to the destination.
+/// Otherwise, the simulation ends at the end of the detour.
+///
+/// Optionally resamples the resulting line so that there is a maximum distance between points.
+/// See [`location_simulation_from_coordinates`] for details of the `seed`.
+#[cfg_attr(feature = "uniffi", uniffi::export(default(seed = None)))]
+pub fn location_simulation_with_detour(
+    route: &Route,
+    leave_at_distance: f64,
+    detour: SimulatedDetour,
+    rejoin_at_distance: Option<f64>,
+    resample_distance: Option<f64>,
+    bias: LocationBias,
+    seed: Option<u64>,
+) -> Result<LocationSimulationState, SimulationError> {
+    let (before_detour, _) = split_at_distance(&route.geometry, leave_at_distance)
+        .ok_or_else(|| detour_error(format!("Can't leave the route at {leave_at_distance}m")))?;
+    let departure = *before_detour
+        .last()
+        .ok_or(SimulationError::NotEnoughPoints)?;
+
+    let detour_coordinates = match detour {
+        SimulatedDetour::Path { coordinates } => {
+            if coordinates.is_empty() {
+                return Err(detour_error(String::from("The detour path is empty")));
+            }
+            if let Some(coordinate) = coordinates
+                .iter()
+                .find(|coordinate| !(coordinate.lat.is_finite() && coordinate.lng.is_finite()))
+            {
+                return Err(detour_error(format!(
+                    "Invalid coordinate in the detour path: {coordinate:?}"
+                )));
+            }
+            coordinates
+        }
+        SimulatedDetour::Bearing { bearing, distance } => {
+            if !(distance.is_finite() && distance > 0.0 && bearing.is_finite()) {
+                return Err(detour_error(format!(
+                    "Invalid detour of {distance}m at {bearing}°"
+                )));
+            }
+            let end = Haversine.destination(Point::from(departure), bearing, distance);
+            vec![GeographicCoordinate::from(end)]
+        }
+    };
+
+    let mut coordinates = before_detour;
+    coordinates.extend(detour_coordinates);
+
+    if let Some(rejoin_at_distance) = rejoin_at_distance {
+        if rejoin_at_distance <= leave_at_distance {
+            return Err(detour_error(format!(
+                "Can't rejoin the route at {rejoin_at_distance}m before leaving it at {leave_at_distance}m"
+            )));
+        }
+        let (_, after_detour) =
+            split_at_distance(&route.geometry, rejoin_at_distance).ok_or_else(|| {
+                detour_error(format!("Can't rejoin the route at {rejoin_at_distance}m"))
+            })?;
+        coordinates.extend(after_detour);
+    }
+
+    location_simulation_from_coordinates(&coordinates, resample_distance, bias, seed)
+}
+
+/// Splits a line at the given distance along it.
+///
+/// Both halves include the (interpolated) point at the split.
+/// Returns `None` if the distance is not within the line.
+fn split_at_distance(
+    coordinates: &[GeographicCoordinate],
+    distance: f64,
+) -> Option<(Vec<GeographicCoordinate>, Vec<GeographicCoordinate>)> {
+    if !(distance.is_finite() && distance >= 0.0) {
+        return None;
+    }
+
+    let mut remaining = distance;
+    for (index, pair) in coordinates.windows(2).enumerate() {
+        let (start, end) = (Point::from(pair[0]), Point::from(pair[1]));
+        let length = Haversine.distance(start, end);
+        if remaining <= length {
+            let split = GeographicCoordinate::from(
+                Haversine.point_at_distance_between(start, end, remaining),
+            );
+            let mut before = coordinates[..=index].to_vec();
+            before.push(split);
+            let mut after = vec![split];
+            after.extend_from_slice(&coordinates[index + 1..]);
+            return Some((before, after));
+        }
+        remaining -= length;
+    }
+
+    None
+}
+
+fn detour_error(error: String) -> SimulationError {
+    SimulationError::InvalidDetour { error }
+}
+
+/// JavaScript wrapper for `location_simulation_with_detour`.
+#[cfg(feature = "wasm-bindgen")]
+#[wasm_bindgen(js_name = locationSimulationWithDetour)]
+pub fn js_location_simulation_with_detour(
+    route: JsValue,
+    leave_at_distance: f64,
+    detour: SimulatedDetour,
+    rejoin_at_distance: Option<f64>,
+    resample_distance: Option<f64>,
+    bias: LocationBias,
+    seed: Option<u64>,
+) -> Result<JsValue, JsValue> {
+    let route: Route = serde_wasm_bindgen::from_value(route)
+        .map_err(|error| JsValue::from_str(&error.to_string()))?;
+
+    location_simulation_with_detour(
+        &route,
+        leave_at_distance,
+        detour,
+        rejoin_at_distance,
+        resample_distance,
+        bias,
+        seed,
+    )
+    .map(|state| serde_wasm_bindgen::to_value(&state).unwrap())
+    .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deviation_detection::{RouteDeviation, RouteDeviationTracking};
+    use crate::models::UserLocation;
+    use crate::navigation_controller::models::{NavigationControllerConfig, TripState};
+    use crate::navigation_controller::test_helpers::{
+        gen_dummy_route_step, gen_route_from_steps, test_config,
+    };
+    use crate::navigation_controller::{NavigationController, Navigator};
+    use crate::simulation::advance_location_simulation;
+
+    /// A straight route heading east along the equator for about 2km.
+    fn straight_route() -> Route {
+        gen_route_from_steps(vec![
+            gen_dummy_route_step(0.0, 0.0, 0.009, 0.0),
+            gen_dummy_route_step(0.009, 0.0, 0.018, 0.0),
+        ])
+    }
+
+    fn simulate(state: LocationSimulationState) -> Vec<UserLocation> {
+        let mut state = state;
+        let mut locations = vec![state.current_location];
+        loop {
+            let new_state = advance_location_simulation(&state);
+            if new_state == state {
+                return locations;
+            }
+            state = new_state;
+            locations.push(state.current_location);
+        }
+    }
+
+    /// Distance in meters from the route along the equator.
+    fn distance_from_route(location: &UserLocation) -> f64 {
+        Haversine.distance(
+            Point::from(location.coordinates),
+            Point::new(location.coordinates.lng, 0.0),
+        )
+    }
+
+    #[test]
+    fn detours_at_bearing_and_rejoins() {
+        let route = straight_route();
+        let locations = simulate(
+            location_simulation_with_detour(
+                &route,
+                500.0,
+                SimulatedDetour::Bearing {
+                    bearing: 0.0,
+                    distance: 300.0,
+                },
+                Some(1000.0),
+                Some(10.0),
+                LocationBias::None,
+                None,
+            )
+            .expect("Unable to create simulation"),
+        );
+
+        let max_deviation = locations
+            .iter()
+            .map(distance_from_route)
+            .fold(0.0, f64::max);
+        assert!((max_deviation - 300.0).abs() < 1.0);
+
+        let last = locations.last().expect("Expected locations");
+        assert!((last.coordinates.lng - 0.018).abs() < 1e-6);
+        assert!(distance_from_route(last) < 1e-6);
+        // Nothing between the departure and rejoining points is on the route.
+        assert!(!locations.iter().any(|location| {
+            distance_from_route(location) < 1.0
+                && (0.0046..0.0089).contains(&location.coordinates.lng)
+        }));
+    }
+
+    #[test]
+    fn follows_path_without_rejoining() {
+        let route = straight_route();
+        let path = vec![
+            GeographicCoordinate {
+                lat: 0.001,
+                lng: 0.005,
+            },
+            GeographicCoordinate {
+                lat: 0.002,
+                lng: 0.004,
+            },
+        ];
+        let locations = simulate(
+            location_simulation_with_detour(
+                &route,
+                500.0,
+                SimulatedDetour::Path {
+                    coordinates: path.clone(),
+                },
+                None,
+                None,
+                LocationBias::None,
+                None,
+            )
+            .expect("Unable to create simulation"),
+        );
+
+        assert_eq!(
+            locations.last().map(|location| location.coordinates),
+            path.last().copied()
+        );
+        assert_eq!(locations[locations.len() - 2].coordinates, path[0]);
+    }
+
+    #[test]
+    fn rejects_invalid_detours() {
+        let route = straight_route();
+        let detour = SimulatedDetour::Bearing {
+            bearing: 90.0,
+            distance: 100.0,
+        };
+
+        for (leave_at_distance, rejoin_at_distance) in [
+            (5000.0, None),
+            (-1.0, None),
+            (500.0, Some(400.0)),
+            (500.0, Some(5000.0)),
+        ] {
+            assert!(matches!(
+                location_simulation_with_detour(
+                    &route,
+                    leave_at_distance,
+                    detour.clone(),
+                    rejoin_at_distance,
+                    None,
+                    LocationBias::None,
+                    None,
+                ),
+                Err(SimulationError::InvalidDetour { .. })
+            ));
+        }
+
+        for coordinates in [
+            vec![],
+            vec![GeographicCoordinate {
+                lat: f64::NAN,
+                lng: 0.001,
+            }],
+            vec![
+                GeographicCoordinate {
+                    lat: 0.001,
+                    lng: 0.001,
+                },
+                GeographicCoordinate {
+                    lat: 0.001,
+                    lng: f64::INFINITY,
+                },
+            ],
+        ] {
+            assert!(matches!(
+                location_simulation_with_detour(
+                    &route,
+                    500.0,
+                    SimulatedDetour::Path { coordinates },
+                    None,
+                    None,
+                    LocationBias::None,
+                    None,
+                ),
+                Err(SimulationError::InvalidDetour { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn detour_triggers_route_deviation() {
+        let route = straight_route();
+        let mut simulation_state = location_simulation_with_detour(
+            &route,
+            500.0,
+            SimulatedDetour::Bearing {
+                bearing: 0.0,
+                distance: 200.0,
+            },
+            Some(1000.0),
+            Some(10.0),
+            LocationBias::None,
+            None,
+        )
+        .expect("Unable to create simulation");
+        let controller = NavigationController::new(
+            route,
+            NavigationControllerConfig {
+                route_deviation_tracking: RouteDeviationTracking::StaticThreshold {
+                    minimum_horizontal_accuracy: 25,
+                    max_acceptable_deviation: 50.0,
+                },
+                ..test_config()
+            },
+        );
+
+        let mut state = controller.get_initial_state(simulation_state.current_location);
+        let mut deviations = vec![];
+        loop {
+            if let TripState::Navigating { deviation, .. } = state.trip_state() {
+                let off_route = matches!(deviation, RouteDeviation::OffRoute { .. });
+                if deviations.last() != Some(&off_route) {
+                    deviations.push(off_route);
+                }
+            }
+            let new_simulation_state = advance_location_simulation(&simulation_state);
+            if new_simulation_state == simulation_state {
+                break;
+            }
+            simulation_state = new_simulation_state;
+            state = controller.update_user_location(simulation_state.current_location, state);
+        }
+
+        // On route, then off route during the detour, then back on route after rejoining.
+        assert_eq!(deviations, vec![false, true, false]);
+        assert!(matches!(state.trip_state(), TripState::Complete { .. }));
+    }
+}
@@ -23,6 +23,8 @@
 //!     // This is often desirable to create a smooth simulated movement when you don't have a GPS trace.
 //!     None,
 //!     LocationBias::None,
+//!     // The seed for any randomness (ex: a random bias); by default, it is derived from the input.
+//!     None,
 //! )?;
 //!
 //! loop {
@@ -39,10 +41,19 @@
 //! # }
 //! ```
 
+pub mod detour;
+pub mod noise;
+pub mod timing;
+pub mod tracks;
+
 use crate::algorithms::trunc_float;
 use crate::models::{CourseOverGround, GeographicCoordinate, Route, UserLocation};
 use geo::{coord, Bearing, Densify, Geodesic, Haversine, LineString, Point};
+use noise::GpsNoiseState;
 use polyline::decode_polyline;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use timing::TimedSimulationState;
 
 #[cfg(any(test, feature = "wasm-bindgen"))]
 use serde::{Deserialize, Serialize};
@@ -77,6 +88,27 @@ pub enum SimulationError {
     #[cfg_attr(feature = "std", error("Not enough points (expected at least two)."))]
     /// Not enough points in the input.
     NotEnoughPoints,
+    #[cfg_attr(feature = "std", error("Failed to parse track: {error}."))]
+    /// Errors parsing a recorded track (e.g. GPX or NMEA).
+    TrackError { error: String },
+    #[cfg_attr(
+        feature = "std",
+        error("Invalid time step (expected a positive number of seconds).")
+    )]
+    /// The time step for a timed simulation is not positive.
+    InvalidTimeStep,
+    #[cfg_attr(feature = "std", error("Invalid detour: {error}."))]
+    /// The detour can't be made along the route.
+    InvalidDetour { error: String },
+    #[cfg_attr(
+        feature = "std",
+        error("Too many steps ({steps}); use a longer time step.")
+    )]
+    /// A timed simulation would take an unreasonable number of steps to complete.
+    TooManySteps { steps: u64 },
+    #[cfg_attr(feature = "std", error("Invalid noise model: {error}."))]
+    /// The GPS noise model has values which are out of range.
+    InvalidNoiseModel { error: String },
 }
 
 /// Controls how simulated locations deviate from the actual route line.
@@ -97,6 +129,7 @@ pub enum LocationBias {
 
     /// Simulates GPS bias by randomly choosing left or right offset on initialization
     /// and maintaining that bias throughout the route.
+    /// The side is chosen using the seed of the simulation, so it is reproducible.
     /// The f64 parameter specifies the offset distance in meters.
     ///
     /// This mimics real-world GPS behavior where bias direction is random but typically
@@ -119,30 +152,60 @@ pub enum LocationBias {
 pub struct LocationSimulationState {
     pub current_location: UserLocation,
     remaining_locations: Vec<GeographicCoordinate>,
+    /// Locations which are played back exactly as they were recorded.
+    ///
+    /// These are used instead of `remaining_locations` when simulating from a recorded track.
+    #[cfg_attr(
+        any(feature = "wasm-bindgen", test),
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    remaining_recorded_locations: Vec<UserLocation>,
+    /// The progress of a simulation which follows the timing of a route
+    /// (see [`timing::location_simulation_from_route_timing`]).
+    ///
+    /// This is used instead of `remaining_locations` when present.
+    #[cfg_attr(
+        any(feature = "wasm-bindgen", test),
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    timed: Option<TimedSimulationState>,
     bias: LocationBias,
+    /// The seed for all randomness in the simulation (ex: the side of a random bias).
+    ///
+    /// This is derived from the input, so the same input always produces the same simulation.
+    #[cfg_attr(any(feature = "wasm-bindgen", test), serde(default))]
+    seed: u64,
+    /// Noise added to each location (see [`noise::location_simulation_with_noise`]).
+    #[cfg_attr(
+        any(feature = "wasm-bindgen", test),
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    noise: Option<GpsNoiseState>,
 }
 
 /// Creates a location simulation from a set of coordinates.
 ///
 /// Optionally resamples the input line so that there is a maximum distance between points.
-#[cfg_attr(feature = "uniffi", uniffi::export)]
+///
+/// The `seed` is used for all randomness in the simulation (ex: the side of a random bias).
+/// When it is `None`, it is derived from the coordinates,
+/// so the same input always produces the same simulation.
+/// Seeds larger than 2^53 can't be represented exactly in JavaScript.
+#[cfg_attr(feature = "uniffi", uniffi::export(default(seed = None)))]
 pub fn location_simulation_from_coordinates(
     coordinates: &[GeographicCoordinate],
     resample_distance: Option<f64>,
     bias: LocationBias,
+    seed: Option<u64>,
 ) -> Result<LocationSimulationState, SimulationError> {
     if let Some((current, rest)) = coordinates.split_first() {
         if let Some(next) = rest.first() {
-            let (jittered_current, bearing) = add_lateral_offset(*current, *next, &bias);
-
-            let accuracy = match bias {
-                LocationBias::None => 0.0,
-                LocationBias::Left(m) | LocationBias::Right(m) | LocationBias::Random(m) => m,
-            };
+            let seed = seed.unwrap_or_else(|| seed_from_coordinates(coordinates));
+            let (jittered_current, bearing) = add_lateral_offset(*current, *next, &bias, seed);
 
             let current_location = UserLocation {
                 coordinates: jittered_current,
-                horizontal_accuracy: accuracy,
+                horizontal_accuracy: horizontal_accuracy(&bias),
                 course_over_ground: Some(CourseOverGround::new(bearing, Some(5))),
                 timestamp: SystemTime::now(),
                 speed: None,
@@ -178,7 +241,11 @@ pub fn location_simulation_from_coordinates(
             Ok(LocationSimulationState {
                 current_location,
                 remaining_locations,
+                remaining_recorded_locations: Vec::new(),
+                timed: None,
                 bias,
+                seed,
+                noise: None,
             })
         } else {
             Err(SimulationError::NotEnoughPoints)
@@ -191,26 +258,30 @@ pub fn location_simulation_from_coordinates(
 /// Creates a location simulation from a route.
 ///
 /// Optionally resamples the route geometry so that there is no more than the specified maximum distance between points.
-#[cfg_attr(feature = "uniffi", uniffi::export)]
+/// See [`location_simulation_from_coordinates`] for details of the `seed`.
+#[cfg_attr(feature = "uniffi", uniffi::export(default(seed = None)))]
 pub fn location_simulation_from_route(
     route: &Route,
     resample_distance: Option<f64>,
     bias: LocationBias,
+    seed: Option<u64>,
 ) -> Result<LocationSimulationState, SimulationError> {
-    // This function is purely a convenience for now,
-    // but we eventually expand the simulation to be aware of route timing
-    location_simulation_from_coordinates(&route.geometry, resample_distance, bias)
+    // This function is purely a convenience;
+    // see `location_simulation_from_route_timing` for a simulation which is aware of route timing.
+    location_simulation_from_coordinates(&route.geometry, resample_distance, bias, seed)
 }
 
 /// Creates a location simulation from a polyline.
 ///
 /// Optionally resamples the input line so that there is no more than the specified maximum distance between points.
-#[cfg_attr(feature = "uniffi", uniffi::export)]
+/// See [`location_simulation_from_coordinates`] for details of the `seed`.
+#[cfg_attr(feature = "uniffi", uniffi::export(default(seed = None)))]
 pub fn location_simulation_from_polyline(
     polyline: &str,
     precision: u32,
     resample_distance: Option<f64>,
     bias: LocationBias,
+    seed: Option<u64>,
 ) -> Result<LocationSimulationState, SimulationError> {
     let linestring =
         decode_polyline(polyline, precision).map_err(|error| SimulationError::PolylineError {
@@ -220,26 +291,80 @@ pub fn location_simulation_from_polyline(
         .coords()
         .map(|c| GeographicCoordinate::from(*c))
         .collect();
-    location_simulation_from_coordinates(&coordinates, resample_distance, bias)
+    location_simulation_from_coordinates(&coordinates, resample_distance, bias, seed)
+}
+
+/// Creates a location simulation which plays back a sequence of recorded locations.
+///
+/// Unlike the other constructors, the locations are used exactly as provided,
+/// including their timestamps, speeds, courses, and accuracy values.
+/// This makes it possible to replay real-world drives through the navigation controller.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+pub fn location_simulation_from_locations(
+    locations: &[UserLocation],
+) -> Result<LocationSimulationState, SimulationError> {
+    match locations.split_first() {
+        Some((current_location, rest)) if !rest.is_empty() => Ok(LocationSimulationState {
+            current_location: *current_location,
+            remaining_locations: Vec::new(),
+            remaining_recorded_locations: Vec::from(rest),
+            timed: None,
+            bias: LocationBias::None,
+            seed: seed_from_coordinates(locations.iter().map(|location| &location.coordinates)),
+            noise: None,
+        }),
+        _ => Err(SimulationError::NotEnoughPoints),
+    }
+}
+
+/// Derives a seed for the simulation from its input (using the FNV-1a hash).
+///
+/// Seeds are limited to 53 bits so that they can be represented exactly in JavaScript.
+fn seed_from_coordinates<'a>(
+    coordinates: impl IntoIterator<Item = &'a GeographicCoordinate>,
+) -> u64 {
+    let hash = coordinates
+        .into_iter()
+        .flat_map(|coordinate| {
+            [coordinate.lat.to_bits(), coordinate.lng.to_bits()]
+                .into_iter()
+                .flat_map(u64::to_le_bytes)
+        })
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    hash >> 11
 }
 
 fn add_lateral_offset(
     current: GeographicCoordinate,
     next: GeographicCoordinate,
     bias: &LocationBias,
+    seed: u64,
 ) -> (GeographicCoordinate, f64) {
     let current_point = Point::from(current);
     let next_point = Point::from(next);
     let bearing = Geodesic.bearing(current_point, next_point);
 
+    (offset_laterally(current, bearing, bias, seed), bearing)
+}
+
+/// Offsets a coordinate perpendicular to the direction of travel according to the bias.
+fn offset_laterally(
+    coordinate: GeographicCoordinate,
+    bearing: f64,
+    bias: &LocationBias,
+    seed: u64,
+) -> GeographicCoordinate {
     match bias {
-        LocationBias::None => (current, bearing),
+        LocationBias::None => coordinate,
         LocationBias::Left(meters) | LocationBias::Right(meters) | LocationBias::Random(meters) => {
             let sign = match bias {
                 LocationBias::Left(_) => -1.0,
                 LocationBias::Right(_) => 1.0,
                 LocationBias::Random(_) => {
-                    if rand::random() {
+                    // The side is derived from the seed alone, so it never changes during a simulation.
+                    if StdRng::seed_from_u64(seed).gen() {
                         1.0
                     } else {
                         -1.0
@@ -257,17 +382,22 @@ fn add_lateral_offset(
             let lat_offset = offset_deg * lateral_bearing_rad.cos();
             let lng_offset = offset_deg * lateral_bearing_rad.sin();
 
-            (
-                GeographicCoordinate {
-                    lat: current.lat + lat_offset,
-                    lng: current.lng + lng_offset,
-                },
-                bearing,
-            )
+            GeographicCoordinate {
+                lat: coordinate.lat + lat_offset,
+                lng: coordinate.lng + lng_offset,
+            }
         }
     }
 }
 
+/// The horizontal accuracy reported for simulated locations with the given bias.
+fn horizontal_accuracy(bias: &LocationBias) -> f64 {
+    match bias {
+        LocationBias::None => 0.0,
+        LocationBias::Left(m) | LocationBias::Right(m) | LocationBias::Random(m) => *m,
+    }
+}
+
 /// Returns the next simulation state based on the desired strategy.
 /// Results of this can be thought of like a stream from a generator function.
 ///
@@ -278,7 +408,55 @@ fn add_lateral_offset(
 /// When there are now more locations to visit, returns the same state forever.
 #[cfg_attr(feature = "uniffi", uniffi::export)]
 pub fn advance_location_simulation(state: &LocationSimulationState) -> LocationSimulationState {
-    if let Some((next_coordinate, rest)) = state.remaining_locations.split_first() {
+    let Some(noise) = &state.noise else {
+        return advance_without_noise(state);
+    };
+
+    let mut noise = noise.clone();
+    let mut next_state = advance_without_noise(state);
+    // Skip over dropped fixes (but never the last one).
+    while next_state != *state {
+        let is_last = next_state.remaining_locations.is_empty()
+            && next_state.remaining_recorded_locations.is_empty()
+            && next_state
+                .timed
+                .as_ref()
+                .is_none_or(TimedSimulationState::is_finished);
+        if let Some(current_location) = noise.apply(next_state.current_location, !is_last) {
+            return LocationSimulationState {
+                current_location,
+                noise: Some(noise),
+                ..next_state
+            };
+        }
+        next_state = advance_without_noise(&next_state);
+    }
+
+    state.clone()
+}
+
+/// Advances the simulation to the next location (without adding any noise).
+fn advance_without_noise(state: &LocationSimulationState) -> LocationSimulationState {
+    if let Some(timed) = &state.timed {
+        match timed.advance(&state.current_location, &state.bias, state.seed) {
+            Some((timed, current_location)) => LocationSimulationState {
+                current_location,
+                timed: Some(timed),
+                ..state.clone()
+            },
+            None => state.clone(),
+        }
+    } else if let Some((next_location, rest)) = state.remaining_recorded_locations.split_first() {
+        LocationSimulationState {
+            current_location: *next_location,
+            remaining_locations: Vec::new(),
+            remaining_recorded_locations: Vec::from(rest),
+            timed: None,
+            bias: state.bias.clone(),
+            seed: state.seed,
+            noise: state.noise.clone(),
+        }
+    } else if let Some((next_coordinate, rest)) = state.remaining_locations.split_first() {
         let (jittered_next, bearing) = add_lateral_offset(
             *next_coordinate,
             if let Some(future) = rest.first() {
@@ -287,16 +465,12 @@ pub fn advance_location_simulation(state: &LocationSimulationState) -> LocationS
                 *next_coordinate
             },
             &state.bias,
+            state.seed,
         );
 
-        let accuracy = match state.bias {
-            LocationBias::None => 0.0,
-            LocationBias::Left(m) | LocationBias::Right(m) | LocationBias::Random(m) => m,
-        };
-
         let next_location = UserLocation {
             coordinates: jittered_next,
-            horizontal_accuracy: accuracy,
+            horizontal_accuracy: horizontal_accuracy(&state.bias),
             course_over_ground: Some(CourseOverGround::new(bearing, Some(5))),
             timestamp: SystemTime::now(),
             speed: None,
@@ -305,7 +479,11 @@ pub fn advance_location_simulation(state: &LocationSimulationState) -> LocationS
         LocationSimulationState {
             current_location: next_location,
             remaining_locations: Vec::from(rest),
+            remaining_recorded_locations: Vec::new(),
+            timed: None,
             bias: state.bias.clone(),
+            seed: state.seed,
+            noise: state.noise.clone(),
         }
     } else {
         state.clone()
@@ -319,11 +497,12 @@ pub fn js_location_simulation_from_coordinates(
     coordinates: JsValue,
     resample_distance: Option<f64>,
     bias: LocationBias,
+    seed: Option<u64>,
 ) -> Result<JsValue, JsValue> {
     let coordinates: Vec<GeographicCoordinate> = serde_wasm_bindgen::from_value(coordinates)
         .map_err(|error| JsValue::from_str(&error.to_string()))?;
 
-    location_simulation_from_coordinates(&coordinates, resample_distance, bias)
+    location_simulation_from_coordinates(&coordinates, resample_distance, bias, seed)
         .map(|state| serde_wasm_bindgen::to_value(&state).unwrap())
         .map_err(|error| JsValue::from_str(&error.to_string()))
 }
@@ -335,11 +514,12 @@ pub fn js_location_simulation_from_route(
     route: JsValue,
     resample_distance: Option<f64>,
     bias: LocationBias,
+    seed: Option<u64>,
 ) -> Result<JsValue, JsValue> {
     let route: Route = serde_wasm_bindgen::from_value(route)
         .map_err(|error| JsValue::from_str(&error.to_string()))?;
 
-    location_simulation_from_route(&route, resample_distance, bias)
+    location_simulation_from_route(&route, resample_distance, bias, seed)
         .map(|state| serde_wasm_bindgen::to_value(&state).unwrap())
         .map_err(|error| JsValue::from_str(&error.to_string()))
 }
@@ -352,8 +532,9 @@ pub fn js_location_simulation_from_polyline(
     precision: u32,
     resample_distance: Option<f64>,
     bias: LocationBias,
+    seed: Option<u64>,
 ) -> Result<JsValue, JsValue> {
-    location_simulation_from_polyline(polyline, precision, resample_distance, bias)
+    location_simulation_from_polyline(polyline, precision, resample_distance, bias, seed)
         .map(|state| serde_wasm_bindgen::to_value(&state).unwrap())
         .map_err(|error| JsValue::from_str(&error.to_string()))
 }
@@ -396,6 +577,7 @@ mod tests {
             ],
             resample_distance,
             LocationBias::None,
+            None,
         )
         .expect("Unable to initialize simulation");
 
@@ -420,6 +602,7 @@ mod tests {
             6,
             None,
             LocationBias::None,
+            None,
         )
         .expect("Unable to parse polyline");
         insta::assert_yaml_snapshot!(state);
@@ -429,9 +612,14 @@ mod tests {
     fn test_extended_interpolation_simulation() {
         let polyline = r#"umrefAzifwgF?yJf@?|C@?sJ?iL@_BBqD@cDzh@L|@?jBuDjCCl@u@^f@nB?|ABd@s@r@_AAiBBiC@kAlAHrEQ|F@pCNpA?pAAfB?~CkAtXsGRXlDw@rCo@jBc@SwAKoDr@}GLyAJ}AEs@]qBs@gE_@qC?aBBqAVkBZwBLmAFcBG_DOuB?}A^wAjA}Av@eBJoAAyA[sBbCUhAEIoCdAaCd@{@Fer@@ae@?aD?o[Ny@Vk@Sg@C_FCcDT[S_@Ow@F}oCXoAVe@_@e@?mE?cDNm@Og@Ok@Ck^N_BRu@a@OJqFFyDV[a@kAIkSLcF|AgNb@{@U_@JaEN}ETW[cA\_TbAkm@P_H\sE`AgFrCkKlAuGrEo\n@_B|@[~sBa@pAc@|AAh`Aa@jGEnGCrh@AfiAAjAx@TW`DO|CK\mEZ?~LBzBA|_@GtA?zPGlKQ?op@?uO@ggA?wE@uFEwXEyOCeFAkMAsKIot@?_FEoYAsI?yC?eH?}C?}GAy]Bux@Aog@AmKCmFC}YA}WVgBRu@vAaBlC{CxDCR?h@AhHQvGApDA|BAhHA`DC|GGzFDlM@jNA|J?bAkBtACvAArCClINfDdAfFGzW[|HI`FE@eMhHEt^KpJE"#;
         let max_distance = 10.0;
-        let mut state =
-            location_simulation_from_polyline(polyline, 6, Some(max_distance), LocationBias::None)
-                .expect("Unable to create initial state");
+        let mut state = location_simulation_from_polyline(
+            polyline,
+            6,
+            Some(max_distance),
+            LocationBias::None,
+            None,
+        )
+        .expect("Unable to create initial state");
         let original_linestring = decode_polyline(polyline, 6).expect("Unable to decode polyline");
 
         // Loop until state no longer changes
@@ -495,7 +683,7 @@ mod tests {
             },
         ];
 
-        let state = location_simulation_from_coordinates(&coordinates, None, bias.clone())
+        let state = location_simulation_from_coordinates(&coordinates, None, bias.clone(), None)
             .expect("Failed to create simulation");
 
         if matches!(bias, LocationBias::None) {
@@ -536,9 +724,13 @@ mod tests {
             },
         ];
 
-        let mut state =
-            location_simulation_from_coordinates(&coordinates, None, LocationBias::Random(4.0))
-                .expect("Failed to create simulation");
+        let mut state = location_simulation_from_coordinates(
+            &coordinates,
+            None,
+            LocationBias::Random(4.0),
+            None,
+        )
+        .expect("Failed to create simulation");
 
         let first_point: Point = state.current_location.coordinates.into();
         let first_original: Point = coordinates[0].into();
@@ -562,4 +754,75 @@ mod tests {
             state = new_state;
         }
     }
+
+    #[test]
+    fn random_bias_is_reproducible_and_keeps_its_side() {
+        // A line heading due east along the equator, so the side of the bias is the sign of the latitude.
+        let coordinates: Vec<_> = (0..20)
+            .map(|i| GeographicCoordinate {
+                lng: f64::from(i) * 0.0001,
+                lat: 0.0,
+            })
+            .collect();
+        let simulate = || {
+            let mut state = location_simulation_from_coordinates(
+                &coordinates,
+                None,
+                LocationBias::Random(4.0),
+                None,
+            )
+            .expect("Failed to create simulation");
+            let mut latitudes = vec![state.current_location.coordinates.lat];
+            loop {
+                let new_state = advance_location_simulation(&state);
+                if new_state == state {
+                    return latitudes;
+                }
+                state = new_state;
+                latitudes.push(state.current_location.coordinates.lat);
+            }
+        };
+
+        let latitudes = simulate();
+        assert_eq!(latitudes.len(), coordinates.len());
+        let is_north = latitudes[0] > 0.0;
+        // The last location has no direction of travel, so only the others are offset to the side.
+        assert!(latitudes[..latitudes.len() - 1]
+            .iter()
+            .all(|lat| (*lat > 0.0) == is_north && lat.abs() > 1e-5));
+        assert_eq!(simulate(), latitudes);
+    }
+
+    #[test]
+    fn explicit_seed_is_used() {
+        let coordinates: Vec<_> = (0..5)
+            .map(|i| GeographicCoordinate {
+                lng: f64::from(i) * 0.0001,
+                lat: 0.0,
+            })
+            .collect();
+        let simulate = |seed| {
+            location_simulation_from_coordinates(
+                &coordinates,
+                None,
+                LocationBias::Random(4.0),
+                seed,
+            )
+            .expect("Failed to create simulation")
+        };
+
+        let default_seed = simulate(None).seed;
+        assert_eq!(default_seed, seed_from_coordinates(&coordinates));
+        assert_eq!(simulate(Some(42)).seed, 42);
+        assert_eq!(
+            simulate(Some(42)).current_location.coordinates,
+            simulate(Some(42)).current_location.coordinates
+        );
+
+        // Different seeds choose different sides of the route for the random bias.
+        let sides: Vec<_> = (0..16)
+            .map(|seed| simulate(Some(seed)).current_location.coordinates.lat > 0.0)
+            .collect();
+        assert!(sides.contains(&true) && sides.contains(&false));
+    }
 }
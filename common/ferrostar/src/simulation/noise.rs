@@ -0,0 +1,532 @@
+//! A model of the errors in real-world GPS readings.
+//!
+//! [`LocationBias`](super::LocationBias) only offsets locations by a constant amount,
+//! which is easy to reason about but much kinder than a real receiver.
+//! The [`GpsNoiseModel`] layers several kinds of error on top of any simulation:
+//!
+//! - Gaussian jitter, which is independent for every fix.
+//! - A slowly wandering drift (random walk), which is correlated between fixes.
+//! - Occasional multipath jumps, where a single fix is far from the true location.
+//! - Course noise.
+//! - Dropped fixes.
+//! - Windows of degraded signal (ex: tunnels and urban canyons),
+//!   where errors grow and fixes are more likely to be dropped.
+//!
+//! All randomness is derived from a seed, so a simulation with noise is reproducible.
+
+use super::{LocationSimulationState, SimulationError, UserLocation};
+use crate::models::CourseOverGround;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[cfg(any(feature = "wasm-bindgen", test))]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "wasm-bindgen")]
+use wasm_bindgen::{prelude::*, JsValue};
+
+#[cfg(feature = "wasm-bindgen")]
+use tsify::Tsify;
+
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String, vec::Vec};
+
+/// Approximate number of meters per degree of latitude.
+const METERS_PER_DEGREE: f64 = 111_111.0;
+
+/// Configures the errors added to simulated locations.
+///
+/// All distances are in meters, and all probabilities are in the range 0 to 1.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[cfg_attr(any(feature = "wasm-bindgen", test), derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct GpsNoiseModel {
+    /// The seed for all randomness. The same seed always produces the same noise.
+    pub seed: u64,
+    /// The standard deviation of the independent error in each fix.
+    pub jitter_std_dev: f64,
+    /// The standard deviation of each step of the random walk drift.
+    pub drift_step_std_dev: f64,
+    /// The maximum distance which the drift can wander from the true location.
+    pub max_drift: f64,
+    /// The probability that a fix is thrown off by a multipath reflection.
+    pub multipath_probability: f64,
+    /// The distance which a multipath reflection moves a fix (in a random direction).
+    pub multipath_distance: f64,
+    /// The standard deviation of the error in the course over ground, in degrees.
+    pub course_std_dev: f64,
+    /// The probability that a fix is dropped entirely.
+    pub dropout_probability: f64,
+    /// Windows of the simulation with a degraded signal.
+    pub degraded_windows: Vec<DegradedSignalWindow>,
+}
+
+/// A window of the simulation where the signal is degraded (ex: a tunnel or urban canyon).
+///
+/// Windows are measured in fixes (including dropped ones) since the noise was added.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[cfg_attr(any(feature = "wasm-bindgen", test), derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct DegradedSignalWindow {
+    /// The index of the first fix in the window.
+    pub start_fix: u64,
+    /// The index of the first fix after the window.
+    pub end_fix: u64,
+    /// The factor by which jitter, drift, and the reported accuracy are multiplied.
+    pub error_multiplier: f64,
+    /// The probability that a fix is dropped within the window
+    /// (ex: 1.0 to simulate a tunnel).
+    pub dropout_probability: f64,
+}
+
+impl GpsNoiseModel {
+    /// Checks that every value in the model is in range.
+    fn validate(&self) -> Result<(), SimulationError> {
+        let non_negative = [
+            ("jitter_std_dev", self.jitter_std_dev),
+            ("drift_step_std_dev", self.drift_step_std_dev),
+            ("max_drift", self.max_drift),
+            ("multipath_distance", self.multipath_distance),
+            ("course_std_dev", self.course_std_dev),
+        ];
+        let probabilities = [
+            ("multipath_probability", self.multipath_probability),
+            ("dropout_probability", self.dropout_probability),
+        ];
+        check_values(&non_negative, &probabilities)?;
+
+        for window in &self.degraded_windows {
+            if window.start_fix > window.end_fix {
+                return Err(noise_model_error(format!(
+                    "degraded window starts at fix {} but ends at fix {}",
+                    window.start_fix, window.end_fix
+                )));
+            }
+            check_values(
+                &[("error_multiplier", window.error_multiplier)],
+                &[("dropout_probability", window.dropout_probability)],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that distances (and similar values) are finite and non-negative,
+/// and that probabilities are between 0 and 1.
+fn check_values(
+    non_negative: &[(&str, f64)],
+    probabilities: &[(&str, f64)],
+) -> Result<(), SimulationError> {
+    if let Some((name, value)) = non_negative
+        .iter()
+        .find(|(_, value)| !(value.is_finite() && *value >= 0.0))
+    {
+        return Err(noise_model_error(format!(
+            "{name} must be a non-negative number (got {value})"
+        )));
+    }
+    if let Some((name, value)) = probabilities
+        .iter()
+        .find(|(_, value)| !(0.0..=1.0).contains(value))
+    {
+        return Err(noise_model_error(format!(
+            "{name} must be between 0 and 1 (got {value})"
+        )));
+    }
+    Ok(())
+}
+
+fn noise_model_error(error: String) -> SimulationError {
+    SimulationError::InvalidNoiseModel { error }
+}
+
+/// The evolving state of the noise in a simulation.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[cfg_attr(any(feature = "wasm-bindgen", test), derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct GpsNoiseState {
+    model: GpsNoiseModel,
+    /// The seed for the next fix.
+    next_seed: u64,
+    /// The index of the next fix.
+    fix_index: u64,
+    /// The current drift east of the true location.
+    drift_east: f64,
+    /// The current drift north of the true location.
+    drift_north: f64,
+}
+
+impl GpsNoiseState {
+    fn new(model: GpsNoiseModel) -> Self {
+        Self {
+            next_seed: model.seed,
+            model,
+            fix_index: 0,
+            drift_east: 0.0,
+            drift_north: 0.0,
+        }
+    }
+
+    /// Adds noise to the next fix.
+    ///
+    /// Returns `None` if the fix is dropped (only possible when `can_drop` is true).
+    pub(super) fn apply(&mut self, location: UserLocation, can_drop: bool) -> Option<UserLocation> {
+        let mut rng = StdRng::seed_from_u64(self.next_seed);
+        // Limited to 53 bits so that the state can be represented exactly in JavaScript.
+        self.next_seed = rng.gen::<u64>() >> 11;
+
+        let model = &self.model;
+        let window = model
+            .degraded_windows
+            .iter()
+            .find(|window| (window.start_fix..window.end_fix).contains(&self.fix_index));
+        self.fix_index += 1;
+        let (error_multiplier, dropout_probability) =
+            window.map_or((1.0, model.dropout_probability), |window| {
+                (
+                    window.error_multiplier,
+                    window.dropout_probability.max(model.dropout_probability),
+                )
+            });
+
+        if can_drop && rng.gen::<f64>() < dropout_probability {
+            return None;
+        }
+
+        // The drift wanders freely, but never further than the maximum.
+        let drift_step = model.drift_step_std_dev * error_multiplier;
+        self.drift_east += gaussian(&mut rng) * drift_step;
+        self.drift_north += gaussian(&mut rng) * drift_step;
+        let drift = self.drift_east.hypot(self.drift_north);
+        if drift > model.max_drift {
+            let scale = model.max_drift.max(0.0) / drift;
+            self.drift_east *= scale;
+            self.drift_north *= scale;
+        }
+
+        let jitter = model.jitter_std_dev * error_multiplier;
+        let mut east = self.drift_east + gaussian(&mut rng) * jitter;
+        let mut north = self.drift_north + gaussian(&mut rng) * jitter;
+        if rng.gen::<f64>() < model.multipath_probability {
+            let direction = rng.gen_range(0.0..core::f64::consts::TAU);
+            east += direction.sin() * model.multipath_distance;
+            north += direction.cos() * model.multipath_distance;
+        }
+
+        let mut coordinates = location.coordinates;
+        coordinates.lat += north / METERS_PER_DEGREE;
+        coordinates.lng += east / (METERS_PER_DEGREE * coordinates.lat.to_radians().cos());
+
+        let course_over_ground = location.course_over_ground.map(|course| {
+            let degrees = f64::from(course.degrees) + gaussian(&mut rng) * model.course_std_dev;
+            CourseOverGround::new(degrees.rem_euclid(360.0), course.accuracy)
+        });
+
+        Some(UserLocation {
+            coordinates,
+            // Like a real receiver, only the jitter is reflected in the reported accuracy
+            // (roughly the 68% confidence radius); drift and multipath go unnoticed.
+            horizontal_accuracy: location.horizontal_accuracy + 1.5 * jitter,
+            course_over_ground,
+            ..location
+        })
+    }
+}
+
+/// Adds GPS noise to a location simulation.
+///
+/// The noise is applied to the current location immediately,
+/// and to every location as the simulation advances.
+/// Dropped fixes are skipped over when advancing, but the final location is never dropped.
+///
+/// Returns an error if any standard deviation or distance in the model is negative (or not finite),
+/// if any probability is outside the range 0 to 1,
+/// or if a degraded window ends before it starts.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+pub fn location_simulation_with_noise(
+    state: LocationSimulationState,
+    model: GpsNoiseModel,
+) -> Result<LocationSimulationState, SimulationError> {
+    model.validate()?;
+
+    let mut noise = GpsNoiseState::new(model);
+    // The initial location is never dropped, so there is always a current location.
+    let current_location = noise
+        .apply(state.current_location, false)
+        .unwrap_or(state.current_location);
+
+    Ok(LocationSimulationState {
+        current_location,
+        noise: Some(noise),
+        ..state
+    })
+}
+
+/// Draws from the standard normal distribution (using the Box-Muller transform).
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    // Shift the first sample into (0, 1] to avoid taking the log of zero.
+    let radius = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt();
+    let angle = rng.gen_range(0.0..core::f64::consts::TAU);
+    radius * angle.cos()
+}
+
+/// JavaScript wrapper for `location_simulation_with_noise`.
+#[cfg(feature = "wasm-bindgen")]
+#[wasm_bindgen(js_name = locationSimulationWithNoise)]
+pub fn js_location_simulation_with_noise(
+    state: JsValue,
+    model: GpsNoiseModel,
+) -> Result<JsValue, JsValue> {
+    let state: LocationSimulationState = serde_wasm_bindgen::from_value(state)
+        .map_err(|error| JsValue::from_str(&error.to_string()))?;
+
+    location_simulation_with_noise(state, model)
+        .map(|state| serde_wasm_bindgen::to_value(&state).unwrap())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GeographicCoordinate;
+    use crate::simulation::{
+        advance_location_simulation, location_simulation_from_coordinates, LocationBias,
+    };
+    use geo::{Distance, Haversine, Point};
+
+    /// A model without any noise, which tests can selectively enable.
+    fn quiet_model(seed: u64) -> GpsNoiseModel {
+        GpsNoiseModel {
+            seed,
+            jitter_std_dev: 0.0,
+            drift_step_std_dev: 0.0,
+            max_drift: 0.0,
+            multipath_probability: 0.0,
+            multipath_distance: 0.0,
+            course_std_dev: 0.0,
+            dropout_probability: 0.0,
+            degraded_windows: vec![],
+        }
+    }
+
+    fn noisy_model(seed: u64) -> GpsNoiseModel {
+        GpsNoiseModel {
+            jitter_std_dev: 3.0,
+            drift_step_std_dev: 1.0,
+            max_drift: 10.0,
+            multipath_probability: 0.05,
+            multipath_distance: 50.0,
+            course_std_dev: 5.0,
+            dropout_probability: 0.05,
+            ..quiet_model(seed)
+        }
+    }
+
+    /// A straight line about 1km long, resampled every 10m.
+    fn simulation() -> LocationSimulationState {
+        location_simulation_from_coordinates(
+            &[
+                GeographicCoordinate { lat: 0.0, lng: 0.0 },
+                GeographicCoordinate {
+                    lat: 0.0,
+                    lng: 0.0001,
+                },
+                GeographicCoordinate {
+                    lat: 0.0,
+                    lng: 0.009,
+                },
+            ],
+            Some(10.0),
+            LocationBias::None,
+            None,
+        )
+        .expect("Unable to create simulation")
+    }
+
+    fn with_noise(model: GpsNoiseModel) -> LocationSimulationState {
+        location_simulation_with_noise(simulation(), model).expect("Invalid noise model")
+    }
+
+    /// Runs the simulation to completion, returning every location (without timestamps).
+    fn run(state: LocationSimulationState) -> Vec<(GeographicCoordinate, f64, Option<u16>)> {
+        let mut state = state;
+        let mut locations = vec![];
+        loop {
+            let location = state.current_location;
+            locations.push((
+                location.coordinates,
+                location.horizontal_accuracy,
+                location.course_over_ground.map(|course| course.degrees),
+            ));
+            let new_state = advance_location_simulation(&state);
+            if new_state == state {
+                return locations;
+            }
+            state = new_state;
+        }
+    }
+
+    /// Distance in meters between a location and the (true) route along the equator.
+    fn error(coordinate: GeographicCoordinate) -> f64 {
+        Haversine.distance(Point::from(coordinate), Point::new(coordinate.lng, 0.0))
+    }
+
+    #[test]
+    fn noise_is_reproducible() {
+        let first = run(with_noise(noisy_model(42)));
+        let second = run(with_noise(noisy_model(42)));
+        let other_seed = run(with_noise(noisy_model(43)));
+
+        assert_eq!(first, second);
+        assert_ne!(first, other_seed);
+    }
+
+    #[test]
+    fn jitter_matches_model() {
+        let locations = run(with_noise(GpsNoiseModel {
+            jitter_std_dev: 3.0,
+            ..quiet_model(7)
+        }));
+
+        let errors: Vec<_> = locations
+            .iter()
+            .map(|(coordinate, _, _)| error(*coordinate))
+            .collect();
+        let rms_error = (errors.iter().map(|e| e * e).sum::<f64>() / errors.len() as f64).sqrt();
+        assert!(locations.len() > 100);
+        // Only one axis of the error is perpendicular to the route.
+        assert!(rms_error > 1.5 && rms_error < 4.5, "RMS error {rms_error}");
+        assert!(errors.iter().all(|error| *error < 6.0 * 3.0));
+        assert!(locations
+            .iter()
+            .all(|(_, accuracy, _)| (accuracy - 4.5).abs() < 1e-9));
+    }
+
+    #[test]
+    fn drift_is_bounded() {
+        let locations = run(with_noise(GpsNoiseModel {
+            drift_step_std_dev: 5.0,
+            max_drift: 8.0,
+            ..quiet_model(1)
+        }));
+
+        let max_error = locations
+            .iter()
+            .map(|(coordinate, _, _)| error(*coordinate))
+            .fold(0.0, f64::max);
+        assert!(max_error > 1.0);
+        // Allow for the approximate conversion from meters to degrees.
+        assert!(max_error <= 8.0 * 1.01);
+    }
+
+    #[test]
+    fn degraded_window_drops_fixes() {
+        let quiet = run(with_noise(quiet_model(3)));
+        let tunnel = run(with_noise(GpsNoiseModel {
+            jitter_std_dev: 1.0,
+            degraded_windows: vec![
+                DegradedSignalWindow {
+                    start_fix: 10,
+                    end_fix: 20,
+                    error_multiplier: 1.0,
+                    dropout_probability: 1.0,
+                },
+                DegradedSignalWindow {
+                    start_fix: 50,
+                    end_fix: 60,
+                    error_multiplier: 10.0,
+                    dropout_probability: 0.0,
+                },
+            ],
+            ..quiet_model(3)
+        }));
+
+        assert_eq!(quiet.len(), tunnel.len() + 10);
+        // The urban canyon reports a worse accuracy.
+        assert!((tunnel[40].1 - 15.0).abs() < 1e-9);
+        assert!((tunnel[39].1 - 1.5).abs() < 1e-9);
+        // The final location is always delivered.
+        let (last, _, _) = tunnel.last().expect("Expected locations");
+        assert!((last.lng - 0.009).abs() < 1e-4);
+    }
+
+    #[test]
+    fn course_noise_and_multipath() {
+        let locations = run(with_noise(GpsNoiseModel {
+            multipath_probability: 0.1,
+            multipath_distance: 50.0,
+            course_std_dev: 10.0,
+            ..quiet_model(5)
+        }));
+
+        let jumps = locations
+            .iter()
+            .filter(|(coordinate, _, _)| error(*coordinate) > 1.0)
+            .count();
+        assert!(jumps > 0 && jumps < 30, "{jumps} multipath jumps");
+        assert!(locations
+            .iter()
+            .any(|(_, _, course)| course.is_some_and(|degrees| degrees != 90)));
+    }
+
+    #[test]
+    fn rejects_invalid_models() {
+        let invalid_models = [
+            GpsNoiseModel {
+                jitter_std_dev: -1.0,
+                ..quiet_model(1)
+            },
+            GpsNoiseModel {
+                max_drift: f64::INFINITY,
+                ..quiet_model(1)
+            },
+            GpsNoiseModel {
+                course_std_dev: f64::NAN,
+                ..quiet_model(1)
+            },
+            GpsNoiseModel {
+                multipath_probability: 1.5,
+                ..quiet_model(1)
+            },
+            GpsNoiseModel {
+                dropout_probability: f64::NAN,
+                ..quiet_model(1)
+            },
+            GpsNoiseModel {
+                degraded_windows: vec![DegradedSignalWindow {
+                    start_fix: 20,
+                    end_fix: 10,
+                    error_multiplier: 1.0,
+                    dropout_probability: 0.0,
+                }],
+                ..quiet_model(1)
+            },
+            GpsNoiseModel {
+                degraded_windows: vec![DegradedSignalWindow {
+                    start_fix: 10,
+                    end_fix: 20,
+                    error_multiplier: 1.0,
+                    dropout_probability: -0.1,
+                }],
+                ..quiet_model(1)
+            },
+        ];
+
+        for model in invalid_models {
+            assert!(
+                matches!(
+                    location_simulation_with_noise(simulation(), model.clone()),
+                    Err(SimulationError::InvalidNoiseModel { .. })
+                ),
+                "{model:?}"
+            );
+        }
+    }
+}
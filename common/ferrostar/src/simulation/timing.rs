@@ -0,0 +1,520 @@
+//! Location simulation which follows the timing of a route.
+//!
+//! The basic simulation moves one (resampled) point along the route each time it is advanced,
+//! which is fine for exercising the navigation logic,
+//! but can't be used to test anything that depends on speed or time (ETAs, speed limits, etc.).
+//!
+//! The simulation in this module instead advances by a fixed amount of simulated time.
+//! The speed along each segment of the route is taken from the `speed` annotation
+//! (as returned by OSRM-compatible routing engines) where available,
+//! and otherwise from the distance and duration of the step.
+//! The simulated vehicle starts from rest, accelerates and brakes at a comfortable rate,
+//! slows down for turns at maneuvers, and comes to a stop at the destination.
+
+use super::{
+    horizontal_accuracy, offset_laterally, LocationBias, LocationSimulationState, SimulationError,
+};
+use crate::algorithms::trunc_float;
+use crate::models::{CourseOverGround, GeographicCoordinate, Route, Speed, UserLocation};
+use geo::{Bearing, Distance, Geodesic, Haversine, InterpolatePoint, Point};
+use std::time::Duration;
+
+#[cfg(any(feature = "wasm-bindgen", test))]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "wasm-bindgen")]
+use wasm_bindgen::{prelude::*, JsValue};
+
+#[cfg(feature = "wasm-bindgen")]
+use tsify::Tsify;
+
+#[cfg(all(feature = "std", not(feature = "web-time")))]
+use std::time::SystemTime;
+
+#[cfg(feature = "web-time")]
+use web_time::SystemTime;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// The speed (in meters per second) used when the route has no timing information (~50 km/h).
+const DEFAULT_SPEED: f64 = 13.9;
+/// The slowest speed (in meters per second) along any segment.
+///
+/// This keeps implausible timing information (ex: a step which takes days)
+/// from stalling the simulation.
+const MINIMUM_SPEED: f64 = 1.0;
+/// The rate at which the simulated vehicle speeds up, in meters per second squared.
+const ACCELERATION: f64 = 1.5;
+/// The rate at which the simulated vehicle slows down, in meters per second squared.
+const DECELERATION: f64 = 2.0;
+/// The maximum speed (in meters per second) through a turn at a maneuver (~18 km/h).
+const TURN_SPEED: f64 = 5.0;
+/// The minimum change in heading (in degrees) at a maneuver which is considered a turn.
+const MINIMUM_TURN_ANGLE: f64 = 30.0;
+/// The vehicle is considered to have arrived when it is closer than this to the destination.
+const ARRIVAL_DISTANCE: f64 = 1.0;
+/// The maximum number of steps a timed simulation may take (over 11 days at one step per second).
+const MAX_STEPS: f64 = 1_000_000.0;
+
+/// A straight section of the route between two consecutive coordinates.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[cfg_attr(any(feature = "wasm-bindgen", test), derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct TimedSegment {
+    start: GeographicCoordinate,
+    end: GeographicCoordinate,
+    length: f64,
+    bearing: f64,
+    /// The distance along the route to the start of this segment.
+    offset: f64,
+    /// The target speed along this segment.
+    speed: f64,
+    /// The maximum speed when entering this segment.
+    entry_speed: f64,
+}
+
+/// The progress of a timed simulation along the route.
+///
+/// Locations are computed one step at a time as the simulation advances,
+/// so the size of this state depends only on the (remaining) route geometry,
+/// not on the duration of the trip.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[cfg_attr(any(feature = "wasm-bindgen", test), derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "wasm-bindgen", derive(Tsify))]
+#[cfg_attr(feature = "wasm-bindgen", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct TimedSimulationState {
+    /// The segments from the current location to the end of the route.
+    segments: Vec<TimedSegment>,
+    /// The simulated time (in seconds) between locations.
+    time_step: f64,
+    /// No constraint further ahead than this (in meters) can slow the vehicle down.
+    braking_horizon: f64,
+    /// The distance travelled along the route.
+    distance: f64,
+    /// The current speed, in meters per second.
+    speed: f64,
+}
+
+impl TimedSimulationState {
+    fn total_distance(&self) -> f64 {
+        self.segments
+            .last()
+            .map_or(0.0, |segment| segment.offset + segment.length)
+    }
+
+    /// Whether the vehicle has arrived at the destination.
+    pub(super) fn is_finished(&self) -> bool {
+        self.distance >= self.total_distance()
+    }
+
+    /// Advances the simulation by one time step.
+    ///
+    /// Returns `None` if the vehicle has already arrived.
+    pub(super) fn advance(
+        &self,
+        previous_location: &UserLocation,
+        bias: &LocationBias,
+        simulation_seed: u64,
+    ) -> Option<(Self, UserLocation)> {
+        if self.is_finished() {
+            return None;
+        }
+        let total_distance = self.total_distance();
+        let (distance, speed) = (self.distance, self.speed);
+
+        // The fastest we can go while still being able to slow down for everything ahead.
+        let mut speed_limit = self.segments[0]
+            .speed
+            .min((2.0 * DECELERATION * (total_distance - distance)).sqrt());
+        for upcoming in &self.segments[1..] {
+            let distance_ahead = upcoming.offset - distance;
+            if distance_ahead > self.braking_horizon {
+                break;
+            }
+            speed_limit = speed_limit
+                .min((upcoming.entry_speed.powi(2) + 2.0 * DECELERATION * distance_ahead).sqrt());
+        }
+
+        let mut next_speed = (speed + ACCELERATION * self.time_step).min(speed_limit);
+        let mut next_distance =
+            (distance + (speed + next_speed) / 2.0 * self.time_step).min(total_distance);
+        if total_distance - next_distance < ARRIVAL_DISTANCE {
+            next_distance = total_distance;
+            next_speed = 0.0;
+        }
+
+        // Drop the segments which have been passed (but always keep the last one).
+        let passed = self.segments[..self.segments.len() - 1]
+            .iter()
+            .take_while(|segment| next_distance >= segment.offset + segment.length)
+            .count();
+        let advanced = Self {
+            segments: self.segments[passed..].to_vec(),
+            distance: next_distance,
+            speed: next_speed,
+            ..*self
+        };
+        let location = location_at(
+            &advanced.segments[0],
+            next_distance,
+            next_speed,
+            previous_location.timestamp + Duration::from_secs_f64(self.time_step),
+            bias,
+            simulation_seed,
+        );
+
+        Some((advanced, location))
+    }
+}
+
+/// Creates a location simulation which follows the timing of a route.
+///
+/// Each time the simulation is advanced, it moves forward by `time_step` seconds of simulated time.
+/// The simulated locations include realistic speeds and monotonically increasing timestamps
+/// (starting from the current time), so this should be advanced in real time
+/// (ex: once per second with a `time_step` of 1.0) for the timestamps to make sense.
+///
+/// Simulations which would take more than a million steps to complete are rejected.
+///
+/// See [`location_simulation_from_coordinates`](super::location_simulation_from_coordinates)
+/// for details of the `seed`.
+#[cfg_attr(feature = "uniffi", uniffi::export(default(seed = None)))]
+pub fn location_simulation_from_route_timing(
+    route: &Route,
+    time_step: f64,
+    bias: LocationBias,
+    seed: Option<u64>,
+) -> Result<LocationSimulationState, SimulationError> {
+    if !(time_step.is_finite() && time_step > 0.0) {
+        return Err(SimulationError::InvalidTimeStep);
+    }
+
+    let segments = segments(route);
+    let Some(first_segment) = segments.first() else {
+        return Err(SimulationError::NotEnoughPoints);
+    };
+
+    // Every segment is travelled at (no more than) its target speed,
+    // plus time to speed up and slow down, so this is a lower bound.
+    let minimum_steps = segments
+        .iter()
+        .map(|segment| segment.length / segment.speed)
+        .sum::<f64>()
+        / time_step;
+    if minimum_steps > MAX_STEPS {
+        return Err(SimulationError::TooManySteps {
+            steps: minimum_steps as u64,
+        });
+    }
+
+    let seed = seed.unwrap_or_else(|| super::seed_from_coordinates(&route.geometry));
+    let current_location = location_at(first_segment, 0.0, 0.0, SystemTime::now(), &bias, seed);
+    let braking_horizon = segments
+        .iter()
+        .map(|segment| segment.speed.powi(2) / (2.0 * DECELERATION))
+        .fold(0.0, f64::max);
+
+    Ok(LocationSimulationState {
+        current_location,
+        remaining_locations: Vec::new(),
+        remaining_recorded_locations: Vec::new(),
+        timed: Some(TimedSimulationState {
+            segments,
+            time_step,
+            braking_horizon,
+            distance: 0.0,
+            speed: 0.0,
+        }),
+        bias,
+        seed,
+        noise: None,
+    })
+}
+
+/// Splits the route into segments, along with their target speeds.
+fn segments(route: &Route) -> Vec<TimedSegment> {
+    let mut segments: Vec<TimedSegment> = Vec::new();
+    let mut offset = 0.0;
+
+    for step in &route.steps {
+        let step_speed =
+            (step.distance > 0.0 && step.duration > 0.0).then(|| step.distance / step.duration);
+        let mut first_in_step = true;
+
+        for (index, pair) in step.geometry.windows(2).enumerate() {
+            let (start, end) = (Point::from(pair[0]), Point::from(pair[1]));
+            let length = Haversine.distance(start, end);
+            if length <= 0.0 {
+                continue;
+            }
+
+            let bearing = Geodesic.bearing(start, end);
+            let speed = annotated_speed(step.annotations.as_deref(), index)
+                .or(step_speed)
+                .unwrap_or(DEFAULT_SPEED)
+                .max(MINIMUM_SPEED);
+            let entry_speed = match segments.last() {
+                Some(previous)
+                    if first_in_step
+                        && turn_angle(previous.bearing, bearing) >= MINIMUM_TURN_ANGLE =>
+                {
+                    speed.min(TURN_SPEED)
+                }
+                _ => speed,
+            };
+
+            segments.push(TimedSegment {
+                start: pair[0],
+                end: pair[1],
+                length,
+                bearing,
+                offset,
+                speed,
+                entry_speed,
+            });
+            offset += length;
+            first_in_step = false;
+        }
+    }
+
+    segments
+}
+
+/// Reads the speed (in meters per second) for a segment of a step from its annotations.
+fn annotated_speed(annotations: Option<&[String]>, index: usize) -> Option<f64> {
+    let annotation: serde_json::Value = serde_json::from_str(annotations?.get(index)?).ok()?;
+    annotation
+        .get("speed")?
+        .as_f64()
+        .filter(|speed| speed.is_finite() && *speed > 0.0)
+}
+
+/// The absolute change in heading between two bearings, in degrees.
+fn turn_angle(from: f64, to: f64) -> f64 {
+    let difference = (to - from).rem_euclid(360.0);
+    difference.min(360.0 - difference)
+}
+
+fn location_at(
+    segment: &TimedSegment,
+    distance: f64,
+    speed: f64,
+    timestamp: SystemTime,
+    bias: &LocationBias,
+    simulation_seed: u64,
+) -> UserLocation {
+    let distance_along_segment = (distance - segment.offset).clamp(0.0, segment.length);
+    let point = Haversine.point_at_distance_between(
+        Point::from(segment.start),
+        Point::from(segment.end),
+        distance_along_segment,
+    );
+    let coordinates = GeographicCoordinate {
+        lat: trunc_float(point.y(), 6),
+        lng: trunc_float(point.x(), 6),
+    };
+
+    UserLocation {
+        coordinates: offset_laterally(coordinates, segment.bearing, bias, simulation_seed),
+        horizontal_accuracy: horizontal_accuracy(bias),
+        course_over_ground: Some(CourseOverGround::new(segment.bearing, Some(5))),
+        timestamp,
+        speed: Some(Speed {
+            value: speed,
+            accuracy: None,
+        }),
+    }
+}
+
+/// JavaScript wrapper for `location_simulation_from_route_timing`.
+#[cfg(feature = "wasm-bindgen")]
+#[wasm_bindgen(js_name = locationSimulationFromRouteTiming)]
+pub fn js_location_simulation_from_route_timing(
+    route: JsValue,
+    time_step: f64,
+    bias: LocationBias,
+    seed: Option<u64>,
+) -> Result<JsValue, JsValue> {
+    let route: Route = serde_wasm_bindgen::from_value(route)
+        .map_err(|error| JsValue::from_str(&error.to_string()))?;
+
+    location_simulation_from_route_timing(&route, time_step, bias, seed)
+        .map(|state| serde_wasm_bindgen::to_value(&state).unwrap())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::navigation_controller::test_helpers::{
+        gen_dummy_route_step, gen_route_from_steps, get_test_route, TestRoute,
+    };
+    use crate::simulation::advance_location_simulation;
+
+    /// Runs the simulation to completion, returning every simulated location.
+    fn simulate(route: &Route, time_step: f64) -> Vec<UserLocation> {
+        let mut state =
+            location_simulation_from_route_timing(route, time_step, LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let mut locations = vec![state.current_location];
+        loop {
+            let new_state = advance_location_simulation(&state);
+            if new_state == state {
+                return locations;
+            }
+            state = new_state;
+            locations.push(state.current_location);
+        }
+    }
+
+    fn speeds(locations: &[UserLocation]) -> Vec<f64> {
+        locations
+            .iter()
+            .map(|location| location.speed.expect("Expected a speed").value)
+            .collect()
+    }
+
+    /// A route with a right angle turn between two 1km steps, at 20 m/s.
+    fn right_angle_route() -> Route {
+        let mut steps = vec![
+            gen_dummy_route_step(0.0, 0.0, 0.0, 0.009),
+            gen_dummy_route_step(0.0, 0.009, 0.009, 0.009),
+        ];
+        for step in &mut steps {
+            step.duration = step.distance / 20.0;
+        }
+        gen_route_from_steps(steps)
+    }
+
+    #[test]
+    fn follows_route_timing() {
+        let route = get_test_route(TestRoute::Extended);
+        let locations = simulate(&route, 1.0);
+
+        for pair in locations.windows(2) {
+            assert_eq!(
+                pair[1].timestamp.duration_since(pair[0].timestamp).ok(),
+                Some(Duration::from_secs(1))
+            );
+        }
+
+        let speeds = speeds(&locations);
+        assert_eq!(speeds.first(), Some(&0.0));
+        assert_eq!(speeds.last(), Some(&0.0));
+        assert!(speeds.iter().all(|speed| *speed >= 0.0));
+
+        let destination = route.geometry.last().expect("Expected a route geometry");
+        let arrival = locations.last().expect("Expected locations").coordinates;
+        assert!((arrival.lat - destination.lat).abs() < 1e-5);
+        assert!((arrival.lng - destination.lng).abs() < 1e-5);
+
+        // Acceleration and turns add some time, but the trip should take about as long as expected.
+        let expected_duration: f64 = route.steps.iter().map(|step| step.duration).sum();
+        let simulated_duration = (locations.len() - 1) as f64;
+        assert!(simulated_duration >= expected_duration * 0.9);
+        assert!(simulated_duration <= expected_duration * 1.5);
+    }
+
+    #[test]
+    fn slows_down_for_turns() {
+        let locations = simulate(&right_angle_route(), 1.0);
+        let speeds = speeds(&locations);
+        let max_speed = speeds.iter().copied().fold(0.0, f64::max);
+        assert!((max_speed - 20.0).abs() < 1e-9);
+
+        // Find the slowest point between the start and the end.
+        let turn_index = locations
+            .iter()
+            .position(|location| location.coordinates.lng > 0.0)
+            .expect("Expected to turn");
+        let slowest_through_turn = speeds[turn_index - 2..=turn_index + 1]
+            .iter()
+            .copied()
+            .fold(f64::INFINITY, f64::min);
+        assert!(slowest_through_turn <= TURN_SPEED + ACCELERATION);
+    }
+
+    #[test]
+    fn uses_speed_annotations() {
+        let mut route = right_angle_route();
+        route.steps[1].annotations = Some(vec![r#"{"speed": 8.0}"#.to_string()]);
+
+        let locations = simulate(&route, 0.5);
+        let speeds = speeds(&locations);
+
+        let (before_turn, after_turn): (Vec<_>, Vec<_>) = locations
+            .iter()
+            .zip(&speeds)
+            .partition(|(location, _)| location.coordinates.lng <= 0.0);
+        assert!(before_turn.iter().any(|(_, speed)| **speed > 8.0));
+        assert!(after_turn.iter().all(|(_, speed)| **speed <= 8.0));
+    }
+
+    #[test]
+    fn state_does_not_grow_with_trip_duration() {
+        let route = right_angle_route();
+        let mut state =
+            location_simulation_from_route_timing(&route, 0.01, LocationBias::None, None)
+                .expect("Unable to create simulation");
+        let segment_count = |state: &LocationSimulationState| {
+            state
+                .timed
+                .as_ref()
+                .map(|timed| timed.segments.len())
+                .expect("Expected a timed simulation")
+        };
+        let initial_segments = segment_count(&state);
+        assert_eq!(initial_segments, 2);
+
+        let mut steps = 0;
+        loop {
+            let new_state = advance_location_simulation(&state);
+            if new_state == state {
+                break;
+            }
+            assert!(segment_count(&new_state) <= initial_segments);
+            state = new_state;
+            steps += 1;
+        }
+        // About 100s of driving, in steps of 10ms.
+        assert!(steps > 9_000);
+        assert_eq!(segment_count(&state), 1);
+    }
+
+    #[test]
+    fn slow_steps_are_floored() {
+        let mut route = right_angle_route();
+        // A step which would take a year.
+        route.steps[0].duration = 365.0 * 24.0 * 60.0 * 60.0;
+
+        let locations = simulate(&route, 1.0);
+        let speeds = speeds(&locations);
+        assert!(speeds[1..speeds.len() - 1]
+            .iter()
+            .all(|speed| *speed >= MINIMUM_SPEED.min(ACCELERATION)));
+    }
+
+    #[test]
+    fn rejects_too_many_steps() {
+        let route = right_angle_route();
+        assert!(matches!(
+            location_simulation_from_route_timing(&route, 1e-6, LocationBias::None, None),
+            Err(SimulationError::TooManySteps { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_time_step() {
+        let route = right_angle_route();
+        for time_step in [0.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                location_simulation_from_route_timing(&route, time_step, LocationBias::None, None),
+                Err(SimulationError::InvalidTimeStep)
+            ));
+        }
+    }
+}
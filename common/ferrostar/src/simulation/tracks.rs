@@ -0,0 +1,554 @@
+//! Location simulation from tracks recorded by other devices.
+//!
+//! This supports GPX track files and NMEA 0183 sentence logs.
+//! All values are taken from the recording as-is; nothing is interpolated or made up.
+//! Where a value is not present in the recording,
+//! it is left empty (or zero for the horizontal accuracy, which is not optional).
+
+use super::{location_simulation_from_locations, LocationSimulationState, SimulationError};
+use crate::models::{CourseOverGround, GeographicCoordinate, Speed, UserLocation};
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use std::time::Duration;
+
+#[cfg(feature = "wasm-bindgen")]
+use wasm_bindgen::{prelude::*, JsValue};
+
+#[cfg(all(feature = "std", not(feature = "web-time")))]
+use std::time::SystemTime;
+
+#[cfg(feature = "web-time")]
+use web_time::SystemTime;
+
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String, vec::Vec};
+
+/// Knots to meters per second.
+const METERS_PER_SECOND_PER_KNOT: f64 = 1852.0 / 3600.0;
+
+/// Creates a location simulation which plays back the track points in a GPX file.
+///
+/// Every track point must have a `time`.
+/// The speed (in meters per second) and course (in degrees) are read from `speed` and `course`
+/// elements, including namespaced variants like `gpxtpx:speed` in the `extensions` of a point.
+/// The horizontal accuracy (in meters) is read from an `accuracy` or `hacc` element if present.
+/// Note that `hdop` is *not* used, as it is a unitless dilution of precision.
+///
+/// Truncated files (ex: from a logger which crashed) are rejected
+/// rather than silently playing back only part of the track.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+pub fn location_simulation_from_gpx(gpx: &str) -> Result<LocationSimulationState, SimulationError> {
+    location_simulation_from_locations(&user_locations_from_gpx(gpx)?)
+}
+
+/// Creates a location simulation which plays back a log of NMEA 0183 sentences.
+///
+/// Positions, speeds and courses are read from RMC sentences (from any talker),
+/// and the horizontal accuracy from GST sentences, if the receiver emits them.
+/// Sentences with an invalid checksum, and RMC sentences without a valid fix, are skipped.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+pub fn location_simulation_from_nmea(
+    nmea: &str,
+) -> Result<LocationSimulationState, SimulationError> {
+    location_simulation_from_locations(&user_locations_from_nmea(nmea)?)
+}
+
+/// Parses the track points in a GPX file.
+///
+/// See [`location_simulation_from_gpx`] for details.
+pub fn user_locations_from_gpx(gpx: &str) -> Result<Vec<UserLocation>, SimulationError> {
+    elements(gpx, "trkpt")
+        .enumerate()
+        .map(|(index, element)| {
+            let (start_tag, content) = element?;
+            let coordinate = |name| {
+                attribute(start_tag, name)
+                    .and_then(|value| value.trim().parse::<f64>().ok())
+                    .ok_or_else(|| {
+                        track_error(format!("Track point {index} has no valid {name} attribute"))
+                    })
+            };
+            let number = |name| -> Result<Option<f64>, SimulationError> {
+                Ok(child_text(content, name)?.and_then(|value| value.trim().parse::<f64>().ok()))
+            };
+
+            let time = child_text(content, "time")?
+                .ok_or_else(|| track_error(format!("Track point {index} has no time")))?;
+
+            Ok(UserLocation {
+                coordinates: GeographicCoordinate {
+                    lat: coordinate("lat")?,
+                    lng: coordinate("lon")?,
+                },
+                horizontal_accuracy: number("accuracy")?.or(number("hacc")?).unwrap_or(0.0),
+                course_over_ground: number("course")?.and_then(course_over_ground),
+                timestamp: parse_rfc3339(time)?,
+                speed: number("speed")?.map(|value| Speed {
+                    value,
+                    accuracy: None,
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Parses the fixes in a log of NMEA 0183 sentences.
+///
+/// See [`location_simulation_from_nmea`] for details.
+pub fn user_locations_from_nmea(nmea: &str) -> Result<Vec<UserLocation>, SimulationError> {
+    /// Everything reported by the receiver for a single point in time.
+    struct Fix<'a> {
+        time: &'a str,
+        location: Option<UserLocation>,
+        accuracy: Option<f64>,
+    }
+
+    let mut fixes: Vec<Fix> = Vec::new();
+    for fields in nmea.lines().filter_map(nmea_fields) {
+        let (Some(sentence), Some(time)) = (fields.first(), fields.get(1)) else {
+            continue;
+        };
+        let field = |index: usize| fields.get(index).copied().unwrap_or_default();
+
+        if fixes.last().is_none_or(|fix| fix.time != *time) {
+            fixes.push(Fix {
+                time,
+                location: None,
+                accuracy: None,
+            });
+        }
+        let Some(fix) = fixes.last_mut() else {
+            continue;
+        };
+
+        if sentence.ends_with("RMC") && field(2) == "A" {
+            let (Some(lat), Some(lng)) = (
+                nmea_coordinate(field(3), field(4)),
+                nmea_coordinate(field(5), field(6)),
+            ) else {
+                continue;
+            };
+            let timestamp = nmea_timestamp(field(9), time)
+                .ok_or_else(|| track_error(format!("Invalid date or time: {} {time}", field(9))))?;
+
+            fix.location = Some(UserLocation {
+                coordinates: GeographicCoordinate { lat, lng },
+                horizontal_accuracy: 0.0,
+                course_over_ground: field(8).parse().ok().and_then(course_over_ground),
+                timestamp,
+                speed: field(7).parse::<f64>().ok().map(|knots| Speed {
+                    value: knots * METERS_PER_SECOND_PER_KNOT,
+                    accuracy: None,
+                }),
+            });
+        } else if sentence.ends_with("GST") {
+            // The standard deviations of the latitude and longitude errors, in meters.
+            if let (Ok(lat_error), Ok(lng_error)) =
+                (field(6).parse::<f64>(), field(7).parse::<f64>())
+            {
+                fix.accuracy = Some(lat_error.hypot(lng_error));
+            }
+        }
+    }
+
+    Ok(fixes
+        .into_iter()
+        .filter_map(|fix| {
+            fix.location.map(|location| UserLocation {
+                horizontal_accuracy: fix.accuracy.unwrap_or(location.horizontal_accuracy),
+                ..location
+            })
+        })
+        .collect())
+}
+
+/// Splits an NMEA sentence into fields (excluding the checksum).
+///
+/// Returns `None` if the line is not a sentence, or has an invalid checksum.
+fn nmea_fields(line: &str) -> Option<Vec<&str>> {
+    let sentence = line.trim().strip_prefix('$')?;
+    let body = match sentence.split_once('*') {
+        Some((body, checksum)) => {
+            let expected = u8::from_str_radix(checksum.trim(), 16).ok()?;
+            let actual = body.bytes().fold(0, |checksum, byte| checksum ^ byte);
+            (expected == actual).then_some(body)?
+        }
+        None => sentence,
+    };
+
+    Some(body.split(',').collect())
+}
+
+/// Parses an NMEA coordinate (`dddmm.mmmm`) with its hemisphere.
+fn nmea_coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let value: f64 = value.parse().ok()?;
+    let degrees = (value / 100.0).trunc();
+    let coordinate = degrees + (value - degrees * 100.0) / 60.0;
+
+    match hemisphere {
+        "N" | "E" => Some(coordinate),
+        "S" | "W" => Some(-coordinate),
+        _ => None,
+    }
+}
+
+/// Parses an NMEA date (`ddmmyy`) and UTC time (`hhmmss.ss`).
+fn nmea_timestamp(date: &str, time: &str) -> Option<SystemTime> {
+    let field = |range: std::ops::Range<usize>| date.get(range)?.parse::<u32>().ok();
+    let year = field(4..6)?;
+    // NMEA only has two digit years; assume that nothing was recorded before 1980.
+    let year = if year < 80 { 2000 + year } else { 1900 + year };
+    let date = NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, field(2..4)?, field(0..2)?)?;
+    let time = NaiveTime::parse_from_str(time, "%H%M%S%.f").ok()?;
+
+    system_time_from_millis(date.and_time(time).and_utc().timestamp_millis())
+}
+
+fn parse_rfc3339(time: &str) -> Result<SystemTime, SimulationError> {
+    DateTime::parse_from_rfc3339(time.trim())
+        .ok()
+        .and_then(|time| system_time_from_millis(time.timestamp_millis()))
+        .ok_or_else(|| track_error(format!("Invalid time: {time}")))
+}
+
+fn system_time_from_millis(millis: i64) -> Option<SystemTime> {
+    let millis = u64::try_from(millis).ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_millis(millis))
+}
+
+/// Normalizes a recorded course to whole degrees in the range [0, 360).
+///
+/// Returns `None` if the course is not a finite number.
+fn course_over_ground(degrees: f64) -> Option<CourseOverGround> {
+    // Rounding first ensures that values just below 360 (or zero) wrap around to zero,
+    // rather than being rounded up to 360 afterwards.
+    let degrees = degrees.round().rem_euclid(360.0);
+    degrees
+        .is_finite()
+        .then(|| CourseOverGround::new(degrees, None))
+}
+
+fn track_error(error: String) -> SimulationError {
+    SimulationError::TrackError { error }
+}
+
+/// Finds all XML elements with the given local name (ignoring any namespace prefix).
+///
+/// Yields the start tag (for reading attributes) and the content of each element.
+/// This is not a general purpose XML parser, but is sufficient for well-formed GPX files.
+/// If a tag is not closed (ex: because the file was truncated), an error is yielded
+/// and iteration stops, so that a partial file is never mistaken for a complete one.
+fn elements<'a>(
+    xml: &'a str,
+    local_name: &'a str,
+) -> impl Iterator<Item = Result<(&'a str, &'a str), SimulationError>> {
+    let mut rest = Some(xml);
+    core::iter::from_fn(move || loop {
+        let xml = rest?;
+        let Some(start) = xml.find('<') else {
+            rest = None;
+            return None;
+        };
+        let Some(tag_len) = xml[start..].find('>').map(|end| end + 1) else {
+            rest = None;
+            return Some(Err(track_error(format!(
+                "Unterminated tag at the end of the file: {}",
+                &xml[start..]
+            ))));
+        };
+        let start_tag = &xml[start..start + tag_len];
+        let xml = &xml[start + tag_len..];
+        rest = Some(xml);
+
+        let name = start_tag[1..]
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or_default();
+        if name.rsplit(':').next() != Some(local_name) {
+            continue;
+        }
+        if start_tag.ends_with("/>") {
+            return Some(Ok((start_tag, "")));
+        }
+
+        let end_tag = format!("</{name}>");
+        let Some(end) = xml.find(&end_tag) else {
+            rest = None;
+            return Some(Err(track_error(format!(
+                "Missing closing tag for <{name}>"
+            ))));
+        };
+        rest = Some(&xml[end + end_tag.len()..]);
+        return Some(Ok((start_tag, &xml[..end])));
+    })
+}
+
+/// Finds the text of the first element with the given local name.
+fn child_text<'a>(xml: &'a str, local_name: &'a str) -> Result<Option<&'a str>, SimulationError> {
+    Ok(elements(xml, local_name)
+        .next()
+        .transpose()?
+        .map(|(_, content)| content)
+        .filter(|content| !content.trim().is_empty()))
+}
+
+/// Reads an attribute from an XML start tag.
+fn attribute<'a>(start_tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = start_tag;
+    while let Some(index) = rest.find(name) {
+        let preceded_by_whitespace = rest[..index].ends_with(char::is_whitespace);
+        rest = &rest[index + name.len()..];
+        if !preceded_by_whitespace {
+            continue;
+        }
+        let Some(value) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+
+    None
+}
+
+/// JavaScript wrapper for `location_simulation_from_gpx`.
+#[cfg(feature = "wasm-bindgen")]
+#[wasm_bindgen(js_name = locationSimulationFromGpx)]
+pub fn js_location_simulation_from_gpx(gpx: &str) -> Result<JsValue, JsValue> {
+    location_simulation_from_gpx(gpx)
+        .map(|state| serde_wasm_bindgen::to_value(&state).unwrap())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+/// JavaScript wrapper for `location_simulation_from_nmea`.
+#[cfg(feature = "wasm-bindgen")]
+#[wasm_bindgen(js_name = locationSimulationFromNmea)]
+pub fn js_location_simulation_from_nmea(nmea: &str) -> Result<JsValue, JsValue> {
+    location_simulation_from_nmea(nmea)
+        .map(|state| serde_wasm_bindgen::to_value(&state).unwrap())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::advance_location_simulation;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1"
+     xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v2">
+  <metadata><time>2024-05-01T12:00:00Z</time></metadata>
+  <trk>
+    <name>Test drive</name>
+    <trkseg>
+      <trkpt lat="47.5" lon="8.25">
+        <ele>400</ele>
+        <time>2024-05-01T12:00:00Z</time>
+        <extensions>
+          <gpxtpx:TrackPointExtension>
+            <gpxtpx:speed>12.5</gpxtpx:speed>
+            <gpxtpx:course>90.4</gpxtpx:course>
+          </gpxtpx:TrackPointExtension>
+          <accuracy>4.5</accuracy>
+        </extensions>
+      </trkpt>
+      <trkpt lon='8.2501' lat='47.5001'><time>2024-05-01T12:00:01.500+00:00</time></trkpt>
+      <trkpt lat="47.5002" lon="8.2502">
+        <time>2024-05-01T14:00:02+02:00</time>
+        <speed>13</speed>
+        <course>-10</course>
+      </trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+
+    fn timestamp(millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    /// Appends the checksum to an NMEA sentence body.
+    fn nmea_sentence(body: &str) -> String {
+        let checksum = body.bytes().fold(0, |checksum, byte| checksum ^ byte);
+        format!("${body}*{checksum:02X}")
+    }
+
+    #[test]
+    fn parses_gpx_track_points() {
+        let locations = user_locations_from_gpx(GPX).expect("Unable to parse GPX");
+
+        assert_eq!(
+            locations,
+            vec![
+                UserLocation {
+                    coordinates: GeographicCoordinate {
+                        lat: 47.5,
+                        lng: 8.25
+                    },
+                    horizontal_accuracy: 4.5,
+                    course_over_ground: Some(CourseOverGround {
+                        degrees: 90,
+                        accuracy: None
+                    }),
+                    timestamp: timestamp(1_714_564_800_000),
+                    speed: Some(Speed {
+                        value: 12.5,
+                        accuracy: None
+                    }),
+                },
+                UserLocation {
+                    coordinates: GeographicCoordinate {
+                        lat: 47.5001,
+                        lng: 8.2501
+                    },
+                    horizontal_accuracy: 0.0,
+                    course_over_ground: None,
+                    timestamp: timestamp(1_714_564_801_500),
+                    speed: None,
+                },
+                UserLocation {
+                    coordinates: GeographicCoordinate {
+                        lat: 47.5002,
+                        lng: 8.2502
+                    },
+                    horizontal_accuracy: 0.0,
+                    course_over_ground: Some(CourseOverGround {
+                        degrees: 350,
+                        accuracy: None
+                    }),
+                    timestamp: timestamp(1_714_564_802_000),
+                    speed: Some(Speed {
+                        value: 13.0,
+                        accuracy: None
+                    }),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn simulation_plays_back_gpx_exactly() {
+        let locations = user_locations_from_gpx(GPX).expect("Unable to parse GPX");
+        let mut state = location_simulation_from_gpx(GPX).expect("Unable to create simulation");
+
+        let mut simulated = vec![state.current_location];
+        loop {
+            let new_state = advance_location_simulation(&state);
+            if new_state == state {
+                break;
+            }
+            state = new_state;
+            simulated.push(state.current_location);
+        }
+
+        assert_eq!(simulated, locations);
+    }
+
+    #[test]
+    fn rejects_gpx_without_times() {
+        let gpx = r#"<gpx><trk><trkseg>
+            <trkpt lat="1" lon="2"></trkpt>
+            <trkpt lat="1" lon="3"></trkpt>
+        </trkseg></trk></gpx>"#;
+
+        assert!(matches!(
+            location_simulation_from_gpx(gpx),
+            Err(SimulationError::TrackError { .. })
+        ));
+        assert!(matches!(
+            location_simulation_from_gpx("<gpx></gpx>"),
+            Err(SimulationError::NotEnoughPoints)
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_gpx() {
+        let complete = user_locations_from_gpx(GPX).expect("Unable to parse GPX");
+        assert_eq!(complete.len(), 3);
+
+        // Cut off in the middle of the second track point.
+        let truncated = &GPX[..GPX.find("2024-05-01T12:00:01").expect("Missing time")];
+        assert!(matches!(
+            user_locations_from_gpx(truncated),
+            Err(SimulationError::TrackError { .. })
+        ));
+
+        // Cut off in the middle of a tag.
+        let truncated = &GPX[..GPX.find("lat='47.5001'").expect("Missing point")];
+        assert!(matches!(
+            user_locations_from_gpx(truncated),
+            Err(SimulationError::TrackError { .. })
+        ));
+    }
+
+    #[test]
+    fn normalizes_courses() {
+        assert_eq!(
+            course_over_ground(-1e-14).map(|course| course.degrees),
+            Some(0)
+        );
+        assert_eq!(
+            course_over_ground(359.7).map(|course| course.degrees),
+            Some(0)
+        );
+        assert_eq!(
+            course_over_ground(-90.0).map(|course| course.degrees),
+            Some(270)
+        );
+        assert_eq!(
+            course_over_ground(720.2).map(|course| course.degrees),
+            Some(0)
+        );
+        assert_eq!(course_over_ground(f64::NAN), None);
+        assert_eq!(course_over_ground(f64::INFINITY), None);
+    }
+
+    #[test]
+    fn parses_nmea_log() {
+        let nmea = [
+            // The classic example sentence, with its published checksum.
+            "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A".to_string(),
+            nmea_sentence("GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,"),
+            nmea_sentence("GPGST,123519,0.006,0.023,0.020,273.6,3.0,4.0,5.3"),
+            // A fix from a different talker, without course or accuracy.
+            nmea_sentence("GNRMC,123520.50,A,4807.040,N,01131.010,E,0.0,,230394,,,A"),
+            // A corrupt sentence (bad checksum) is skipped.
+            "$GPRMC,123521,A,4907.038,N,01131.000,E,022.4,084.4,230394,003.1,W*00".to_string(),
+            // No valid fix.
+            nmea_sentence("GPRMC,123522,V,,,,,,,230394,,,N"),
+            // Southern and western hemispheres.
+            nmea_sentence("GPRMC,123523,A,3352.000,S,15112.000,W,1.0,180.0,230394,,"),
+        ]
+        .join("\r\n");
+
+        let locations = user_locations_from_nmea(&nmea).expect("Unable to parse NMEA");
+
+        assert_eq!(locations.len(), 3);
+        let first = locations[0];
+        assert!((first.coordinates.lat - (48.0 + 7.038 / 60.0)).abs() < 1e-9);
+        assert!((first.coordinates.lng - (11.0 + 31.0 / 60.0)).abs() < 1e-9);
+        assert_eq!(first.horizontal_accuracy, 5.0);
+        assert_eq!(
+            first.course_over_ground,
+            Some(CourseOverGround {
+                degrees: 84,
+                accuracy: None
+            })
+        );
+        assert!(first
+            .speed
+            .is_some_and(|speed| (speed.value - 22.4 * METERS_PER_SECOND_PER_KNOT).abs() < 1e-9));
+        // 1994-03-23T12:35:19Z
+        assert_eq!(first.timestamp, timestamp(764_426_119_000));
+
+        let second = locations[1];
+        assert_eq!(second.timestamp, timestamp(764_426_120_500));
+        assert_eq!(second.course_over_ground, None);
+        assert_eq!(second.horizontal_accuracy, 0.0);
+
+        let third = locations[2];
+        assert!((third.coordinates.lat + (33.0 + 52.0 / 60.0)).abs() < 1e-9);
+        assert!((third.coordinates.lng + (151.0 + 12.0 / 60.0)).abs() < 1e-9);
+    }
+}